@@ -0,0 +1,74 @@
+/*
+ * C interface to the headless training environment in the tetris library (src/rl/ffi.rs).
+ * Build the library with `cargo build --release` and link against libtetris.
+ * Environments share no state, so each one may be driven from its own thread.
+ */
+#ifndef TETRIS_ENV_H
+#define TETRIS_ENV_H
+
+#include <stdint.h>
+
+#define TETRIS_ENV_ABI_VERSION 1
+#define TETRIS_ENV_ROWS 20
+#define TETRIS_ENV_COLUMNS 10
+#define TETRIS_ENV_MAX_PREVIEWS 5
+
+#define TETRIS_ENV_ACTION_SPACE_INPUT 0
+#define TETRIS_ENV_ACTION_SPACE_PLACEMENT 1
+
+#define TETRIS_ENV_RULES_STANDARD 0
+#define TETRIS_ENV_RULES_GUIDELINE 1
+#define TETRIS_ENV_RULES_NES 2
+#define TETRIS_ENV_RULES_ARCADE 3
+
+/* Presses in the input action space; -1 lets a frame pass. */
+#define TETRIS_ENV_NO_INPUT -1
+#define TETRIS_ENV_MOVE_LEFT 0
+#define TETRIS_ENV_MOVE_RIGHT 1
+#define TETRIS_ENV_SOFT_DROP 2
+#define TETRIS_ENV_ROTATE_CLOCKWISE 3
+#define TETRIS_ENV_ROTATE_COUNTER_CLOCKWISE 4
+#define TETRIS_ENV_HOLD 5
+
+typedef struct TetrisEnv TetrisEnv;
+
+typedef struct {
+    uint32_t action_space;
+    uint32_t rules;
+    float line_reward;
+    float score_reward;
+    float survival_reward;
+    float game_over_reward;
+} TetrisEnvConfig;
+
+/* Cells are row by row from the top: 0 empty, 1 to 7 for I, J, L, O, S, T, Z and 8 for garbage.
+ * Piece cells are (column, row) pairs in the same grid; rows can be negative while it enters. */
+typedef struct {
+    uint8_t board[TETRIS_ENV_ROWS * TETRIS_ENV_COLUMNS];
+    uint8_t piece;
+    int8_t piece_cells[4][2];
+    uint8_t queue[TETRIS_ENV_MAX_PREVIEWS];
+    uint8_t queue_length;
+    uint8_t hold;
+    uint8_t hold_available;
+} TetrisObservation;
+
+typedef struct {
+    uint32_t cleaned_lines;
+    uint32_t lines;
+    uint32_t score;
+    uint32_t level;
+    uint32_t pieces;
+    uint32_t frames;
+} TetrisStepInfo;
+
+uint32_t tetris_env_abi_version(void);
+TetrisEnv *tetris_env_new(const TetrisEnvConfig *config);
+void tetris_env_free(TetrisEnv *env);
+int32_t tetris_env_reset(TetrisEnv *env, uint32_t seed, TetrisObservation *observation);
+int32_t tetris_env_step(TetrisEnv *env, int32_t action, TetrisObservation *observation, float *reward,
+                        TetrisStepInfo *info);
+uint32_t tetris_env_placement_count(const TetrisEnv *env);
+int32_t tetris_env_placement_board(const TetrisEnv *env, uint32_t index, uint8_t *board);
+
+#endif
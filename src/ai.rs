@@ -0,0 +1,331 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use rand::Rng;
+use crate::controls::{Action, Controller};
+use crate::game::GamePlayState;
+use crate::pathfinding::{placements, Placement};
+use crate::world::Board;
+
+/// Where the evaluator would put the active piece, and whether it would hold first to play the
+/// held piece, or the next one when nothing is held, there instead.
+#[derive(Debug, Clone)]
+pub struct Move {
+    pub hold: bool,
+    pub placement: Placement,
+}
+
+/// The runners-up a mistake picks from: the second best move to this one.
+const MISTAKE_CHOICES: usize = 4;
+
+/// How well a computer opponent plays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Expert,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Expert];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "EASY",
+            Difficulty::Normal => "NORMAL",
+            Difficulty::Hard => "HARD",
+            Difficulty::Expert => "EXPERT",
+        }
+    }
+
+    /// The most pieces it places a second, or None for as fast as it can press.
+    pub fn pieces_per_second(&self) -> Option<f32> {
+        match self {
+            Difficulty::Easy => Some(0.4),
+            Difficulty::Normal => Some(0.6),
+            Difficulty::Hard => Some(0.8),
+            Difficulty::Expert => None,
+        }
+    }
+
+    /// Pieces it looks at when choosing a move: the active one alone, or the next one as well.
+    pub fn search_depth(&self) -> u8 {
+        match self {
+            Difficulty::Easy | Difficulty::Normal => 1,
+            Difficulty::Hard | Difficulty::Expert => 2,
+        }
+    }
+
+    /// The chance of it choosing one of the runners-up instead of the best move.
+    pub fn error_rate(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.2,
+            Difficulty::Normal => 0.15,
+            Difficulty::Hard => 0.05,
+            Difficulty::Expert => 0.0,
+        }
+    }
+
+    /// Frames it takes to react to a new piece before its first press.
+    pub fn reaction_frames(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 24,
+            Difficulty::Normal => 16,
+            Difficulty::Hard => 10,
+            Difficulty::Expert => 0,
+        }
+    }
+
+    /// Frames it waits between presses, so it plays at a speed people can follow.
+    pub fn action_delay(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 6,
+            Difficulty::Normal => 4,
+            Difficulty::Hard => 3,
+            Difficulty::Expert => 0,
+        }
+    }
+}
+
+/// How much each feature of a board counts towards its evaluation. Lines are a reward; every
+/// other feature is a penalty.
+#[derive(Debug, Clone, Copy)]
+pub struct Weights {
+    pub lines: f32,
+    pub aggregate_height: f32,
+    pub holes: f32,
+    pub bumpiness: f32,
+    pub wells: f32,
+    pub row_transitions: f32,
+    pub column_transitions: f32,
+}
+
+impl Weights {
+    pub fn new() -> Weights {
+        Weights {
+            lines: 3.4,
+            aggregate_height: -0.51,
+            holes: -7.9,
+            bumpiness: -0.18,
+            wells: -3.4,
+            row_transitions: -3.2,
+            column_transitions: -9.3,
+        }
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights::new()
+    }
+}
+
+/// The best move for the active piece, judged over the board, the queue and the hold, looking one
+/// piece ahead. None when no piece is in play.
+pub fn best_move(state: &GamePlayState, weights: &Weights) -> Option<Move> {
+    ranked_moves(state, weights, 2).into_iter().next()
+}
+
+/// Every move for the active piece, best first, looking `depth` pieces ahead counting the active
+/// one, which is one or two.
+pub fn ranked_moves(state: &GamePlayState, weights: &Weights, depth: u8) -> Vec<Move> {
+    if !state.piece_active() {
+        return Vec::new();
+    }
+    let rotation = state.config.rules.rotation;
+    let mut choices = vec![(false, state.tetrimino.clone(), state.queue.front())];
+    if state.hold_enabled && !state.hold_used {
+        let (held, next) = match state.hold {
+            Some(held) => (Some(held), state.queue.front()),
+            None => (state.queue.front().copied(), state.queue.get(1)),
+        };
+        if let Some(held) = held {
+            choices.push((true, state.new_piece(&held), next));
+        }
+    }
+
+    let mut ranked: Vec<(f32, Move)> = Vec::new();
+    for (hold, start, next) in choices {
+        let next = next.filter(|_| depth > 1).map(|kind| state.new_piece(kind));
+        for placement in placements(&start, &state.board, rotation) {
+            let score = match &next {
+                Some(next) => placements(next, &placement.board, rotation).iter()
+                    .map(|second| evaluate(&second.board, placement.cleaned_lines + second.cleaned_lines, weights))
+                    .fold(f32::MIN, f32::max),
+                None => evaluate(&placement.board, placement.cleaned_lines, weights),
+            };
+            ranked.push((score, Move { hold, placement }));
+        }
+    }
+    ranked.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+    ranked.into_iter().map(|(_, ranked_move)| ranked_move).collect()
+}
+
+/// Scores a board by its features; higher is better.
+pub fn evaluate(board: &Board, cleaned_lines: u8, weights: &Weights) -> f32 {
+    let columns = board.columns();
+    let rows = board.rows();
+    let heights: Vec<usize> = (1..=columns)
+        .map(|column| (0..rows).find(|row| filled(board, *row, column)).map_or(0, |row| rows - row))
+        .collect();
+
+    let aggregate_height: usize = heights.iter().sum();
+    let bumpiness: usize = heights.windows(2)
+        .map(|pair| pair[0].abs_diff(pair[1]))
+        .sum();
+
+    let mut holes = 0;
+    let mut column_transitions = 0;
+    for column in 1..=columns {
+        for row in 0..rows {
+            let here = filled(board, row, column);
+            if !here && row >= rows - heights[column - 1] {
+                holes += 1;
+            }
+            if here != filled(board, row + 1, column) {
+                column_transitions += 1;
+            }
+        }
+    }
+
+    let mut row_transitions = 0;
+    for row in 0..rows {
+        for column in 0..=columns {
+            if filled(board, row, column) != filled(board, row, column + 1) {
+                row_transitions += 1;
+            }
+        }
+    }
+
+    // Cumulative well depth: a well three deep counts 1 + 2 + 3, as deep wells are much worse.
+    let mut wells = 0;
+    for column in 1..=columns {
+        let mut depth = 0;
+        for row in 0..rows {
+            if filled(board, row, column) {
+                depth = 0;
+            } else if filled(board, row, column - 1) && filled(board, row, column + 1) {
+                depth += 1;
+                wells += depth;
+            }
+        }
+    }
+
+    weights.lines * f32::from(cleaned_lines)
+        + weights.aggregate_height * aggregate_height as f32
+        + weights.holes * holes as f32
+        + weights.bumpiness * bumpiness as f32
+        + weights.wells * wells as f32
+        + weights.row_transitions * row_transitions as f32
+        + weights.column_transitions * column_transitions as f32
+}
+
+/// Plays a game through the same actions a player's keys map to: it plans the best move for each
+/// new piece and then presses its way there. With a difficulty it plays slower, looks less far
+/// ahead and makes mistakes on purpose.
+pub struct AiController {
+    weights: Weights,
+    difficulty: Option<Difficulty>,
+    plan: VecDeque<Action>,
+    planned_piece: Option<u32>,
+    /// The frame the last piece it reacted to came into play, for the pieces per second cap.
+    piece_frame: Option<u32>,
+    holding: bool,
+    wait: u32,
+}
+
+impl AiController {
+    pub fn new() -> AiController {
+        AiController {
+            weights: Weights::new(),
+            difficulty: None,
+            plan: VecDeque::new(),
+            planned_piece: None,
+            piece_frame: None,
+            holding: false,
+            wait: 0,
+        }
+    }
+
+    pub fn with_difficulty(difficulty: Difficulty) -> AiController {
+        AiController {
+            difficulty: Some(difficulty),
+            ..AiController::new()
+        }
+    }
+
+    fn plan(&self, state: &GamePlayState) -> Vec<Action> {
+        let depth = self.difficulty.map_or(2, |difficulty| difficulty.search_depth());
+        let mut moves = ranked_moves(state, &self.weights, depth);
+        if moves.is_empty() {
+            return Vec::new();
+        }
+        let error_rate = self.difficulty.map_or(0.0, |difficulty| difficulty.error_rate());
+        let choices = moves.len().min(MISTAKE_CHOICES + 1);
+        let mut rng = rand::thread_rng();
+        let index = if choices > 1 && rng.gen::<f32>() < error_rate {
+            rng.gen_range(1, choices)
+        } else {
+            0
+        };
+        let chosen = moves.swap_remove(index);
+        if chosen.hold {
+            vec![Action::Hold]
+        } else {
+            chosen.placement.actions
+        }
+    }
+
+    /// Frames to wait before the first press on a new piece: the reaction time, or longer when
+    /// the last piece came too soon for the pieces per second cap.
+    fn reaction(&self, state: &GamePlayState) -> u32 {
+        let difficulty = match self.difficulty {
+            Some(difficulty) => difficulty,
+            None => return 0,
+        };
+        let frames_per_piece = difficulty.pieces_per_second().map_or(0, |pps| (60.0 / pps) as u32);
+        let since_last = self.piece_frame.map_or(u32::MAX, |frame| state.frames.saturating_sub(frame));
+        difficulty.reaction_frames().max(frames_per_piece.saturating_sub(since_last))
+    }
+}
+
+impl Default for AiController {
+    fn default() -> AiController {
+        AiController::new()
+    }
+}
+
+impl Controller for AiController {
+    fn next_action(&mut self, state: &GamePlayState) -> Option<Action> {
+        if !state.piece_active() {
+            return None;
+        }
+        if self.planned_piece != Some(state.pieces) {
+            self.plan = self.plan(state).into_iter().collect();
+            self.planned_piece = Some(state.pieces);
+            if self.holding {
+                self.holding = false;
+                self.wait = 0;
+            } else {
+                self.wait = self.reaction(state);
+                self.piece_frame = Some(state.frames);
+            }
+        }
+        if self.wait > 0 {
+            self.wait -= 1;
+            return None;
+        }
+        let action = self.plan.pop_front();
+        match action {
+            Some(Action::Hold) => self.holding = true,
+            Some(Action::SoftDrop) => (),
+            _ => self.wait = self.difficulty.map_or(0, |difficulty| difficulty.action_delay()),
+        }
+        action
+    }
+}
+
+/// Whether a cell is taken, counting the walls and the floor as taken.
+fn filled(board: &Board, row: usize, column: usize) -> bool {
+    board.data.get((row, column)).is_none_or(|cell| !cell.is_empty())
+}
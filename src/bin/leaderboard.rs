@@ -0,0 +1,366 @@
+//! A self-hosted leaderboard server. Games are posted with their replays, played again headless to
+//! check the claimed result, and ranked per mode. Everything is served as JSON over plain HTTP, as
+//! documented in `docs/leaderboard.md`, and kept in a file of JSON lines between runs.
+//!
+//!     cargo run --release --bin leaderboard -- --port 7881 --data leaderboard.jsonl
+//!     cargo run --release -- --leaderboard http://localhost:7881
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tetris::leaderboard::{Entry, Submission, DEFAULT_PORT};
+use tetris::tbp::json::Json;
+
+const USAGE: &str = "Usage: leaderboard [options]
+
+  --port PORT      port to listen on (default 7881)
+  --data FILE      where accepted games are kept (default leaderboard.jsonl)
+  --limit N        most entries a leaderboard lists (default 100)";
+
+/// The largest request body read, which is far more than the replay of an hour's play.
+const MAX_BODY: usize = 8 * 1024 * 1024;
+/// The largest request line and headers read, all together.
+const MAX_HEADER: usize = 16 * 1024;
+/// The most requests served at once. Connections beyond it are answered `503` straight away.
+const MAX_CONNECTIONS: usize = 64;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Options {
+    port: u16,
+    data: String,
+    limit: usize,
+}
+
+/// Every accepted game, with the replay it was verified from.
+struct Store {
+    entries: Vec<Entry>,
+    replays: Vec<Json>,
+    data: String,
+}
+
+impl Store {
+    /// Reads the games accepted on earlier runs. They were verified then, so they are not played
+    /// again.
+    fn load(data: &str) -> Store {
+        let mut store = Store { entries: Vec::new(), replays: Vec::new(), data: String::from(data) };
+        let source = fs::read_to_string(data).unwrap_or_default();
+        for (number, line) in source.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let read = Json::parse(line).and_then(|json| {
+                let entry = Entry::from_json(json.get("entry").ok_or("Expected an entry")?)?;
+                Ok((entry, json.get("replay").cloned().ok_or("Expected a replay")?))
+            });
+            match read {
+                Ok((entry, replay)) => {
+                    store.entries.push(entry);
+                    store.replays.push(replay);
+                }
+                Err(error) => eprintln!("Skipping line {} of {}: {}", number + 1, data, error),
+            }
+        }
+        for (id, entry) in store.entries.iter_mut().enumerate() {
+            entry.id = id as u32;
+        }
+        store
+    }
+
+    /// Whether a game with the same seed and inputs as `replay` is already on the leaderboards.
+    fn has_replay(&self, replay: &Json) -> bool {
+        self.replays.iter().any(|stored| {
+            stored.get("seed") == replay.get("seed") && stored.get("inputs") == replay.get("inputs")
+        })
+    }
+
+    fn add(&mut self, mut entry: Entry, replay: Json) -> Result<Entry, String> {
+        entry.id = self.entries.len() as u32;
+        let line = Json::object(vec![("entry", entry.to_json()), ("replay", replay.clone())]);
+        OpenOptions::new().create(true).append(true).open(&self.data)
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(|error| format!("Could not save the game: {}", error))?;
+        self.entries.push(entry.clone());
+        self.replays.push(replay);
+        Ok(entry)
+    }
+
+    /// The entries for `mode`, best first, with only those under `rules` if given.
+    fn ranked(&self, mode: &str, rules: Option<&str>) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self.entries.iter()
+            .filter(|entry| entry.mode == mode && rules.is_none_or(|rules| entry.rules.eq_ignore_ascii_case(rules)))
+            .collect();
+        entries.sort_by(|a, b| a.rank(b));
+        entries
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    body: String,
+}
+
+impl Request {
+    fn query(&self, key: &str) -> Option<&str> {
+        self.query.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str())
+    }
+}
+
+fn main() {
+    let options = match parse_options(std::env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let store = Store::load(&options.data);
+    println!("{} games on the leaderboards from {}", store.entries.len(), options.data);
+    let listener = match TcpListener::bind(("0.0.0.0", options.port)) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("Could not listen on port {}: {}", options.port, error);
+            process::exit(1);
+        }
+    };
+    println!("Listening on http://localhost:{}", options.port);
+
+    let store = Arc::new(Mutex::new(store));
+    let limit = options.limit;
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            let _ = respond(&mut stream, 503, &error_json("The server is busy"));
+            continue;
+        }
+        let store = Arc::clone(&store);
+        let connections = Arc::clone(&connections);
+        thread::spawn(move || {
+            if let Err(error) = serve(stream, &store, limit) {
+                eprintln!("{}", error);
+            }
+            connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+fn parse_options(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options { port: DEFAULT_PORT, data: String::from("leaderboard.jsonl"), limit: 100 };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--port" => options.port = number(&value("--port")?)?,
+            "--data" => options.data = value("--data")?,
+            "--limit" => options.limit = number(&value("--limit")?)?,
+            "--help" | "-h" => return Err(String::from("Serves leaderboards of verified games.")),
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Expected a number, got '{}'", value))
+}
+
+fn serve(mut stream: TcpStream, store: &Mutex<Store>, limit: usize) -> Result<(), String> {
+    stream.set_read_timeout(Some(READ_TIMEOUT)).map_err(|error| error.to_string())?;
+    let (status, body) = match read_request(&stream) {
+        Ok(request) => route(&request, store, limit),
+        Err(error) => (400, error_json(&error)),
+    };
+    respond(&mut stream, status, &body)
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &Json) -> Result<(), String> {
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status, reason(status), body.len(), body,
+    );
+    stream.write_all(response.as_bytes()).map_err(|error| error.to_string())
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut remaining = MAX_HEADER;
+    let line = read_header_line(&mut reader, &mut remaining)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or("Empty request")?.to_string();
+    let target = parts.next().ok_or("Expected a path")?;
+
+    let mut length = 0;
+    loop {
+        let header = read_header_line(&mut reader, &mut remaining)?;
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().map_err(|_| "Bad Content-Length")?;
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return Err(format!("Requests are at most {} bytes", MAX_BODY));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|error| error.to_string())?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (decode(key), decode(value)))
+        .collect();
+    Ok(Request {
+        method,
+        path: decode(path.trim_end_matches('/')),
+        query,
+        body: String::from_utf8(body).map_err(|_| "The body is not UTF-8")?,
+    })
+}
+
+/// Reads the request line or a header, taking its length from the `remaining` header bytes. An
+/// empty line means the stream has ended.
+fn read_header_line(reader: &mut BufReader<&TcpStream>, remaining: &mut usize) -> Result<String, String> {
+    let mut bytes = Vec::new();
+    reader.by_ref().take(*remaining as u64 + 1).read_until(b'\n', &mut bytes).map_err(|error| error.to_string())?;
+    if bytes.len() > *remaining {
+        return Err(format!("Headers are at most {} bytes", MAX_HEADER));
+    }
+    *remaining -= bytes.len();
+    String::from_utf8(bytes).map_err(|_| String::from("The headers are not UTF-8"))
+}
+
+/// The response status and body for a request.
+fn route(request: &Request, store: &Mutex<Store>, limit: usize) -> (u16, Json) {
+    let segments: Vec<&str> = request.path.split('/').filter(|segment| !segment.is_empty()).collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["scores"]) => submit(&request.body, store),
+        ("GET", ["leaderboards"]) => {
+            let store = store.lock().unwrap();
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for entry in &store.entries {
+                *counts.entry(&entry.mode).or_insert(0) += 1;
+            }
+            let modes = counts.iter()
+                .map(|(mode, count)| Json::object(vec![("mode", Json::string(mode)), ("entries", Json::Number(*count as f64))]))
+                .collect();
+            (200, Json::object(vec![("modes", Json::Array(modes))]))
+        }
+        ("GET", ["leaderboards", mode]) => {
+            let store = store.lock().unwrap();
+            let limit = request.query("limit").and_then(|limit| limit.parse().ok()).unwrap_or(limit).min(limit);
+            let entries = store.ranked(mode, request.query("rules")).iter()
+                .take(limit)
+                .enumerate()
+                .map(|(index, entry)| ranked_json(entry, index + 1))
+                .collect();
+            (200, Json::object(vec![("mode", Json::string(mode)), ("entries", Json::Array(entries))]))
+        }
+        ("GET", ["replays", id]) => {
+            let store = store.lock().unwrap();
+            match id.parse::<usize>().ok().and_then(|id| store.replays.get(id)) {
+                Some(replay) => (200, replay.clone()),
+                None => (404, error_json("No such replay")),
+            }
+        }
+        (_, ["scores"]) | (_, ["leaderboards"]) | (_, ["leaderboards", _]) | (_, ["replays", _]) => {
+            (405, error_json("Method not allowed"))
+        }
+        _ => (404, error_json("Not found")),
+    }
+}
+
+/// Verifies a posted game and ranks it. The replay is played before taking the lock, so slow
+/// verifications do not hold up other requests.
+fn submit(body: &str, store: &Mutex<Store>) -> (u16, Json) {
+    let submission = match Json::parse(body).and_then(|json| Submission::from_json(&json)) {
+        Ok(submission) => submission,
+        Err(error) => return (400, error_json(&error)),
+    };
+    let entry = match submission.verify() {
+        Ok(entry) => entry,
+        Err(error) => return (422, error_json(&error)),
+    };
+    let replay = submission.replay.to_json();
+    let mut store = store.lock().unwrap();
+    if store.has_replay(&replay) {
+        return (409, error_json("This game is already on the leaderboards"));
+    }
+    match store.add(entry, replay) {
+        Ok(entry) => {
+            let ranked = store.ranked(&entry.mode, Some(&entry.rules));
+            let rank = ranked.iter().position(|ranked| ranked.id == entry.id).map_or(0, |index| index + 1);
+            println!("{} scored {} in {} ({}), rank {}", entry.name, entry.score, entry.mode, entry.rules, rank);
+            let mut response = ranked_json(&entry, rank);
+            if let Json::Object(fields) = &mut response {
+                fields.push((String::from("of"), Json::Number(ranked.len() as f64)));
+            }
+            (201, response)
+        }
+        Err(error) => (500, error_json(&error)),
+    }
+}
+
+fn ranked_json(entry: &Entry, rank: usize) -> Json {
+    let mut json = entry.to_json();
+    if let Json::Object(fields) = &mut json {
+        fields.insert(0, (String::from("rank"), Json::Number(rank as f64)));
+    }
+    json
+}
+
+fn error_json(error: &str) -> Json {
+    Json::object(vec![("error", Json::string(error))])
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Undoes percent-encoding in a URL, with `+` as a space.
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = text.get(index + 1..index + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
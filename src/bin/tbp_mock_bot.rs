@@ -0,0 +1,109 @@
+//! A stand-in Tetris Bot Protocol bot for trying the bot support without a real engine. It knows
+//! nothing about the board: it suggests every location of the active piece, lowest and leftmost
+//! first, and leaves it to the game to play the first one that fits.
+//!
+//!     cargo run -- --bot target/debug/tbp_mock_bot
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use tetris::tbp::json::Json;
+
+const ORIENTATIONS: [&str; 4] = ["north", "east", "south", "west"];
+const COLUMNS: i64 = 10;
+const ROWS: i64 = 22;
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut queue: VecDeque<String> = VecDeque::new();
+    let mut hold: Option<String> = None;
+
+    send(&mut out, message("info", vec![
+        ("name", Json::string("Mock")),
+        ("version", Json::string("1.0")),
+        ("author", Json::string("tetris")),
+        ("features", Json::Array(Vec::new())),
+    ]))?;
+
+    for line in stdin.lock().lines() {
+        let request = match Json::parse(&line?) {
+            Ok(request) => request,
+            Err(error) => {
+                send(&mut out, message("error", vec![("reason", Json::String(error))]))?;
+                continue;
+            }
+        };
+        match request.get("type").and_then(Json::as_str) {
+            Some("rules") => send(&mut out, message("ready", Vec::new()))?,
+            Some("start") => {
+                queue = request.get("queue").and_then(Json::as_array).unwrap_or(&[]).iter()
+                    .filter_map(|piece| piece.as_str().map(String::from))
+                    .collect();
+                hold = request.get("hold").and_then(Json::as_str).map(String::from);
+            }
+            Some("suggest") => {
+                let moves = match queue.front() {
+                    Some(piece) => every_location(piece),
+                    None => Vec::new(),
+                };
+                send(&mut out, message("suggestion", vec![("moves", Json::Array(moves))]))?;
+            }
+            Some("play") => {
+                let played = request.get("move")
+                    .and_then(|played| played.get("location"))
+                    .and_then(|location| location.get("type"))
+                    .and_then(Json::as_str);
+                if played.is_some() && played != queue.front().map(String::as_str) {
+                    let current = queue.pop_front();
+                    if hold.is_none() {
+                        queue.pop_front();
+                    }
+                    hold = current;
+                } else {
+                    queue.pop_front();
+                }
+            }
+            Some("new_piece") => {
+                if let Some(piece) = request.get("piece").and_then(Json::as_str) {
+                    queue.push_back(String::from(piece));
+                }
+            }
+            Some("stop") => {
+                queue.clear();
+                hold = None;
+            }
+            Some("quit") => break,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn every_location(piece: &str) -> Vec<Json> {
+    let mut moves = Vec::new();
+    for y in 0..ROWS {
+        for x in 0..COLUMNS {
+            for orientation in ORIENTATIONS.iter() {
+                let location = Json::object(vec![
+                    ("type", Json::string(piece)),
+                    ("orientation", Json::string(orientation)),
+                    ("x", Json::Number(x as f64)),
+                    ("y", Json::Number(y as f64)),
+                ]);
+                moves.push(Json::object(vec![("location", location), ("spin", Json::string("none"))]));
+            }
+        }
+    }
+    moves
+}
+
+fn message(kind: &str, mut fields: Vec<(&str, Json)>) -> Json {
+    fields.insert(0, ("type", Json::string(kind)));
+    Json::object(fields)
+}
+
+fn send(out: &mut impl Write, message: Json) -> io::Result<()> {
+    writeln!(out, "{}", message)?;
+    out.flush()
+}
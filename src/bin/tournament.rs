@@ -0,0 +1,430 @@
+//! Plays seeded games between bots with no window and reports how they did, to compare bots and
+//! rule changes quickly. A bot is `ai` for the built-in AI or a command that starts a Tetris Bot
+//! Protocol bot. Games run as fast as the machine allows, and wait for bots while they think.
+//! With `--broadcast`, games are played in real time for spectators to watch instead.
+//!
+//!     cargo run --release --bin tournament -- --games 20 --rules Guideline ai target/release/tbp_mock_bot
+//!     cargo run --release --bin tournament -- --versus ai "my_bot --fast"
+
+use std::fs;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+use tetris::ai::AiController;
+use tetris::controls::{Action, Controller};
+use tetris::game::{GameConfig, GameEvent, GamePlayState};
+use tetris::modes::GameMode;
+use tetris::modes::rules::RuleSet;
+use tetris::modes::versus::{AttackTable, VersusPlayer};
+use tetris::net::spectate::Broadcaster;
+use tetris::tbp::TbpController;
+use tetris::tbp::json::Json;
+
+const USAGE: &str = "Usage: tournament [options] <bot>...
+
+  <bot>            ai for the built-in AI, or a command that starts a Tetris Bot Protocol bot
+  --games N        games per bot, or per match with --versus (default 10)
+  --seed S         seed of the first game; the games after it count up from there (default 0)
+  --rules RULES    a preset name or a .rules file (default Standard)
+  --minutes M      game time after which a game is stopped (default 5)
+  --versus         play the first two bots against each other with garbage
+  --json FILE      also write the results to FILE as JSON
+  --broadcast PORT let spectators watch on PORT, playing in real time";
+
+/// How long a bot may think about a single piece before it counts as crashed.
+const BOT_TIMEOUT: Duration = Duration::from_secs(10);
+const FRAMES_PER_SECOND: f64 = 60.0;
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+struct Options {
+    bots: Vec<String>,
+    games: u32,
+    seed: u32,
+    rules: RuleSet,
+    frames: u32,
+    versus: bool,
+    json: Option<String>,
+    broadcast: Option<u16>,
+}
+
+/// What one bot did in one game.
+struct Record {
+    lines: u16,
+    score: u32,
+    pieces: u32,
+    attack: u16,
+    frames: u32,
+    topped_out: bool,
+    crashed: bool,
+}
+
+/// A figure reported for each game, by name.
+type Metric = (&'static str, fn(&Record) -> f64);
+
+const METRICS: [Metric; 5] = [
+    ("lines", |record| f64::from(record.lines)),
+    ("score", |record| f64::from(record.score)),
+    ("pps", |record| per_second(f64::from(record.pieces), record.frames)),
+    ("apm", |record| 60.0 * per_second(f64::from(record.attack), record.frames)),
+    ("survival", |record| f64::from(record.frames) / FRAMES_PER_SECOND),
+];
+
+/// A game in progress for one bot, with the action it has settled on for the coming frame.
+struct Player {
+    state: GamePlayState,
+    controller: Box<dyn Controller>,
+    decided: Option<Option<Action>>,
+    thinking_since: Option<Instant>,
+    pieces: u32,
+    crashed: bool,
+}
+
+impl Player {
+    /// Player `index` of a game. Garbage holes are picked from the game's seed plus one more than
+    /// the index, as online, so a game replays the same from its seed.
+    fn new(bot: &str, index: usize, config: GameConfig) -> Player {
+        let mut state = GamePlayState::new(config, None).expect("Games without a puzzle always start");
+        let hole_seed = state.seed.wrapping_add(index as u32 + 1);
+        state.versus = Some(VersusPlayer::with_seed(AttackTable::guideline(), hole_seed));
+        let (controller, crashed): (Box<dyn Controller>, bool) = match bot {
+            "ai" => (Box::new(AiController::new()), false),
+            command => match TbpController::new(command) {
+                Ok(bot) => (Box::new(bot), false),
+                Err(error) => {
+                    eprintln!("Could not start {}: {}", command, error);
+                    (Box::new(AiController::new()), true)
+                }
+            },
+        };
+        Player {
+            state,
+            controller,
+            decided: None,
+            thinking_since: None,
+            pieces: 0,
+            crashed,
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.state.game_over || self.crashed
+    }
+
+    /// Asks the controller for the coming frame's action, returning false while it is still
+    /// thinking. A controller that fails or thinks for too long has crashed.
+    fn ready(&mut self) -> bool {
+        if self.done() || self.decided.is_some() {
+            return true;
+        }
+        let action = self.controller.next_action(&self.state);
+        if self.controller.failed() {
+            self.crashed = true;
+            return true;
+        }
+        if action.is_none() && self.controller.thinking() {
+            let since = *self.thinking_since.get_or_insert_with(Instant::now);
+            if since.elapsed() > BOT_TIMEOUT {
+                self.crashed = true;
+                return true;
+            }
+            return false;
+        }
+        self.thinking_since = None;
+        self.decided = Some(action);
+        true
+    }
+
+    fn step(&mut self) {
+        if self.done() {
+            return;
+        }
+        if let Some(Some(action)) = self.decided.take() {
+            self.state.apply(action);
+        }
+        self.decided = None;
+        if let Some(GameEvent::Locked(_)) = self.state.step() {
+            self.pieces += 1;
+        }
+    }
+
+    fn record(&self) -> Record {
+        Record {
+            lines: self.state.score.lines,
+            score: self.state.score.score,
+            pieces: self.pieces,
+            attack: self.state.versus.as_ref().map_or(0, |versus| versus.lines_sent),
+            frames: self.state.frames,
+            topped_out: self.state.topped_out,
+            crashed: self.crashed,
+        }
+    }
+}
+
+fn main() {
+    let options = match parse_options(std::env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut broadcaster = options.broadcast.map(|port| match Broadcaster::listen(port) {
+        Ok(broadcaster) => {
+            println!("Broadcasting to spectators on port {}", broadcaster.port());
+            broadcaster
+        }
+        Err(error) => {
+            eprintln!("Could not broadcast on port {}: {}", port, error);
+            process::exit(1);
+        }
+    });
+
+    let mut results = Vec::new();
+    if options.versus {
+        let (records, wins, draws) = play_match(&options, &mut broadcaster);
+        print_table(&options.bots[..2], &records);
+        println!("\n{} {} - {} {} ({} drawn)", options.bots[0], wins[0], wins[1], options.bots[1], draws);
+        for (bot, records) in options.bots.iter().zip(records.iter()) {
+            results.push(summary_json(bot, records));
+        }
+        write_json(&options, results, Some(Json::object(vec![
+            ("wins", Json::Array(wins.iter().map(|wins| Json::Number(f64::from(*wins))).collect())),
+            ("draws", Json::Number(f64::from(draws))),
+        ])));
+    } else {
+        let records: Vec<Vec<Record>> = options.bots.iter()
+            .map(|bot| (0..options.games).map(|game| play_solo(bot, &options, game, &mut broadcaster)).collect())
+            .collect();
+        print_table(&options.bots, &records);
+        for (bot, records) in options.bots.iter().zip(records.iter()) {
+            results.push(summary_json(bot, records));
+        }
+        write_json(&options, results, None);
+    }
+}
+
+fn parse_options(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options {
+        bots: Vec::new(),
+        games: 10,
+        seed: 0,
+        rules: RuleSet::standard(),
+        frames: 5 * 60 * 60,
+        versus: false,
+        json: None,
+        broadcast: None,
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--games" => options.games = number(&value("--games")?)?,
+            "--seed" => options.seed = number(&value("--seed")?)?,
+            "--rules" => options.rules = rules(&value("--rules")?)?,
+            "--minutes" => options.frames = number::<u32>(&value("--minutes")?)? * 60 * 60,
+            "--versus" => options.versus = true,
+            "--json" => options.json = Some(value("--json")?),
+            "--broadcast" => options.broadcast = Some(number(&value("--broadcast")?)?),
+            "--help" | "-h" => return Err(String::from("Runs headless games between bots.")),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => options.bots.push(arg),
+        }
+    }
+    if options.bots.is_empty() {
+        return Err(String::from("No bots given"));
+    }
+    if options.versus && options.bots.len() != 2 {
+        return Err(String::from("--versus needs exactly two bots"));
+    }
+    Ok(options)
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Expected a number, got '{}'", value))
+}
+
+fn rules(name: &str) -> Result<RuleSet, String> {
+    if let Some(preset) = RuleSet::presets().into_iter().find(|preset| preset.name.eq_ignore_ascii_case(name)) {
+        return Ok(preset);
+    }
+    let source = fs::read_to_string(name).map_err(|error| format!("No preset or file '{}': {}", name, error))?;
+    RuleSet::parse(&source).map_err(|error| format!("Could not read {}: {}", name, error))
+}
+
+fn game_config(options: &Options, mode: GameMode, game: u32) -> GameConfig {
+    let mut config = GameConfig::new(mode);
+    config.rules = options.rules.clone();
+    config.seed = Some(options.seed.wrapping_add(game));
+    config
+}
+
+fn play_solo(bot: &str, options: &Options, game: u32, broadcaster: &mut Option<Broadcaster>) -> Record {
+    let mut player = Player::new(bot, 0, game_config(options, GameMode::Marathon, game));
+    let mut next_frame = Instant::now();
+    while !player.done() && player.state.frames < options.frames {
+        if player.ready() {
+            player.step();
+            broadcast(broadcaster, &[(bot, &player.state)], &mut next_frame);
+        } else {
+            thread::sleep(Duration::from_millis(1));
+        }
+        if let Some(versus) = player.state.versus.as_mut() {
+            versus.take_outgoing();
+        }
+    }
+    player.record()
+}
+
+/// Plays the games of a match, both players dealt the same pieces. Returns each bot's records,
+/// its wins and the games drawn, which are those that run out of time or end for both at once.
+fn play_match(options: &Options, broadcaster: &mut Option<Broadcaster>) -> (Vec<Vec<Record>>, [u32; 2], u32) {
+    let mut records = vec![Vec::new(), Vec::new()];
+    let mut wins = [0, 0];
+    let mut draws = 0;
+    for game in 0..options.games {
+        let mut players: Vec<Player> = options.bots.iter()
+            .enumerate()
+            .map(|(index, bot)| Player::new(bot, index, game_config(options, GameMode::Versus, game)))
+            .collect();
+        let mut next_frame = Instant::now();
+        while players.iter().all(|player| !player.done()) && players[0].state.frames < options.frames {
+            let ready: Vec<bool> = players.iter_mut().map(Player::ready).collect();
+            if ready.contains(&false) {
+                thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+            for player in players.iter_mut() {
+                player.step();
+            }
+            let sent: Vec<u8> = players.iter_mut()
+                .map(|player| player.state.versus.as_mut().map_or(0, VersusPlayer::take_outgoing))
+                .collect();
+            for (index, lines) in sent.iter().enumerate() {
+                let opponent = &mut players[1 - index].state;
+                let columns = opponent.board.columns();
+                if let Some(versus) = opponent.versus.as_mut() {
+                    versus.receive(*lines, columns);
+                }
+            }
+            let watched = [(options.bots[0].as_str(), &players[0].state), (options.bots[1].as_str(), &players[1].state)];
+            broadcast(broadcaster, &watched, &mut next_frame);
+        }
+        match (players[0].done(), players[1].done()) {
+            (false, true) => wins[0] += 1,
+            (true, false) => wins[1] += 1,
+            _ => draws += 1,
+        }
+        for (index, player) in players.iter().enumerate() {
+            records[index].push(player.record());
+        }
+    }
+    (records, wins, draws)
+}
+
+/// Sends the frame just played to spectators, then waits for its time to be up so they watch
+/// the game at normal speed.
+fn broadcast(broadcaster: &mut Option<Broadcaster>, players: &[(&str, &GamePlayState)], next_frame: &mut Instant) {
+    let broadcaster = match broadcaster {
+        Some(broadcaster) => broadcaster,
+        None => return,
+    };
+    broadcaster.publish(players);
+    *next_frame += FRAME;
+    let now = Instant::now();
+    if *next_frame > now {
+        thread::sleep(*next_frame - now);
+    } else {
+        *next_frame = now;
+    }
+}
+
+fn per_second(count: f64, frames: u32) -> f64 {
+    if frames == 0 {
+        0.0
+    } else {
+        count * FRAMES_PER_SECOND / f64::from(frames)
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    match sorted.len() {
+        0 => 0.0,
+        length if length % 2 == 0 => (sorted[length / 2 - 1] + sorted[length / 2]) / 2.0,
+        length => sorted[length / 2],
+    }
+}
+
+/// The share of games for which `happened` holds.
+fn rate(records: &[Record], happened: fn(&Record) -> bool) -> f64 {
+    mean(&records.iter().map(|record| if happened(record) { 1.0 } else { 0.0 }).collect::<Vec<_>>())
+}
+
+/// One row per bot, each metric as mean / median.
+fn print_table(bots: &[String], records: &[Vec<Record>]) {
+    let width = bots.iter().map(String::len).max().unwrap_or(0).max(3);
+    print!("{:<width$} {:>5}", "BOT", "GAMES", width = width);
+    for (name, _) in METRICS.iter() {
+        print!(" {:>17}", name.to_uppercase());
+    }
+    println!(" {:>8} {:>8}", "TOP-OUT", "CRASH");
+    for (bot, records) in bots.iter().zip(records.iter()) {
+        print!("{:<width$} {:>5}", bot, records.len(), width = width);
+        for (_, metric) in METRICS.iter() {
+            let values: Vec<f64> = records.iter().map(metric).collect();
+            print!(" {:>8.2}/{:<8.2}", mean(&values), median(&values));
+        }
+        println!(
+            " {:>7.0}% {:>7.0}%",
+            100.0 * rate(records, |record| record.topped_out),
+            100.0 * rate(records, |record| record.crashed),
+        );
+    }
+}
+
+fn summary_json(bot: &str, records: &[Record]) -> Json {
+    let mut fields = vec![
+        ("bot", Json::string(bot)),
+        ("games", Json::Number(records.len() as f64)),
+    ];
+    for (name, metric) in METRICS.iter() {
+        let values: Vec<f64> = records.iter().map(metric).collect();
+        fields.push((name, Json::object(vec![
+            ("mean", Json::Number(mean(&values))),
+            ("median", Json::Number(median(&values))),
+        ])));
+    }
+    fields.push(("top_out_rate", Json::Number(rate(records, |record| record.topped_out))));
+    fields.push(("crash_rate", Json::Number(rate(records, |record| record.crashed))));
+    Json::object(fields)
+}
+
+fn write_json(options: &Options, bots: Vec<Json>, versus: Option<Json>) {
+    let path = match &options.json {
+        Some(path) => path,
+        None => return,
+    };
+    let mut fields = vec![
+        ("rules", Json::string(&options.rules.name)),
+        ("games", Json::Number(f64::from(options.games))),
+        ("seed", Json::Number(f64::from(options.seed))),
+        ("bots", Json::Array(bots)),
+    ];
+    if let Some(versus) = versus {
+        fields.push(("versus", versus));
+    }
+    if let Err(error) = fs::write(path, format!("{}\n", Json::object(fields))) {
+        eprintln!("Could not write {}: {}", path, error);
+        process::exit(1);
+    }
+}
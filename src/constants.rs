@@ -3,9 +3,13 @@ pub const BOARD_HEIGHT: f32 = 462.0;
 pub const SCREEN_WIDTH: f32 = 792.0;
 pub const SCREEN_HEIGHT: f32 = BOARD_HEIGHT + BOARD_HEIGHT / 4.0;
 pub const FALL_TIME: f32 = 1.0;
+pub const FADE_TIME: f32 = 5.0;
+pub const ATTRACT_TIME: f32 = 20.0;
 pub const POINTS_FOR_ONE_LINE: u32 = 40;
 pub const POINTS_FOR_TWO_LINES: u32 = 100;
 pub const POINTS_FOR_THREE_LINES: u32 = 300;
 pub const POINTS_FOR_MORE_THAN_THREE_LINES: u32 = 1200;
 pub const TOP_BOUNDARY: i8 = 0;
-
+pub const VERSUS_BEST_OF: u8 = 3;
+pub const SPRINT_LINES: u16 = 40;
+pub const ULTRA_FRAMES: u32 = 2 * 60 * 60;
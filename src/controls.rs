@@ -0,0 +1,125 @@
+use ggez::event::{KeyCode, Button};
+use crate::game::GamePlayState;
+
+/// Everything a player can do to the active piece.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    Hold,
+}
+
+impl Action {
+    /// The letter that stands for the action wherever inputs are written down, as in replays and
+    /// the online protocol.
+    pub fn letter(&self) -> char {
+        match self {
+            Action::MoveLeft => 'L',
+            Action::MoveRight => 'R',
+            Action::SoftDrop => 'D',
+            Action::RotateClockwise => 'C',
+            Action::RotateCounterClockwise => 'A',
+            Action::Hold => 'H',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Action> {
+        match letter {
+            'L' => Some(Action::MoveLeft),
+            'R' => Some(Action::MoveRight),
+            'D' => Some(Action::SoftDrop),
+            'C' => Some(Action::RotateClockwise),
+            'A' => Some(Action::RotateCounterClockwise),
+            'H' => Some(Action::Hold),
+            _ => None,
+        }
+    }
+}
+
+/// Plays the active piece in place of the keyboard, with at most one action a frame.
+pub trait Controller {
+    fn next_action(&mut self, state: &GamePlayState) -> Option<Action>;
+
+    /// Whether it is still deciding what to do, so a game that need not keep real time can wait.
+    fn thinking(&self) -> bool {
+        false
+    }
+
+    /// Whether it has stopped playing for good, such as a bot that reported an error.
+    fn failed(&self) -> bool {
+        false
+    }
+}
+
+pub struct KeyMap {
+    bindings: Vec<(KeyCode, Action)>,
+}
+
+impl KeyMap {
+    pub fn single_player() -> KeyMap {
+        KeyMap {
+            bindings: vec![
+                (KeyCode::Left, Action::MoveLeft),
+                (KeyCode::Right, Action::MoveRight),
+                (KeyCode::Down, Action::SoftDrop),
+                (KeyCode::W, Action::RotateClockwise),
+                (KeyCode::Q, Action::RotateCounterClockwise),
+                (KeyCode::C, Action::Hold),
+                (KeyCode::LShift, Action::Hold),
+            ],
+        }
+    }
+
+    pub fn left_player() -> KeyMap {
+        KeyMap {
+            bindings: vec![
+                (KeyCode::A, Action::MoveLeft),
+                (KeyCode::D, Action::MoveRight),
+                (KeyCode::S, Action::SoftDrop),
+                (KeyCode::W, Action::RotateClockwise),
+                (KeyCode::Q, Action::RotateCounterClockwise),
+                (KeyCode::LShift, Action::Hold),
+            ],
+        }
+    }
+
+    pub fn right_player() -> KeyMap {
+        KeyMap {
+            bindings: vec![
+                (KeyCode::Left, Action::MoveLeft),
+                (KeyCode::Right, Action::MoveRight),
+                (KeyCode::Down, Action::SoftDrop),
+                (KeyCode::Up, Action::RotateClockwise),
+                (KeyCode::RControl, Action::RotateCounterClockwise),
+                (KeyCode::RShift, Action::Hold),
+            ],
+        }
+    }
+
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.bindings.iter()
+            .find(|(binding, _)| *binding == key)
+            .map(|(_, action)| *action)
+    }
+}
+
+/// Gamepad buttons stand in for keys, so menus and the single player key map work unchanged.
+pub fn gamepad_key(button: Button) -> Option<KeyCode> {
+    match button {
+        Button::DPadUp => Some(KeyCode::Up),
+        Button::DPadDown => Some(KeyCode::Down),
+        Button::DPadLeft => Some(KeyCode::Left),
+        Button::DPadRight => Some(KeyCode::Right),
+        Button::South => Some(KeyCode::Return),
+        Button::East => Some(KeyCode::Escape),
+        Button::North => Some(KeyCode::W),
+        Button::West => Some(KeyCode::Q),
+        Button::LeftTrigger | Button::RightTrigger => Some(KeyCode::C),
+        Button::Start => Some(KeyCode::Space),
+        Button::Select => Some(KeyCode::Tab),
+        _ => None,
+    }
+}
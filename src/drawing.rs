@@ -1,12 +1,54 @@
 use crate::resources::Assets;
-use crate::constants::{BOARD_WIDTH, BOARD_HEIGHT};
+use crate::skins::{Ghost, Sprite, GHOST_ALPHA};
+use crate::constants::{BOARD_WIDTH, BOARD_HEIGHT, SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::layout::{self, PLAYFIELD};
 use ggez::{Context, GameResult, graphics};
-use ggez::graphics::{Text, Scale};
+use ggez::graphics::{Text, Scale, Mesh, MeshBuilder, DrawMode, Rect, Font, Color};
 use std::convert::TryFrom;
-use crate::world::{Tetrimino, TetriminoType, ScoreBoard, Board};
+use std::f32::consts::PI;
+use std::collections::VecDeque;
+use crate::world::{Tetrimino, TetriminoType, ScoreBoard, Board, BoardType};
+use crate::game::Lock;
 use crate::types::{ScreenPoint2, WorldPoint2};
+use crate::modes::master::Master;
+use crate::modes::survival::Survival;
+use crate::modes::finesse::{Finesse, SESSION_PIECES};
+use crate::modes::StackVisibility;
+use crate::modes::puzzle::{Puzzle, PuzzleRun};
 use crate::SharedState;
 
+/// Magenta, which no block uses, so the hint never looks like part of the stack.
+const HINT_COLOR: graphics::Color = graphics::Color::new(1.0, 0.0, 1.0, 1.0);
+/// The part of the line clear delay the cleared rows flash for.
+const FLASH_END: f32 = 0.5;
+const FLASH_ALPHA: f32 = 0.8;
+/// When the cleared rows start to dissolve; every cell is gone by the end of the delay.
+const DISSOLVE_START: f32 = 0.25;
+/// How far particles fall by the time they fade out, in cells.
+const PARTICLE_FALL: f32 = 4.0;
+
+/// How big a clear's animation is: more flashes and particles for the clears worth more.
+struct ClearEffect {
+    flashes: u32,
+    /// Particles thrown by each cell as it dissolves.
+    particles: usize,
+    color: Color,
+}
+
+impl ClearEffect {
+    fn of(lock: &Lock) -> ClearEffect {
+        if lock.perfect_clear {
+            ClearEffect { flashes: 4, particles: 6, color: Color::from_rgb(229, 223, 36) }
+        } else if lock.t_spin {
+            ClearEffect { flashes: 3, particles: 4, color: Color::from_rgb(156, 16, 229) }
+        } else if lock.lines >= 4 {
+            ClearEffect { flashes: 3, particles: 4, color: Color::from_rgb(0, 229, 229) }
+        } else {
+            ClearEffect { flashes: 1, particles: usize::from(lock.lines).min(3), color: graphics::WHITE }
+        }
+    }
+}
+
 pub fn draw_tetrimino(
     assets: &mut Assets,
     ctx: &mut Context,
@@ -14,32 +56,156 @@ pub fn draw_tetrimino(
     board_dimensions: (f32, f32),
     offset: Option<(f32, f32)>,
 ) -> GameResult {
-    let (board_w, board_h) = board_dimensions;
-    let mut pos = world_to_screen_coords(board_w, board_h, &tetrimino.pos);
-    if let Some((x_offset, y_offset)) = offset {
-        pos = ScreenPoint2::from([pos.x + x_offset, pos.y + y_offset]);
+    let sprite = assets.block(TetriminoType::to_code(&tetrimino.kind)).unwrap();
+    draw_cells(ctx, sprite, tetrimino, board_dimensions, offset, graphics::WHITE)
+}
+
+/// Shows where the falling piece would land, if the skin has a ghost.
+pub fn draw_ghost(
+    assets: &mut Assets,
+    ctx: &mut Context,
+    tetrimino: &Tetrimino,
+    board: &Board,
+    board_dimensions: (f32, f32),
+    offset: Option<(f32, f32)>,
+) -> GameResult {
+    let mut ghost = tetrimino.clone();
+    while ghost.move_down(board) {}
+    match &assets.skin.ghost {
+        Ghost::Off => Ok(()),
+        Ghost::Faded => {
+            let sprite = assets.block(TetriminoType::to_code(&tetrimino.kind)).unwrap();
+            draw_cells(ctx, sprite, &ghost, board_dimensions, offset, Color::new(1.0, 1.0, 1.0, GHOST_ALPHA))
+        }
+        Ghost::Sprite(sprite) => draw_cells(ctx, sprite, &ghost, board_dimensions, offset, graphics::WHITE),
+    }
+}
+
+fn draw_cells(
+    ctx: &mut Context,
+    sprite: &Sprite,
+    tetrimino: &Tetrimino,
+    board_dimensions: (f32, f32),
+    offset: Option<(f32, f32)>,
+    color: Color,
+) -> GameResult {
+    let (board_width, board_height) = board_dimensions;
+    let (x_offset, y_offset) = offset.unwrap_or((0.0, 0.0));
+    for cell in tetrimino.cells().iter() {
+        let point = world_to_screen_coords(board_width, board_height, cell);
+        sprite.draw(ctx, ScreenPoint2::new(point.x + x_offset, point.y + y_offset), cell_size(board_dimensions), color)?;
+    }
+    Ok(())
+}
+
+/// The skin's background, if it has one, stretched over the screen.
+pub fn draw_background(ctx: &mut Context, assets: &Assets) -> GameResult {
+    match &assets.skin.background {
+        Some(background) => {
+            let scale = [
+                SCREEN_WIDTH / f32::from(background.width()),
+                SCREEN_HEIGHT / f32::from(background.height()),
+            ];
+            graphics::draw(ctx, background, graphics::DrawParam::new().scale(scale))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Animates the lines `lock` cleared while they wait out the line clear delay, `progress` of the
+/// way through it. The rows flash, then dissolve cell by cell into particles.
+pub fn draw_line_clear(
+    assets: &mut Assets,
+    ctx: &mut Context,
+    board: &Board,
+    lock: &Lock,
+    progress: f32,
+    board_dimensions: (f32, f32),
+    offset: Option<(f32, f32)>,
+) -> GameResult {
+    let effect = ClearEffect::of(lock);
+    let (board_width, board_height) = board_dimensions;
+    let (cell_width, cell_height) = cell_size(board_dimensions);
+    let (x_offset, y_offset) = offset.unwrap_or((0.0, 0.0));
+    let flash = if progress < FLASH_END {
+        (progress / FLASH_END * PI * effect.flashes as f32).sin().abs()
+    } else {
+        0.0
     };
-    let image = assets.block_image(TetriminoType::to_code(&tetrimino.kind)).unwrap();
 
-    for vector in tetrimino.vectors.iter() {
-        let mut vector_pos =
-            world_to_screen_coords(
-                board_w,
-                board_h,
-                &WorldPoint2::from([vector.x + tetrimino.pos.x, vector.y + tetrimino.pos.y]));
-        if let Some((x_offset, y_offset)) = offset {
-            vector_pos = ScreenPoint2::from([vector_pos.x + x_offset, vector_pos.y + y_offset]);
-        };
-        let draw_params = graphics::DrawParam::new()
-            .scale([0.5, 0.5])
-            .dest(vector_pos);
-        graphics::draw(ctx, image, draw_params)?
+    let mut shapes = MeshBuilder::new();
+    let mut shape_count = 0;
+    for row in board.full_rows() {
+        for column in 1..=board.columns() {
+            let point = world_to_screen_coords(board_width, board_height, &WorldPoint2::new(column as i8, row as i8));
+            let point = ScreenPoint2::new(point.x + x_offset, point.y + y_offset);
+            let vanish = DISSOLVE_START + (1.0 - DISSOLVE_START) * 0.9 * noise(row, column, 0);
+            if progress < vanish {
+                let code = board.data.index((row, column)).code;
+                if let Some(sprite) = assets.block(code) {
+                    sprite.draw(ctx, point, (cell_width, cell_height), graphics::WHITE)?;
+                }
+                continue;
+            }
+
+            let age = (progress - vanish) / (1.0 - vanish);
+            let color = Color { a: 1.0 - age, ..effect.color };
+            let size = cell_width / 6.0;
+            for particle in 0..effect.particles {
+                let spread = (noise(row, column, 2 * particle + 1) - 0.5) * 4.0 * cell_width;
+                let rise = noise(row, column, 2 * particle + 2) * 3.0 * cell_height;
+                let x = point.x + cell_width / 2.0 + spread * age;
+                let y = point.y + cell_height / 2.0 - rise * age + PARTICLE_FALL * cell_height * age * age;
+                shapes.rectangle(DrawMode::fill(), Rect::new(x - size / 2.0, y - size / 2.0, size, size), color);
+                shape_count += 1;
+            }
+        }
+        if flash > 0.0 {
+            let left = world_to_screen_coords(board_width, board_height, &WorldPoint2::new(1, row as i8));
+            let bounds = Rect::new(left.x + x_offset, left.y + y_offset, cell_width * board.columns() as f32, cell_height);
+            shapes.rectangle(DrawMode::fill(), bounds, Color { a: flash * FLASH_ALPHA, ..effect.color });
+            shape_count += 1;
+        }
+    }
+    // A mesh cannot be built without any shapes.
+    if shape_count > 0 {
+        let mesh = shapes.build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::new())?;
     }
+    Ok(())
+}
 
-    let draw_params = graphics::DrawParam::new()
-        .scale([0.5, 0.5])
-        .dest(pos);
-    graphics::draw(ctx, image, draw_params)
+/// A number from 0 to 1 that looks random but is the same every frame for the same arguments,
+/// so the line clear animation keeps no state of its own.
+fn noise(row: usize, column: usize, index: usize) -> f32 {
+    let mut hash = (row as u32).wrapping_mul(73_856_093)
+        ^ (column as u32).wrapping_mul(19_349_663)
+        ^ (index as u32).wrapping_mul(83_492_791);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0x5bd1_e995);
+    hash ^= hash >> 15;
+    (hash % 10_000) as f32 / 10_000.0
+}
+
+/// Outlines the cells where the hint suggests the piece goes.
+pub fn draw_hint(
+    ctx: &mut Context,
+    tetrimino: &Tetrimino,
+    board_dimensions: (f32, f32),
+) -> GameResult {
+    let (board_width, board_height) = board_dimensions;
+    let (cell_width, cell_height) = cell_size(board_dimensions);
+    for cell in tetrimino.cells().iter() {
+        let point = world_to_screen_coords(board_width, board_height, cell);
+        let outline = Mesh::new_rectangle(
+            ctx,
+            DrawMode::stroke(2.0),
+            Rect::new(point.x, point.y, cell_width, cell_height),
+            HINT_COLOR,
+        )?;
+        graphics::draw(ctx, &outline, graphics::DrawParam::new())?
+    }
+    Ok(())
 }
 
 pub fn draw_board(
@@ -47,8 +213,13 @@ pub fn draw_board(
     ctx: &mut Context,
     board: &Board,
     board_dimensions: (f32, f32),
+    offset: Option<(f32, f32)>,
+    stack_visibility: StackVisibility,
+    frame: u32,
 ) -> GameResult {
     let (board_width, board_height) = board_dimensions;
+    // Lines being cleared are drawn by `draw_line_clear`.
+    let clearing = board.full_rows();
     for (r, row) in board.data.row_iter().enumerate() {
         for (c, _element) in row.column_iter().enumerate() {
             let point =
@@ -59,12 +230,32 @@ pub fn draw_board(
                         i8::try_from(c).expect("Failed to convert X coordinate"),
                         i8::try_from(r).expect("Failed to convert Y coordinate"),
                     ]));
-            let image = assets.block_image(*_element.get((0, 0)).unwrap());
-            if let Some(image) = image {
-                let draw_params = graphics::DrawParam::new()
-                    .scale([0.5, 0.5])
-                    .dest(point);
-                graphics::draw(ctx, image, draw_params)?
+            let point = match offset {
+                Some((x_offset, y_offset)) => ScreenPoint2::from([point.x + x_offset, point.y + y_offset]),
+                None => point,
+            };
+            let cell = _element.get((0, 0)).unwrap();
+            if cell.is_empty() {
+                continue;
+            }
+            let alpha = match BoardType::from_code(cell.code) {
+                Some(BoardType::LIMIT) => 1.0,
+                _ if clearing.contains(&r) => continue,
+                _ => stack_visibility.alpha(cell, frame),
+            };
+            if alpha > 0.0 {
+                if let Some(sprite) = assets.block(cell.code) {
+                    sprite.draw(ctx, point, cell_size(board_dimensions), Color::new(1.0, 1.0, 1.0, alpha))?
+                }
+            }
+            if stack_visibility.flashing(cell, frame) {
+                let outline = Mesh::new_rectangle(
+                    ctx,
+                    DrawMode::stroke(2.0),
+                    Rect::new(point.x, point.y, board_width / 12.0, board_height / 21.0),
+                    graphics::WHITE,
+                )?;
+                graphics::draw(ctx, &outline, graphics::DrawParam::new())?
             }
         }
     }
@@ -77,44 +268,305 @@ pub fn draw_score_board(
     shared_state: &SharedState,
 ) -> GameResult {
     let mut lines = Text::new(format!("LINES: {}", score_board.lines));
-    lines.set_font(shared_state.assets.font, Scale::uniform(10.0));
+    lines.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
 
     let mut score = Text::new(format!("SCORE: {}", score_board.score));
-    score.set_font(shared_state.assets.font, Scale::uniform(10.0));
+    score.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
 
     let mut level = Text::new(format!("LEVEL: {}", score_board.level));
-    level.set_font(shared_state.assets.font, Scale::uniform(10.0));
+    level.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
 
     let mut next_piece = Text::new("NEXT");
-    next_piece.set_font(shared_state.assets.font, Scale::uniform(10.0));
+    next_piece.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
 
     graphics::draw(
         ctx,
         &lines,
-        (ScreenPoint2::new(BOARD_WIDTH * 2.0 + BOARD_WIDTH / 8.0, 2.0 * BOARD_HEIGHT / 8.0), graphics::WHITE),
+        (layout::right_panel(2.0), shared_state.assets.skin.colors.text),
     )?;
 
     graphics::draw(
         ctx,
         &score,
-        (ScreenPoint2::new(BOARD_WIDTH * 2.0 + BOARD_WIDTH / 8.0, 3.0 * BOARD_HEIGHT / 8.0), graphics::WHITE),
+        (layout::right_panel(3.0), shared_state.assets.skin.colors.text),
     )?;
 
     graphics::draw(
         ctx,
         &level,
-        (ScreenPoint2::new(BOARD_WIDTH * 2.0 + BOARD_WIDTH / 8.0, 4.0 * BOARD_HEIGHT / 8.0), graphics::WHITE),
+        (layout::right_panel(4.0), shared_state.assets.skin.colors.text),
     )?;
 
     graphics::draw(
         ctx,
         &next_piece,
-        (ScreenPoint2::new(BOARD_WIDTH * 2.0 + BOARD_WIDTH / 8.0, 5.5 * BOARD_HEIGHT / 8.0), graphics::WHITE),
+        (layout::right_panel(5.5), shared_state.assets.skin.colors.text),
     )
 }
 
+pub fn draw_master_hud(
+    ctx: &mut Context,
+    master: &Master,
+    score_board: &ScoreBoard,
+    shared_state: &SharedState,
+) -> GameResult {
+    let seconds = master.frames / 60;
+    let mut time = Text::new(format!("TIME: {:02}:{:02}.{:02}", seconds / 60, seconds % 60, (master.frames % 60) * 100 / 60));
+    time.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+
+    let mut section = Text::new(format!("{:03}/{:03}", score_board.level, master.section_stop(score_board)));
+    section.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+
+    let mut grade = if master.finished {
+        Text::new(format!("GRADE: {}", master.grade_name()))
+    } else if master.roll.is_some() {
+        Text::new("CREDITS")
+    } else {
+        Text::new("GRADE: ?")
+    };
+    grade.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+
+    graphics::draw(
+        ctx,
+        &time,
+        (layout::left_panel(2.0), shared_state.assets.skin.colors.text),
+    )?;
+
+    graphics::draw(
+        ctx,
+        &section,
+        (layout::left_panel(3.0), shared_state.assets.skin.colors.text),
+    )?;
+
+    graphics::draw(
+        ctx,
+        &grade,
+        (layout::left_panel(4.0), shared_state.assets.skin.colors.text),
+    )
+}
+
+/// A clock counting `frames`, with a second line such as the lines still to clear.
+pub fn draw_clock_hud(
+    ctx: &mut Context,
+    frames: u32,
+    detail: &str,
+    shared_state: &SharedState,
+) -> GameResult {
+    let seconds = frames / 60;
+    let mut time = Text::new(format!("TIME: {:02}:{:02}.{:02}", seconds / 60, seconds % 60, (frames % 60) * 100 / 60));
+    time.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+
+    let mut detail = Text::new(detail);
+    detail.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+
+    graphics::draw(
+        ctx,
+        &time,
+        (layout::left_panel(2.0), shared_state.assets.skin.colors.text),
+    )?;
+
+    graphics::draw(
+        ctx,
+        &detail,
+        (layout::left_panel(3.0), shared_state.assets.skin.colors.text),
+    )
+}
+
+pub fn draw_survival_hud(
+    ctx: &mut Context,
+    survival: &Survival,
+    shared_state: &SharedState,
+) -> GameResult {
+    let seconds = survival.frames / 60;
+    let mut time = Text::new(format!("TIME: {:02}:{:02}", seconds / 60, seconds % 60));
+    time.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+
+    let mut next_rise = Text::new(format!("NEXT ROW: {:.1}", survival.next_rise as f32 / 60.0));
+    next_rise.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+
+    graphics::draw(
+        ctx,
+        &time,
+        (layout::left_panel(2.0), shared_state.assets.skin.colors.text),
+    )?;
+
+    graphics::draw(
+        ctx,
+        &next_rise,
+        (layout::left_panel(3.0), shared_state.assets.skin.colors.text),
+    )
+}
+
+pub fn draw_finesse_hud(
+    ctx: &mut Context,
+    finesse: &Finesse,
+    shared_state: &SharedState,
+) -> GameResult {
+    let mut lines = vec![
+        format!("PIECES: {}/{}", finesse.pieces, SESSION_PIECES),
+        format!("FAULTS: {}", finesse.faults),
+        format!("FINESSE: {:.0}%", finesse.percentage()),
+        format!("PIECE: +{}", finesse.running_faults),
+    ];
+    if let Some(faults) = finesse.last_faults {
+        lines.push(format!("LAST: +{}", faults));
+    }
+    if finesse.strict {
+        lines.push(String::from("STRICT"));
+    }
+
+    for (index, line) in lines.iter().enumerate() {
+        let mut text = Text::new(line.as_str());
+        text.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+        graphics::draw(
+            ctx,
+            &text,
+            (layout::left_panel(2.0 + 0.5 * index as f32), shared_state.assets.skin.colors.text),
+        )?;
+    }
+    Ok(())
+}
+
+/// Tells the player the game was assisted, and when hints are on whether the hint holds first.
+pub fn draw_assist_hud(
+    ctx: &mut Context,
+    hold_hint: bool,
+    shared_state: &SharedState,
+) -> GameResult {
+    let mut lines = vec!["ASSISTED"];
+    if hold_hint {
+        lines.push("HINT: HOLD");
+    }
+    for (index, line) in lines.iter().enumerate() {
+        let mut text = Text::new(*line);
+        text.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+        graphics::draw(
+            ctx,
+            &text,
+            (layout::right_panel(1.0 + 0.5 * index as f32), shared_state.assets.skin.colors.text),
+        )?;
+    }
+    Ok(())
+}
+
+pub fn draw_hold(
+    assets: &mut Assets,
+    ctx: &mut Context,
+    hold: Option<&TetriminoType>,
+    board_dimensions: (f32, f32),
+) -> GameResult {
+    let mut hold_piece = Text::new("HOLD");
+    hold_piece.set_font(assets.skin.font, Scale::uniform(10.0));
+
+    graphics::draw(
+        ctx,
+        &hold_piece,
+        (layout::left_panel(5.5), assets.skin.colors.text),
+    )?;
+
+    match hold {
+        Some(kind) => draw_tetrimino(assets, ctx, &Tetrimino::from(kind), board_dimensions, Option::from((layout::LEFT_PANEL.x - PLAYFIELD.x, BOARD_HEIGHT / 2.0))),
+        None => Ok(()),
+    }
+}
+
+/// The next piece at full size under the NEXT label, and any further previews at half size
+/// below it.
+pub fn draw_previews(
+    assets: &mut Assets,
+    ctx: &mut Context,
+    queue: &VecDeque<TetriminoType>,
+) -> GameResult {
+    for (index, kind) in queue.iter().enumerate() {
+        if index == 0 {
+            draw_tetrimino(assets, ctx, &Tetrimino::from(kind), (BOARD_WIDTH, BOARD_HEIGHT), Option::from((layout::RIGHT_PANEL.x - PLAYFIELD.x, BOARD_HEIGHT / 2.0)))?;
+        } else {
+            let offset = (layout::RIGHT_PANEL.x - PLAYFIELD.x + BOARD_WIDTH / 6.0, 0.64 * BOARD_HEIGHT + 40.0 * (index - 1) as f32);
+            draw_tetrimino(assets, ctx, &Tetrimino::from(kind), (BOARD_WIDTH / 2.0, BOARD_HEIGHT / 2.0), Option::from(offset))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn draw_puzzle_hud(
+    ctx: &mut Context,
+    puzzle_run: &PuzzleRun,
+    puzzle: &Puzzle,
+    shared_state: &SharedState,
+) -> GameResult {
+    let mut title = Text::new(puzzle.title.to_uppercase());
+    title.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+
+    graphics::draw(
+        ctx,
+        &title,
+        (layout::left_panel(2.0), shared_state.assets.skin.colors.text),
+    )?;
+
+    for (index, goal) in puzzle_run.goals().iter().enumerate() {
+        let mark = if puzzle_run.goal_met(goal) { "x" } else { " " };
+        let mut goal_text = Text::new(format!("[{}] {}", mark, goal.describe()));
+        goal_text.set_font(shared_state.assets.skin.font, Scale::uniform(8.0));
+
+        graphics::draw(
+            ctx,
+            &goal_text,
+            (layout::left_panel(2.5 + 0.5 * index as f32), shared_state.assets.skin.colors.text),
+        )?;
+    }
+
+    let mut pieces = Text::new(format!("PIECES: {}", puzzle_run.pieces_locked));
+    pieces.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+
+    graphics::draw(
+        ctx,
+        &pieces,
+        (layout::left_panel(4.5), shared_state.assets.skin.colors.text),
+    )
+}
+
+/// One line of text centered on the screen.
+pub fn draw_centered(ctx: &mut Context, font: Font, text: &str, size: f32, y: f32, color: Color) -> GameResult {
+    let mut text = Text::new(text);
+    text.set_font(font, Scale::uniform(size));
+    let width = text.width(ctx) as f32;
+    graphics::draw(ctx, &text, (layout::centered(width, y), color))
+}
+
+/// Draws the queued garbage as a red bar rising from the bottom of the board, one cell per line.
+pub fn draw_garbage_meter(
+    ctx: &mut Context,
+    pending: u16,
+    board_dimensions: (f32, f32),
+    x: f32,
+    y_offset: f32,
+) -> GameResult {
+    if pending == 0 {
+        return Ok(());
+    }
+    let (board_width, board_height) = board_dimensions;
+    let cell_height = board_height / 21.0;
+    let height = cell_height * f32::from(pending.min(20));
+    let bottom = world_to_screen_coords(board_width, board_height, &WorldPoint2::new(0, 20)).y + y_offset;
+    let meter = Mesh::new_rectangle(
+        ctx,
+        DrawMode::fill(),
+        Rect::new(x, bottom - height, board_width * layout::GARBAGE_METER, height),
+        graphics::Color::from_rgb(230, 0, 36),
+    )?;
+    graphics::draw(ctx, &meter, graphics::DrawParam::new())
+}
+
+/// The size of a cell on a board of `board_dimensions`, counting its walls and floor.
+fn cell_size(board_dimensions: (f32, f32)) -> (f32, f32) {
+    let (board_width, board_height) = board_dimensions;
+    (board_width / 12.0, board_height / 21.0)
+}
+
+/// The first playfield column stays in place whatever the cell size, so big boards line up with
+/// standard ones.
 fn world_to_screen_coords(board_width: f32, board_height: f32, point: &WorldPoint2) -> ScreenPoint2 {
-    let x = (point.x as f32) * (board_width / 12.0) + BOARD_WIDTH;
-    let y = (point.y as f32) * (board_height / 21.0) + BOARD_HEIGHT / 4.0;
+    let x = (point.x as f32 - 1.0) * (board_width / 12.0) + PLAYFIELD.x + PLAYFIELD.w / 12.0;
+    let y = (point.y as f32) * (board_height / 21.0) + PLAYFIELD.y;
     ScreenPoint2::new(x, y)
 }
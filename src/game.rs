@@ -0,0 +1,621 @@
+use std::collections::VecDeque;
+use ggez::GameResult;
+use rand::{Rng, SeedableRng, StdRng};
+use crate::constants::{TOP_BOUNDARY, SPRINT_LINES, ULTRA_FRAMES};
+use crate::world::{Board, TetriminoType, Tetrimino, ScoreBoard};
+use crate::modes::{GameMode, StackVisibility};
+use crate::modes::master::Master;
+use crate::modes::puzzle::{Puzzle, PuzzleRun, PuzzleOutcome};
+use crate::modes::versus::VersusPlayer;
+use crate::modes::survival::Survival;
+use crate::modes::finesse::Finesse;
+use crate::modes::rules::{RuleSet, PieceGenerator, LockDelay, TopOut};
+use crate::pathfinding;
+use crate::controls::Action;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Falling,
+    LineClear(u32),
+    Are(u32),
+}
+
+/// What happened during a frame, so scenes can play sounds and react to the end of a game.
+#[derive(Debug, PartialEq)]
+pub enum GameEvent {
+    Locked(u8),
+    ToppedOut,
+}
+
+/// A piece as it locked and what it cleared, for spectators and effects.
+#[derive(Debug, Clone)]
+pub struct Lock {
+    pub tetrimino: Tetrimino,
+    pub frame: u32,
+    pub lines: u8,
+    pub t_spin: bool,
+    pub perfect_clear: bool,
+}
+
+/// Everything chosen on the pre-game screen. Without a seed every game deals a new sequence;
+/// `garbage_rows` and `big` are handicaps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameConfig {
+    pub mode: GameMode,
+    pub start_level: u16,
+    pub seed: Option<u32>,
+    pub garbage_rows: u8,
+    pub big: bool,
+    pub rules: RuleSet,
+}
+
+impl GameConfig {
+    pub fn new(mode: GameMode) -> GameConfig {
+        GameConfig {
+            mode,
+            start_level: 0,
+            seed: None,
+            garbage_rows: 0,
+            big: false,
+            rules: match mode {
+                GameMode::Master => RuleSet::arcade(),
+                _ => RuleSet::standard(),
+            },
+        }
+    }
+}
+
+/// Cheap to clone, so a game can be snapshotted and restored frame by frame.
+#[derive(Clone)]
+pub struct GamePlayState {
+    pub board: Board,
+    pub frames: u32,
+    gravity: f32,
+    lock_frames: u32,
+    pub phase: Phase,
+    /// The length of the current line clear delay, for telling how far through it the game is.
+    line_clear_frames: u32,
+    pub game_over: bool,
+    pub topped_out: bool,
+    pub start_again: bool,
+    pub config: GameConfig,
+    /// The seed the pieces are dealt from, chosen at random when the config has none.
+    pub seed: u32,
+    rng: StdRng,
+    generator: PieceGenerator,
+    lock_resets: u8,
+    pub master: Option<Master>,
+    pub puzzle: Option<PuzzleRun>,
+    pub versus: Option<VersusPlayer>,
+    pub survival: Option<Survival>,
+    pub finesse: Option<Finesse>,
+    pub hold_enabled: bool,
+    pub hold_used: bool,
+    pub hold: Option<TetriminoType>,
+    /// Counts the pieces put into play, so a controller can tell a new piece from the last one.
+    pub pieces: u32,
+    pub queue: VecDeque<TetriminoType>,
+    pub last_lock: Option<Lock>,
+    /// Set once hints or the AI have helped, which keeps the game out of high scores.
+    pub assisted: bool,
+    pub score: ScoreBoard,
+    pub tetrimino: Tetrimino,
+}
+
+impl GamePlayState {
+    pub fn new(config: GameConfig, puzzle: Option<&Puzzle>) -> GameResult<GamePlayState> {
+        let mode = config.mode;
+        let board = match puzzle {
+            Some(puzzle) => Board::from_rows(&puzzle.rows),
+            None if config.big => Board::big(),
+            None => Board::new(),
+        };
+        let score = ScoreBoard::with_level(config.start_level);
+        let seed = config.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let generator = PieceGenerator::new(config.rules.randomizer);
+        let hold_enabled = puzzle.map_or(config.rules.hold, |puzzle| puzzle.hold_enabled);
+        let garbage_rows = config.garbage_rows;
+
+        let mut game_play_state = GamePlayState {
+            board,
+            frames: 0,
+            gravity: 0.0,
+            lock_frames: 0,
+            phase: Phase::Falling,
+            line_clear_frames: 0,
+            game_over: false,
+            topped_out: false,
+            config,
+            seed,
+            rng: StdRng::from_seed(&[seed as usize]),
+            generator,
+            lock_resets: 0,
+            master: GamePlayState::mode_state(mode),
+            puzzle: puzzle.map(PuzzleRun::new),
+            versus: None,
+            survival: match mode {
+                GameMode::Survival => Some(Survival::new()),
+                _ => None,
+            },
+            finesse: match mode {
+                GameMode::Finesse { strict } => Some(Finesse::new(strict)),
+                _ => None,
+            },
+            hold_enabled,
+            hold_used: false,
+            assisted: false,
+            hold: puzzle.and_then(|puzzle| puzzle.hold),
+            pieces: 0,
+            queue: VecDeque::new(),
+            last_lock: None,
+            score,
+            start_again: false,
+            tetrimino: Tetrimino::from(&TetriminoType::I),
+        };
+        for _ in 0..garbage_rows {
+            let hole = game_play_state.rng.gen_range(1, game_play_state.board.columns() + 1);
+            game_play_state.board.push_garbage(1, hole, 0);
+        }
+        let first_tetrimino = game_play_state.draw_piece().unwrap();
+        game_play_state.tetrimino = game_play_state.new_piece(&first_tetrimino);
+        game_play_state.fill_queue();
+
+        Ok(game_play_state)
+    }
+
+    fn mode_state(mode: GameMode) -> Option<Master> {
+        match mode {
+            GameMode::Master => Some(Master::new()),
+            _ => None,
+        }
+    }
+
+    /// Advances the game by one frame: gravity, lock delay, line clear delay and entry delay.
+    pub fn step(&mut self) -> Option<GameEvent> {
+        if self.game_over {
+            return None;
+        }
+        self.frames += 1;
+
+        if let Some(master) = &mut self.master {
+            master.tick();
+            if master.finished {
+                self.finish();
+                return None;
+            }
+        }
+
+        if self.config.mode == GameMode::Ultra && self.frames >= ULTRA_FRAMES {
+            self.finish();
+            return None;
+        }
+
+        let rising = match &mut self.survival {
+            Some(survival) => survival.tick(&mut self.score),
+            None => false,
+        };
+        if rising && !self.raise_garbage() {
+            self.top_out();
+            return Some(GameEvent::ToppedOut);
+        }
+
+        let timings = self.config.mode.timings(self.score.level, &self.config.rules);
+        match self.phase {
+            Phase::LineClear(0) => {
+                self.board.clear_full_rows();
+                if timings.are > 0 {
+                    self.phase = Phase::Are(timings.are);
+                } else {
+                    self.spawn();
+                }
+            }
+            Phase::LineClear(frames) => self.phase = Phase::LineClear(frames - 1),
+            Phase::Are(0) => self.spawn(),
+            Phase::Are(frames) => self.phase = Phase::Are(frames - 1),
+            Phase::Falling => {
+                self.gravity += timings.gravity;
+                while self.gravity >= 1.0 {
+                    self.gravity -= 1.0;
+                    if self.tetrimino.move_down(&self.board) {
+                        self.lock_frames = 0;
+                    } else {
+                        self.gravity = 0.0;
+                    }
+                }
+
+                if self.tetrimino.is_grounded(&self.board) {
+                    self.lock_frames += 1;
+                    if self.lock_frames >= timings.lock_delay {
+                        if self.config.rules.top_out == TopOut::SpawnRow && self.tetrimino.pos.y == TOP_BOUNDARY {
+                            self.top_out();
+                            return Some(GameEvent::ToppedOut);
+                        }
+                        if self.judge_finesse() {
+                            return None;
+                        }
+                        let cleaned_lines = self.lock();
+                        if self.topped_out {
+                            return Some(GameEvent::ToppedOut);
+                        }
+                        return Some(GameEvent::Locked(cleaned_lines));
+                    }
+                }
+            }
+        }
+        if self.topped_out {
+            return Some(GameEvent::ToppedOut);
+        }
+        None
+    }
+
+    pub fn apply(&mut self, action: Action) {
+        if !self.piece_active() {
+            return;
+        }
+        if let Some(finesse) = &mut self.finesse {
+            finesse.record(action);
+        }
+
+        let rotation = self.config.rules.rotation;
+        let moved = match action {
+            Action::MoveLeft => self.tetrimino.move_left(&self.board),
+            Action::MoveRight => self.tetrimino.move_right(&self.board),
+            Action::SoftDrop => {
+                if self.tetrimino.move_down(&self.board) {
+                    self.lock_frames = 0;
+                }
+                false
+            }
+            Action::RotateCounterClockwise => {
+                rotation.rotates(self.tetrimino.kind) && self.tetrimino.rotate(false, &self.board, rotation.kicks())
+            }
+            Action::RotateClockwise => {
+                rotation.rotates(self.tetrimino.kind) && self.tetrimino.rotate(true, &self.board, rotation.kicks())
+            }
+            Action::Hold => {
+                self.hold();
+                false
+            }
+        };
+        if moved {
+            self.reset_lock_delay();
+        }
+        if action != Action::SoftDrop {
+            self.track_finesse();
+        }
+    }
+
+    /// Under move reset lock delay, moving or rotating a grounded piece restarts its lock delay
+    /// a limited number of times.
+    fn reset_lock_delay(&mut self) {
+        if let LockDelay::Move { limit, .. } = self.config.rules.lock_delay {
+            if self.lock_frames > 0 && self.lock_resets < limit {
+                self.lock_frames = 0;
+                self.lock_resets += 1;
+            }
+        }
+    }
+
+    fn draw_piece(&mut self) -> Option<TetriminoType> {
+        match &mut self.puzzle {
+            Some(puzzle) => puzzle.next_piece(),
+            None => Some(self.generator.next(&mut self.rng)),
+        }
+    }
+
+    /// Tops the preview queue up to the number of previews the rules show, and at least one.
+    fn fill_queue(&mut self) {
+        let previews = usize::from(self.config.rules.previews.max(1));
+        while self.queue.len() < previews {
+            match self.draw_piece() {
+                Some(kind) => self.queue.push_back(kind),
+                None => break,
+            }
+        }
+    }
+
+    fn spawn(&mut self) {
+        let kind = match self.queue.pop_front().or_else(|| self.hold.take()) {
+            Some(kind) => kind,
+            None => {
+                self.game_over = true;
+                return;
+            }
+        };
+        self.tetrimino = self.new_piece(&kind);
+        self.pieces += 1;
+        self.fill_queue();
+        self.gravity = 0.0;
+        self.lock_frames = 0;
+        self.lock_resets = 0;
+        self.hold_used = false;
+        self.phase = Phase::Falling;
+        if let Some(master) = &mut self.master {
+            master.on_spawn(&mut self.score);
+        }
+        if self.config.rules.top_out == TopOut::BlockOut && self.tetrimino.collides(&self.board) {
+            self.top_out();
+        }
+    }
+
+    /// Judges the finesse of the piece about to lock. In strict mode a piece with faults goes
+    /// back to the top instead of locking, and this returns true.
+    fn judge_finesse(&mut self) -> bool {
+        let start = self.new_piece(&self.tetrimino.kind);
+        let finesse = match &mut self.finesse {
+            Some(finesse) => finesse,
+            None => return false,
+        };
+        if finesse.on_lock(&start, &self.tetrimino, &self.board, self.config.rules.rotation) == 0 || !finesse.strict {
+            return false;
+        }
+        self.tetrimino = start;
+        self.pieces += 1;
+        self.gravity = 0.0;
+        self.lock_frames = 0;
+        true
+    }
+
+    /// Updates the running finesse faults of the active piece after a press.
+    fn track_finesse(&mut self) {
+        if self.finesse.is_none() {
+            return;
+        }
+        let start = self.new_piece(&self.tetrimino.kind);
+        let mut landing = self.tetrimino.clone();
+        while landing.move_down(&self.board) {}
+        if let Some(finesse) = &mut self.finesse {
+            finesse.track(&start, &landing, &self.board, self.config.rules.rotation);
+        }
+    }
+
+    /// A piece of the given kind where pieces enter the board under the current rules.
+    pub fn new_piece(&self, kind: &TetriminoType) -> Tetrimino {
+        pathfinding::spawn(*kind, &self.board, &self.config.rules)
+    }
+
+    fn hold(&mut self) {
+        if !self.hold_enabled || self.hold_used {
+            return;
+        }
+        if let Some(finesse) = &mut self.finesse {
+            finesse.on_hold();
+        }
+        match self.hold.replace(self.tetrimino.kind) {
+            Some(held) => {
+                self.tetrimino = self.new_piece(&held);
+                self.pieces += 1;
+                self.gravity = 0.0;
+                self.lock_frames = 0;
+                self.lock_resets = 0;
+            }
+            None => self.spawn(),
+        }
+        self.hold_used = true;
+    }
+
+    /// Locks the active piece into the board and enters the line clear or entry delay,
+    /// spawning the next piece straight away when both delays are zero. Cleared lines stay on
+    /// the board until the line clear delay is over.
+    fn lock(&mut self) -> u8 {
+        let t_spin = self.tetrimino.is_t_spin(&self.board);
+        self.board.place(&self.tetrimino, self.frames);
+        let mut cleared = self.board.clone();
+        let mut cleaned_lines = cleared.clear_full_rows();
+        if self.config.big {
+            cleaned_lines *= 2;
+        }
+        let perfect_clear = cleared.is_empty();
+        self.last_lock = Some(Lock {
+            tetrimino: self.tetrimino.clone(),
+            frame: self.frames,
+            lines: cleaned_lines,
+            t_spin,
+            perfect_clear,
+        });
+        match &mut self.master {
+            Some(master) => {
+                if master.on_lock(&mut self.score, cleaned_lines, perfect_clear) {
+                    self.board = if self.config.big { Board::big() } else { Board::new() };
+                }
+            }
+            None => self.score.add_lines(cleaned_lines, t_spin, &self.config.rules),
+        }
+        if let Some(survival) = &self.survival {
+            survival.update_score(&mut self.score);
+        }
+
+        if let Some(versus) = &mut self.versus {
+            let mut overflow = false;
+            for (lines, hole) in versus.on_lock(cleaned_lines, t_spin, perfect_clear) {
+                overflow |= !self.board.push_garbage(lines, hole, self.frames);
+            }
+            if overflow {
+                self.top_out();
+                return cleaned_lines;
+            }
+        }
+
+        if let Some(puzzle) = &mut self.puzzle {
+            let pieces_left = !self.queue.is_empty() || self.hold.is_some();
+            puzzle.on_lock(cleaned_lines, t_spin, perfect_clear, pieces_left);
+            self.game_over |= puzzle.outcome.is_some();
+        }
+        if self.config.mode == GameMode::Sprint && self.score.lines >= SPRINT_LINES {
+            self.game_over = true;
+        }
+        if self.finesse.as_ref().is_some_and(Finesse::finished) {
+            self.game_over = true;
+        }
+        if self.game_over {
+            self.finish();
+            return cleaned_lines;
+        }
+
+        let timings = self.config.mode.timings(self.score.level, &self.config.rules);
+        if cleaned_lines > 0 && timings.line_clear > 0 {
+            self.line_clear_frames = timings.line_clear;
+            self.phase = Phase::LineClear(timings.line_clear);
+            return cleaned_lines;
+        }
+        self.board.clear_full_rows();
+        if timings.are > 0 {
+            self.phase = Phase::Are(timings.are);
+        } else {
+            self.spawn();
+        }
+        cleaned_lines
+    }
+
+    /// Pushes a survival garbage row in from below, lifting the falling piece out of its way.
+    /// Returns false when the stack or the piece is pushed off the top.
+    fn raise_garbage(&mut self) -> bool {
+        // Lines waiting out the line clear delay would be counted as part of the stack.
+        self.board.clear_full_rows();
+        let hole = self.rng.gen_range(1, self.board.columns() + 1);
+        if !self.board.push_garbage(1, hole, self.frames) {
+            return false;
+        }
+        self.phase != Phase::Falling || self.tetrimino.lift_clear_of(&self.board)
+    }
+
+    /// Ends the game with the stack as it ends up, without lines still waiting to be cleared.
+    fn finish(&mut self) {
+        self.game_over = true;
+        self.board.clear_full_rows();
+    }
+
+    fn top_out(&mut self) {
+        self.game_over = true;
+        self.topped_out = true;
+        if let Some(master) = &mut self.master {
+            master.finish();
+        }
+        if let Some(puzzle) = &mut self.puzzle {
+            puzzle.outcome = Some(PuzzleOutcome::Failed);
+        }
+    }
+
+    pub fn stack_visibility(&self) -> StackVisibility {
+        if self.game_over {
+            return StackVisibility::Visible;
+        }
+        match &self.master {
+            Some(master) => master.stack_visibility(),
+            None => self.config.mode.stack_visibility(),
+        }
+    }
+
+    /// How far through the line clear delay the game is, from 0 to 1, while cleared lines are
+    /// still on the board.
+    pub fn clear_progress(&self) -> Option<f32> {
+        match self.phase {
+            Phase::LineClear(frames) if self.line_clear_frames > 0 => {
+                Some(1.0 - frames as f32 / self.line_clear_frames as f32)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn piece_active(&self) -> bool {
+        self.phase == Phase::Falling && !self.game_over
+    }
+}
+
+pub(crate) fn get_random_tetrimino_type<R: Rng>(rng: &mut R) -> Option<TetriminoType> {
+    TetriminoType::from_code(rng.gen_range(1, 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{BoardType, Cell};
+
+    const LINE_CLEAR: u32 = 10;
+    const ARE: u32 = 5;
+
+    /// A game with line clear and entry delays whose bottom row lacks the four cells on the left,
+    /// and an I piece over that gap.
+    fn game(mode: GameMode) -> GamePlayState {
+        let mut config = GameConfig::new(mode);
+        config.seed = Some(3);
+        config.rules.lock_delay = LockDelay::Step(1);
+        config.rules.line_clear = LINE_CLEAR;
+        config.rules.are = ARE;
+        let mut state = GamePlayState::new(config, None).unwrap();
+        let bottom = state.board.rows() - 1;
+        for column in 5..=state.board.columns() {
+            state.board.data[(bottom, column)] = Cell { code: BoardType::GARBAGE_CODE, locked_at: 0 };
+        }
+        state.tetrimino = state.new_piece(&TetriminoType::I);
+        while state.tetrimino.move_left(&state.board) {}
+        while state.tetrimino.move_down(&state.board) {}
+        state
+    }
+
+    fn lock(state: &mut GamePlayState) -> u8 {
+        for _ in 0..10 {
+            if let Some(GameEvent::Locked(lines)) = state.step() {
+                return lines;
+            }
+        }
+        panic!("the piece never locked");
+    }
+
+    #[test]
+    fn cleared_rows_stay_until_the_line_clear_delay_ends() {
+        let mut state = game(GameMode::Marathon);
+        let bottom = state.board.rows() - 1;
+        assert_eq!(lock(&mut state), 1);
+        assert_eq!(state.phase, Phase::LineClear(LINE_CLEAR));
+        for _ in 0..LINE_CLEAR {
+            assert_eq!(state.board.full_rows(), vec![bottom]);
+            state.step();
+        }
+        assert_eq!(state.board.full_rows(), vec![bottom]);
+        state.step();
+        assert!(state.board.full_rows().is_empty());
+        assert!(state.board.is_empty());
+    }
+
+    #[test]
+    fn the_entry_delay_comes_before_the_next_piece() {
+        let mut state = game(GameMode::Marathon);
+        lock(&mut state);
+        let pieces = state.pieces;
+        for _ in 0..=LINE_CLEAR {
+            state.step();
+        }
+        assert_eq!(state.phase, Phase::Are(ARE));
+        for _ in 0..ARE {
+            assert!(!state.piece_active());
+            assert_eq!(state.pieces, pieces);
+            state.step();
+        }
+        state.step();
+        assert!(state.piece_active());
+        assert_eq!(state.pieces, pieces + 1);
+    }
+
+    #[test]
+    fn the_score_and_perfect_clears_see_the_cleared_board() {
+        let mut state = game(GameMode::Marathon);
+        lock(&mut state);
+        assert!(!state.board.is_empty());
+        assert_eq!(state.score.lines, 1);
+        assert!(state.score.score > 0);
+        let last_lock = state.last_lock.as_ref().unwrap();
+        assert_eq!(last_lock.lines, 1);
+        assert!(last_lock.perfect_clear);
+    }
+
+    #[test]
+    fn finishing_drops_rows_waiting_to_be_cleared() {
+        let mut state = game(GameMode::Sprint);
+        state.score.lines = SPRINT_LINES - 1;
+        lock(&mut state);
+        assert!(state.game_over);
+        assert!(state.board.full_rows().is_empty());
+        assert!(state.board.is_empty());
+    }
+}
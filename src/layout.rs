@@ -0,0 +1,162 @@
+//! Resolution independence. Every scene draws on a virtual screen of `SCREEN_WIDTH` by
+//! `SCREEN_HEIGHT`, placing things relative to the regions below, and `Layout` fits that screen
+//! into the window whatever its size, centered with bars on the sides that are left over.
+
+use ggez::{Context, GameResult, graphics};
+use ggez::conf::FullscreenType;
+use ggez::graphics::Rect;
+use crate::constants::{BOARD_WIDTH, BOARD_HEIGHT, SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::types::ScreenPoint2;
+
+/// The standard playfield, walls and floor included.
+pub const PLAYFIELD: Rect = Rect::new(BOARD_WIDTH, BOARD_HEIGHT / 4.0, BOARD_WIDTH, BOARD_HEIGHT);
+/// The columns either side of the playfield, where the HUD goes.
+pub const LEFT_PANEL: Rect = Rect::new(0.0, 0.0, BOARD_WIDTH, SCREEN_HEIGHT);
+pub const RIGHT_PANEL: Rect = Rect::new(BOARD_WIDTH * 2.0, 0.0, BOARD_WIDTH, SCREEN_HEIGHT);
+/// The spacing of HUD lines in the side panels.
+pub const HUD_LINE: f32 = BOARD_HEIGHT / 8.0;
+/// The width of a garbage meter, as a fraction of the width of its board.
+pub const GARBAGE_METER: f32 = 1.0 / 48.0;
+/// The left edge of text on menu pages.
+pub const MARGIN: f32 = 50.0;
+/// The spacing of lines of centered text, like the help lines at the bottom of the screen.
+pub const TEXT_LINE: f32 = 20.0;
+/// Where the two boards of a match are moved from the standard playfield, each toward its side
+/// of the screen and over the side panel there.
+pub const SPLIT_BOARDS: [f32; 2] = [-BOARD_WIDTH + 40.0, BOARD_WIDTH - 40.0];
+/// Where the next and held pieces of a board without side panels are drawn: above it, and this
+/// far to either side.
+pub const PREVIEW_X_OFFSET: f32 = BOARD_WIDTH / 4.0;
+pub const PREVIEW_Y_OFFSET: f32 = -90.0;
+/// The top of the start screen's menu, and the spacing of its entries.
+const MENU_TOP: f32 = 190.0;
+const MENU_LINE: f32 = 20.0;
+/// The gap kept under the last help line.
+const FOOTER_MARGIN: f32 = 17.5;
+
+/// How the virtual screen is scaled to the window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scaling {
+    /// As large as fits, by any factor.
+    Fit,
+    /// As large as fits by a whole factor, so every virtual pixel covers the same number of
+    /// window pixels.
+    Integer,
+}
+
+impl Scaling {
+    pub fn from_name(name: &str) -> Option<Scaling> {
+        match name {
+            "fit" => Some(Scaling::Fit),
+            "integer" => Some(Scaling::Integer),
+            _ => None,
+        }
+    }
+}
+
+pub struct Layout {
+    pub scaling: Scaling,
+    pub fullscreen: bool,
+}
+
+impl Layout {
+    pub fn new(scaling: Scaling, fullscreen: bool) -> Layout {
+        Layout { scaling, fullscreen }
+    }
+
+    /// Fits the virtual screen to the window again, after it was resized or changed display.
+    /// The drawable size is in physical pixels, so on a HiDPI display the game is drawn at the
+    /// display's full resolution.
+    pub fn apply(&self, ctx: &mut Context) -> GameResult {
+        let (width, height) = graphics::drawable_size(ctx);
+        let fit = (width / SCREEN_WIDTH).min(height / SCREEN_HEIGHT);
+        // Window pixels per virtual pixel.
+        let scale = match self.scaling {
+            Scaling::Integer if fit >= 1.0 => fit.floor(),
+            _ => fit,
+        };
+        // The bars are whole pixels wide, so the screen's pixels line up with the window's.
+        let left = ((width - SCREEN_WIDTH * scale) / 2.0).floor();
+        let top = ((height - SCREEN_HEIGHT * scale) / 2.0).floor();
+        graphics::set_screen_coordinates(
+            ctx,
+            Rect::new(-left / scale, -top / scale, width / scale, height / scale),
+        )
+    }
+
+    pub fn toggle_fullscreen(&mut self, ctx: &mut Context) -> GameResult {
+        self.fullscreen = !self.fullscreen;
+        graphics::set_fullscreen(ctx, self.fullscreen_type())?;
+        self.apply(ctx)
+    }
+
+    pub fn toggle_scaling(&mut self, ctx: &mut Context) -> GameResult {
+        self.scaling = match self.scaling {
+            Scaling::Fit => Scaling::Integer,
+            Scaling::Integer => Scaling::Fit,
+        };
+        self.apply(ctx)
+    }
+
+    /// Fullscreen takes the desktop's resolution, so switching is quick and the scaling decides
+    /// how the game fills it.
+    pub fn fullscreen_type(&self) -> FullscreenType {
+        if self.fullscreen {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Windowed
+        }
+    }
+}
+
+/// HUD line `line` of the left panel.
+pub fn left_panel(line: f32) -> ScreenPoint2 {
+    panel(LEFT_PANEL, line)
+}
+
+/// HUD line `line` of the right panel.
+pub fn right_panel(line: f32) -> ScreenPoint2 {
+    panel(RIGHT_PANEL, line)
+}
+
+fn panel(panel: Rect, line: f32) -> ScreenPoint2 {
+    ScreenPoint2::new(panel.x + panel.w / 8.0, panel.y + line * HUD_LINE)
+}
+
+/// Where something `width` wide starts to be centered on the screen at height `y`.
+pub fn centered(width: f32, y: f32) -> ScreenPoint2 {
+    ScreenPoint2::new((SCREEN_WIDTH - width) / 2.0, y)
+}
+
+/// A point on the left margin of a menu page, `y` down from the top.
+pub fn page(y: f32) -> ScreenPoint2 {
+    ScreenPoint2::new(MARGIN, y)
+}
+
+/// The height of entry `index` of the start screen's menu.
+pub fn menu_entry(index: usize) -> f32 {
+    MENU_TOP + index as f32 * MENU_LINE
+}
+
+/// The height of help line `line` at the bottom of the screen, counting up from the last one.
+pub fn footer(line: f32) -> f32 {
+    SCREEN_HEIGHT - FOOTER_MARGIN - line * TEXT_LINE
+}
+
+/// The height of centered text line `line`, counting down from the top of the playfield, for the
+/// lobby pages and the space between the boards of a match. Negative lines are above it.
+pub fn centered_line(line: f32) -> f32 {
+    PLAYFIELD.y + line * TEXT_LINE
+}
+
+/// Where the garbage meter starts for a board moved `x_offset` from the standard playfield and
+/// drawn `scale` times its size. Boards on the left have it on their right and the others on
+/// their left, so in a match both meters face the middle of the screen.
+pub fn garbage_meter(x_offset: f32, scale: f32) -> f32 {
+    let width = PLAYFIELD.w * scale * GARBAGE_METER;
+    if x_offset < 0.0 {
+        PLAYFIELD.x + x_offset + PLAYFIELD.w * scale + width
+    } else {
+        PLAYFIELD.x + x_offset - 2.0 * width
+    }
+}
@@ -0,0 +1,289 @@
+//! Leaderboards kept by a self-hosted server. A finished game is sent with its replay, and the
+//! server plays the replay again to check the claimed result before ranking it. The server is the
+//! `leaderboard` binary; its HTTP API is documented in `docs/leaderboard.md`.
+
+use std::cmp::Ordering;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use crate::constants::SPRINT_LINES;
+use crate::game::{GameConfig, GamePlayState};
+use crate::modes::GameMode;
+use crate::modes::rules::RuleSet;
+use crate::net;
+use crate::replay::{mode_code, Replay};
+use crate::tbp::json::Json;
+
+pub const DEFAULT_PORT: u16 = 7881;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A finished game as sent to the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Submission {
+    pub name: String,
+    pub score: u32,
+    pub lines: u16,
+    pub frames: u32,
+    /// Whether hints or the AI helped, which keeps the game off the leaderboards.
+    pub assisted: bool,
+    pub replay: Replay,
+}
+
+impl Submission {
+    pub fn of(name: &str, state: &GamePlayState, replay: Replay) -> Submission {
+        Submission {
+            name: String::from(name),
+            score: state.score.score,
+            lines: state.score.lines,
+            frames: state.frames,
+            assisted: state.assisted,
+            replay,
+        }
+    }
+
+    /// Plays the replay and checks that it ends the way the submission claims, giving the entry
+    /// to rank if it does.
+    pub fn verify(&self) -> Result<Entry, String> {
+        if self.assisted {
+            return Err(String::from("Assisted games are not ranked"));
+        }
+        let config = &self.replay.config;
+        eligible(config)?;
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > 16 || name.chars().any(char::is_control) {
+            return Err(String::from("Names are 1 to 16 characters long"));
+        }
+
+        let state = self.replay.play().map_err(|error| error.to_string())?;
+        if !state.game_over {
+            return Err(String::from("The replay ends before the game does"));
+        }
+        let played = (state.score.score, state.score.lines, state.frames);
+        if played != (self.score, self.lines, self.frames) {
+            return Err(format!(
+                "The replay scores {} with {} lines in {} frames, not {} with {} lines in {} frames",
+                played.0, played.1, played.2, self.score, self.lines, self.frames,
+            ));
+        }
+        if config.mode == GameMode::Sprint && (state.topped_out || state.score.lines < SPRINT_LINES) {
+            return Err(format!("Sprint games only count once {} lines are cleared", SPRINT_LINES));
+        }
+        Ok(Entry {
+            id: 0,
+            name: String::from(name),
+            mode: mode_code(config.mode),
+            rules: config.rules.name.clone(),
+            start_level: config.start_level,
+            score: state.score.score,
+            lines: state.score.lines,
+            frames: state.frames,
+        })
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("name", Json::string(&self.name)),
+            ("score", Json::Number(f64::from(self.score))),
+            ("lines", Json::Number(f64::from(self.lines))),
+            ("frames", Json::Number(f64::from(self.frames))),
+            ("assisted", Json::Bool(self.assisted)),
+            ("replay", self.replay.to_json()),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Result<Submission, String> {
+        Ok(Submission {
+            name: string(json, "name")?.to_string(),
+            score: number(json, "score")? as u32,
+            lines: number(json, "lines")? as u16,
+            frames: number(json, "frames")? as u32,
+            assisted: json.get("assisted").and_then(Json::as_bool).unwrap_or(false),
+            replay: Replay::from_json(json.get("replay").ok_or("Expected a replay")?)?,
+        })
+    }
+}
+
+/// Whether games played with `config` can be ranked: single player modes with a score, played
+/// under one of the built-in rule presets on a normal board.
+pub fn eligible(config: &GameConfig) -> Result<(), String> {
+    match config.mode {
+        GameMode::Puzzle | GameMode::Versus | GameMode::Coop | GameMode::Finesse { .. } => {
+            return Err(format!("{} games are not ranked", config.mode.name()));
+        }
+        _ => (),
+    }
+    if config.big {
+        return Err(String::from("Big mode games are not ranked"));
+    }
+    if !RuleSet::presets().contains(&config.rules) {
+        return Err(String::from("Only games under the built-in rule presets are ranked"));
+    }
+    Ok(())
+}
+
+/// A verified result on a leaderboard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Given by the server, for fetching the replay.
+    pub id: u32,
+    pub name: String,
+    /// The mode as `replay::mode_code` names it.
+    pub mode: String,
+    pub rules: String,
+    pub start_level: u16,
+    pub score: u32,
+    pub lines: u16,
+    pub frames: u32,
+}
+
+impl Entry {
+    /// Better entries come first: the fastest sprints, and the highest scores in every other mode,
+    /// with the faster game first on a tie. Among equals, the earlier entry stays ahead.
+    pub fn rank(&self, other: &Entry) -> Ordering {
+        let by_time = self.frames.cmp(&other.frames);
+        let order = if self.mode == mode_code(GameMode::Sprint) {
+            by_time
+        } else {
+            other.score.cmp(&self.score).then(by_time)
+        };
+        order.then(self.id.cmp(&other.id))
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("id", Json::Number(f64::from(self.id))),
+            ("name", Json::string(&self.name)),
+            ("mode", Json::string(&self.mode)),
+            ("rules", Json::string(&self.rules)),
+            ("start_level", Json::Number(f64::from(self.start_level))),
+            ("score", Json::Number(f64::from(self.score))),
+            ("lines", Json::Number(f64::from(self.lines))),
+            ("frames", Json::Number(f64::from(self.frames))),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Result<Entry, String> {
+        Ok(Entry {
+            id: number(json, "id")? as u32,
+            name: string(json, "name")?.to_string(),
+            mode: string(json, "mode")?.to_string(),
+            rules: string(json, "rules")?.to_string(),
+            start_level: number(json, "start_level")? as u16,
+            score: number(json, "score")? as u32,
+            lines: number(json, "lines")? as u16,
+            frames: number(json, "frames")? as u32,
+        })
+    }
+}
+
+/// Sends a game to the server at `url`, such as `http://localhost:7881`. Gives back the entry as
+/// ranked, with its `rank` on its leaderboard, or why the server turned it down.
+pub fn submit(url: &str, submission: &Submission) -> Result<Json, String> {
+    request(url, "POST", "/scores", Some(&submission.to_json()))
+}
+
+/// The ranked entries for a mode, best first, with only those played under `rules` if given.
+pub fn leaderboard(url: &str, mode: GameMode, rules: Option<&str>) -> Result<Vec<Entry>, String> {
+    let mut path = format!("/leaderboards/{}", mode_code(mode));
+    if let Some(rules) = rules {
+        path.push_str(&format!("?rules={}", rules.replace(' ', "%20")));
+    }
+    let response = request(url, "GET", &path, None)?;
+    response.get("entries").and_then(Json::as_array).ok_or("Expected entries")?
+        .iter()
+        .map(Entry::from_json)
+        .collect()
+}
+
+/// A minimal HTTP/1.1 client: plain `http://` only, one request per connection, JSON both ways.
+fn request(url: &str, method: &str, path: &str, body: Option<&Json>) -> Result<Json, String> {
+    let rest = url.strip_prefix("http://").ok_or("Only http:// server URLs are supported")?;
+    let (host, base) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], rest[slash..].trim_end_matches('/')),
+        None => (rest, ""),
+    };
+    let socket_address = net::resolve(host, 80).map_err(|error| error.to_string())?;
+    let mut stream = TcpStream::connect_timeout(&socket_address, TIMEOUT).map_err(|error| error.to_string())?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|error| error.to_string())?;
+
+    let body = body.map(Json::to_string).unwrap_or_default();
+    let request = format!(
+        "{} {}{} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method, base, path, host, body.len(), body,
+    );
+    stream.write_all(request.as_bytes()).map_err(|error| error.to_string())?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).map_err(|error| error.to_string())?;
+
+    let status = response.split(' ').nth(1).and_then(|status| status.parse::<u16>().ok()).ok_or("Bad response")?;
+    let content = response.split("\r\n\r\n").nth(1).unwrap_or("");
+    let json = Json::parse(content).map_err(|_| format!("The server answered {}", status))?;
+    if status >= 400 {
+        let error = json.get("error").and_then(Json::as_str).unwrap_or("the request failed");
+        return Err(format!("The server answered {}: {}", status, error));
+    }
+    Ok(json)
+}
+
+fn number(json: &Json, key: &str) -> Result<i64, String> {
+    json.get(key).and_then(Json::as_i64).ok_or(format!("Expected a number for '{}'", key))
+}
+
+fn string<'a>(json: &'a Json, key: &str) -> Result<&'a str, String> {
+    json.get(key).and_then(Json::as_str).ok_or(format!("Expected text for '{}'", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::tests::record;
+
+    fn ultra_submission() -> Submission {
+        let (state, replay) = record(GameMode::Ultra, u32::MAX);
+        assert!(state.game_over);
+        Submission::of("ADA", &state, replay)
+    }
+
+    #[test]
+    fn verifies_an_honest_game() {
+        let submission = ultra_submission();
+        let entry = submission.verify().unwrap();
+        assert_eq!(entry.name, "ADA");
+        assert_eq!(entry.mode, "ultra");
+        assert_eq!(entry.score, submission.score);
+        assert_eq!(entry.frames, submission.frames);
+    }
+
+    #[test]
+    fn rejects_tampered_results() {
+        let honest = ultra_submission();
+        let mut score = honest.clone();
+        score.score += 100;
+        assert!(score.verify().is_err());
+        let mut lines = honest.clone();
+        lines.lines += 1;
+        assert!(lines.verify().is_err());
+        let mut frames = honest.clone();
+        frames.frames -= 1;
+        assert!(frames.verify().is_err());
+        let mut cut_short = honest.clone();
+        cut_short.replay.frames.truncate(100);
+        assert!(cut_short.verify().is_err());
+        let mut assisted = honest;
+        assisted.assisted = true;
+        assert!(assisted.verify().is_err());
+    }
+
+    #[test]
+    fn ranks_only_eligible_games() {
+        assert!(eligible(&GameConfig::new(GameMode::Marathon)).is_ok());
+        assert!(eligible(&GameConfig::new(GameMode::Versus)).is_err());
+        assert!(eligible(&GameConfig::new(GameMode::Puzzle)).is_err());
+        let mut big = GameConfig::new(GameMode::Marathon);
+        big.big = true;
+        assert!(eligible(&big).is_err());
+        let mut custom = GameConfig::new(GameMode::Marathon);
+        custom.rules.previews = 3;
+        assert!(eligible(&custom).is_err());
+    }
+}
@@ -0,0 +1,16 @@
+//! The rules of the game, without any drawing, sound or windowing, so bots, tools and training
+//! environments can run games headless. The game itself is the `tetris` binary.
+
+pub mod ai;
+pub mod constants;
+pub mod controls;
+pub mod game;
+pub mod leaderboard;
+pub mod modes;
+pub mod net;
+pub mod pathfinding;
+pub mod replay;
+pub mod rl;
+pub mod tbp;
+pub mod types;
+pub mod world;
@@ -1,38 +1,93 @@
-use ggez::event::{KeyMods, KeyCode};
+use ggez::event::{KeyMods, KeyCode, Button, GamepadId};
 use ggez::{GameResult, Context, ContextBuilder, conf, event};
 use std::env;
 use std::path;
+use std::time::Duration;
 use scenes::start;
-use scenes::game_over;
-use scenes::game_play;
-use types::SceneStack;
+use scenes::online::{self, Launch};
+use scenes::SceneStack;
 use crate::resources::Assets;
+use crate::layout::{Layout, Scaling};
 use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::modes::puzzle::{Puzzle, PuzzlePack, PuzzleProgress};
+use crate::net::simulator::Conditions;
+use crate::net::spectate::{Broadcaster, SPECTATE_PORT};
 use ggez::audio::SoundSource;
 
+use tetris::{ai, constants, controls, game, leaderboard, modes, net, replay, tbp, types, world};
+
 mod scenes;
-mod world;
-mod constants;
-mod types;
 mod drawing;
+mod layout;
 mod resources;
+mod skins;
 
 pub struct SharedState {
-    game_started: bool,
+    puzzle_pack: Option<PuzzlePack>,
+    puzzle_index: usize,
+    puzzle_progress: PuzzleProgress,
+    bot_command: Option<String>,
+    online_launch: Option<Launch>,
+    port: u16,
+    network_conditions: Option<Conditions>,
+    /// The leaderboard server finished games are sent to, from `--leaderboard <url>`.
+    leaderboard_url: Option<String>,
+    /// Sends every game played to spectators, from `--broadcast [port]`.
+    broadcaster: Option<Broadcaster>,
+    layout: Layout,
     assets: Assets
 }
 
 impl SharedState {
     fn new(ctx: &mut Context) -> GameResult<SharedState> {
-        let assets = Assets::new(ctx)?;
+        let mut assets = Assets::new(ctx)?;
+        if let Some(name) = argument("--skin") {
+            match assets.skins.iter().position(|manifest| manifest.is_called(&name)) {
+                Some(index) => assets.use_skin(ctx, index)?,
+                None => eprintln!("There is no skin called '{}'", name),
+            }
+        }
 
         let s = SharedState {
-            game_started: false,
+            puzzle_pack: None,
+            puzzle_index: 0,
+            puzzle_progress: PuzzleProgress::load(ctx),
+            bot_command: argument("--bot"),
+            online_launch: online_launch(),
+            port: argument("--port").and_then(|port| port.parse().ok()).unwrap_or(net::DEFAULT_PORT),
+            network_conditions: network_conditions(),
+            leaderboard_url: argument("--leaderboard"),
+            broadcaster: broadcaster(),
+            layout: layout(),
             assets
         };
 
         Ok(s)
     }
+
+    fn current_puzzle(&self) -> Option<&Puzzle> {
+        self.puzzle_pack.as_ref().and_then(|pack| pack.puzzles.get(self.puzzle_index))
+    }
+
+    fn next_puzzle(&mut self) -> bool {
+        let puzzle_count = self.puzzle_pack.as_ref().map_or(0, |pack| pack.puzzles.len());
+        if self.puzzle_index + 1 < puzzle_count {
+            self.puzzle_index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn complete_current_puzzle(&mut self, ctx: &mut Context) {
+        if let Some(pack) = &self.puzzle_pack {
+            if let Some(puzzle) = pack.puzzles.get(self.puzzle_index) {
+                if let Err(error) = self.puzzle_progress.complete(ctx, pack, puzzle) {
+                    eprintln!("Could not save puzzle progress: {}", error);
+                }
+            }
+        }
+    }
 }
 
 struct MainState {
@@ -45,12 +100,16 @@ impl MainState {
         shared_state.assets.theme.set_repeat(true);
         shared_state.assets.theme.play()?;
 
+        shared_state.layout.apply(ctx)?;
+
         let mut main_state = MainState {
             scenes: SceneStack::new(ctx, shared_state)
         };
-        main_state.scenes.push(game_over::GameOverScene::new()?);
-        main_state.scenes.push(game_play::GamePlayScene::new(ctx)?);
         main_state.scenes.push(start::StartScene::new()?);
+        if main_state.scenes.world.online_launch.is_some() {
+            let online_scene = online::OnlineScene::new(ctx, &mut main_state.scenes.world)?;
+            main_state.scenes.push(online_scene);
+        }
         Ok(main_state)
     }
 }
@@ -66,9 +125,89 @@ impl ggez::event::EventHandler for MainState {
         Ok(())
     }
 
-    fn key_down_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
-        self.scenes.input(keycode, false)
+    /// F11 or Alt+Enter toggles fullscreen and F10 switches between scalings, in every scene.
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, keymods: KeyMods, _repeat: bool) {
+        let layout = &mut self.scenes.world.layout;
+        let changed = match keycode {
+            KeyCode::F11 => Some(layout.toggle_fullscreen(ctx)),
+            KeyCode::Return if keymods.contains(KeyMods::ALT) => Some(layout.toggle_fullscreen(ctx)),
+            KeyCode::F10 => Some(layout.toggle_scaling(ctx)),
+            _ => None,
+        };
+        match changed {
+            Some(Err(error)) => eprintln!("Could not change the display: {}", error),
+            Some(Ok(())) => (),
+            None => self.scenes.input(keycode, false),
+        }
+    }
+
+    fn resize_event(&mut self, ctx: &mut Context, _width: f32, _height: f32) {
+        if let Err(error) = self.scenes.world.layout.apply(ctx) {
+            eprintln!("Could not fit the game to the window: {}", error);
+        }
+    }
+
+    fn gamepad_button_down_event(&mut self, _ctx: &mut Context, button: Button, _id: GamepadId) {
+        if let Some(keycode) = controls::gamepad_key(button) {
+            self.scenes.input(keycode, false)
+        }
+    }
+}
+
+/// The value given after `name` on the command line, such as the external bot to hand control to
+/// instead of the built-in AI from `--bot "<command>"`.
+fn argument(name: &str) -> Option<String> {
+    let mut args = env::args().skip_while(|arg| arg != name);
+    args.next();
+    args.next()
+}
+
+/// `--host` opens an online game on `--port`, or the default port; `--join <address>` joins one
+/// and `--spectate <address>` watches a broadcast.
+fn online_launch() -> Option<Launch> {
+    if env::args().any(|arg| arg == "--host") {
+        Some(Launch::Host)
+    } else if let Some(address) = argument("--spectate") {
+        Some(Launch::Watch(address))
+    } else {
+        argument("--join").map(Launch::Join)
+    }
+}
+
+/// `--broadcast` lets spectators watch every game played, on the given port or the default one.
+fn broadcaster() -> Option<Broadcaster> {
+    if !env::args().any(|arg| arg == "--broadcast") {
+        return None;
+    }
+    let port = argument("--broadcast").and_then(|port| port.parse().ok()).unwrap_or(SPECTATE_PORT);
+    match Broadcaster::listen(port) {
+        Ok(broadcaster) => {
+            println!("Broadcasting to spectators on port {}", broadcaster.port());
+            Some(broadcaster)
+        }
+        Err(error) => {
+            eprintln!("Could not broadcast on port {}: {}", port, error);
+            None
+        }
+    }
+}
+
+/// `--fullscreen` starts in fullscreen, and `--scaling integer` scales the game by whole factors
+/// only, for sharp pixels, instead of as large as fits.
+fn layout() -> Layout {
+    let scaling = argument("--scaling").and_then(|name| Scaling::from_name(&name)).unwrap_or(Scaling::Fit);
+    Layout::new(scaling, env::args().any(|arg| arg == "--fullscreen"))
+}
+
+/// `--lag <milliseconds>` and `--loss <percent>` make online games play as if over a worse
+/// network, for trying rollback on one machine.
+fn network_conditions() -> Option<Conditions> {
+    let lag = argument("--lag").and_then(|lag| lag.parse().ok());
+    let loss = argument("--loss").and_then(|loss| loss.parse().ok());
+    if lag.is_none() && loss.is_none() {
+        return None;
     }
+    Some(Conditions::new(Duration::from_millis(lag.unwrap_or(0)), loss.unwrap_or(0.0)))
 }
 
 fn main() -> GameResult {
@@ -84,6 +223,10 @@ fn main() -> GameResult {
     config.window_setup.title = String::from("Just another Tetris");
     config.window_mode.width = SCREEN_WIDTH;
     config.window_mode.height = SCREEN_HEIGHT;
+    config.window_mode.resizable = true;
+    config.window_mode.min_width = SCREEN_WIDTH / 2.0;
+    config.window_mode.min_height = SCREEN_HEIGHT / 2.0;
+    config.window_mode.fullscreen_type = layout().fullscreen_type();
 
     let (ref mut ctx, ref mut event_loop) = ContextBuilder::new("tetris", "Jose Matias Misiego Ruiz")
         .conf(config)
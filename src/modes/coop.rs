@@ -0,0 +1,231 @@
+use rand::{Rng, SeedableRng, StdRng};
+use crate::constants::TOP_BOUNDARY;
+use crate::world::{Board, Tetrimino, TetriminoType, ScoreBoard};
+use crate::game::{GameEvent, get_random_tetrimino_type};
+use crate::controls::Action;
+use crate::modes::GameMode;
+use crate::modes::rules::RuleSet;
+
+/// Playfield columns of the shared co-op board, twice the usual width.
+pub const COOP_COLUMNS: usize = 20;
+/// Board columns where each player's pieces enter, one over each half of the board.
+pub const SPAWN_COLUMNS: [i8; 2] = [6, 16];
+
+/// One player's falling piece and queue on the shared board.
+pub struct CoopPlayer {
+    pub tetrimino: Tetrimino,
+    pub next_tetrimino: TetriminoType,
+    pub spawn_column: i8,
+    gravity: f32,
+    lock_frames: u32,
+    rng: StdRng,
+}
+
+impl CoopPlayer {
+    fn new(spawn_column: i8, seed: u32) -> CoopPlayer {
+        let mut rng = StdRng::from_seed(&[seed as usize]);
+        let mut player = CoopPlayer {
+            tetrimino: Tetrimino::from(&TetriminoType::I),
+            next_tetrimino: get_random_tetrimino_type(&mut rng).unwrap(),
+            spawn_column,
+            gravity: 0.0,
+            lock_frames: 0,
+            rng,
+        };
+        player.spawn();
+        player
+    }
+
+    fn spawn(&mut self) {
+        let kind = std::mem::replace(&mut self.next_tetrimino, get_random_tetrimino_type(&mut self.rng).unwrap());
+        self.tetrimino = Tetrimino::from(&kind);
+        self.tetrimino.pos.x = self.spawn_column;
+        self.gravity = 0.0;
+        self.lock_frames = 0;
+    }
+}
+
+/// Two players dropping pieces onto one wide board, sharing its line clears and score.
+pub struct CoopState {
+    pub board: Board,
+    pub players: Vec<CoopPlayer>,
+    pub score: ScoreBoard,
+    pub frames: u32,
+    pub game_over: bool,
+    rules: RuleSet,
+}
+
+impl CoopState {
+    pub fn new() -> CoopState {
+        CoopState::with_seed(rand::thread_rng().gen(), RuleSet::standard())
+    }
+
+    /// A game under `rules` whose first player is dealt pieces from `seed` and the second from
+    /// `seed` plus 1, so two machines given the same inputs play the same game.
+    pub fn with_seed(seed: u32, rules: RuleSet) -> CoopState {
+        CoopState {
+            board: Board::with_columns(COOP_COLUMNS),
+            players: SPAWN_COLUMNS.iter()
+                .enumerate()
+                .map(|(index, column)| CoopPlayer::new(*column, seed.wrapping_add(index as u32)))
+                .collect(),
+            score: ScoreBoard::new(),
+            frames: 0,
+            game_over: false,
+            rules,
+        }
+    }
+
+    /// Advances both players by one frame. Either piece topping out ends the game for both.
+    pub fn step(&mut self) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        if self.game_over {
+            return events;
+        }
+        self.frames += 1;
+
+        let timings = GameMode::Coop.timings(self.score.level, &self.rules);
+        for index in 0..self.players.len() {
+            self.players[index].gravity += timings.gravity;
+            while self.players[index].gravity >= 1.0 {
+                self.players[index].gravity -= 1.0;
+                if self.shift(index, Tetrimino::move_down) {
+                    self.players[index].lock_frames = 0;
+                } else {
+                    self.players[index].gravity = 0.0;
+                }
+            }
+
+            if !self.is_grounded(index) {
+                continue;
+            }
+            let player = &mut self.players[index];
+            player.lock_frames += 1;
+            if player.lock_frames < timings.lock_delay {
+                continue;
+            }
+            if player.tetrimino.pos.y == TOP_BOUNDARY {
+                self.game_over = true;
+                events.push(GameEvent::ToppedOut);
+                break;
+            }
+
+            let cleaned_lines = self.board.update(&player.tetrimino, self.frames);
+            self.score.add_lines(cleaned_lines, false, &self.rules);
+            player.spawn();
+            events.push(GameEvent::Locked(cleaned_lines));
+            if !self.lift_overlapping_pieces() {
+                self.game_over = true;
+                events.push(GameEvent::ToppedOut);
+                break;
+            }
+        }
+        events
+    }
+
+    pub fn apply(&mut self, index: usize, action: Action) {
+        if self.game_over {
+            return;
+        }
+
+        match action {
+            Action::MoveLeft => {
+                self.shift(index, Tetrimino::move_left);
+            }
+            Action::MoveRight => {
+                self.shift(index, Tetrimino::move_right);
+            }
+            Action::SoftDrop => {
+                if self.shift(index, Tetrimino::move_down) {
+                    self.players[index].lock_frames = 0;
+                }
+            }
+            Action::RotateCounterClockwise => {
+                self.shift(index, |tetrimino, board| tetrimino.rotate(false, board, &[(0, 0)]));
+            }
+            Action::RotateClockwise => {
+                self.shift(index, |tetrimino, board| tetrimino.rotate(true, board, &[(0, 0)]));
+            }
+            Action::Hold => (),
+        }
+    }
+
+    /// Moves player `index`'s piece as `change` does against the stack, unless that would put it
+    /// on another player's piece. Returns whether it moved.
+    fn shift(&mut self, index: usize, change: fn(&mut Tetrimino, &Board) -> bool) -> bool {
+        let mut moved = self.players[index].tetrimino.clone();
+        let shifted = change(&mut moved, &self.board) && !self.overlaps_others(index, &moved);
+        if shifted {
+            self.players[index].tetrimino = moved;
+        }
+        shifted
+    }
+
+    /// Whether player `index`'s piece rests on the stack or on its partner's piece.
+    fn is_grounded(&self, index: usize) -> bool {
+        let mut below = self.players[index].tetrimino.clone();
+        !below.move_down(&self.board) || self.overlaps_others(index, &below)
+    }
+
+    /// Whether `tetrimino` covers a cell of a falling piece other than player `index`'s.
+    fn overlaps_others(&self, index: usize, tetrimino: &Tetrimino) -> bool {
+        let cells = tetrimino.cells();
+        self.players.iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .any(|(_, other)| other.tetrimino.cells().iter().any(|cell| cells.contains(cell)))
+    }
+
+    /// A new piece can enter on top of its partner's, and cleared lines drop the stack under a
+    /// falling piece, which can leave it tucked inside the blocks that came down. Such pieces are
+    /// lifted until they fit again. Returns false when one still does not fit at the top of the
+    /// playfield.
+    fn lift_overlapping_pieces(&mut self) -> bool {
+        for index in 0..self.players.len() {
+            loop {
+                let tetrimino = &self.players[index].tetrimino;
+                let fits = !tetrimino.collides(&self.board) && !self.overlaps_others(index, tetrimino);
+                if fits {
+                    break;
+                }
+                if tetrimino.pos.y <= TOP_BOUNDARY {
+                    return false;
+                }
+                self.players[index].tetrimino.pos.y -= 1;
+            }
+        }
+        true
+    }
+}
+
+impl Default for CoopState {
+    fn default() -> CoopState {
+        CoopState::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlapping(state: &CoopState) -> bool {
+        let cells = state.players[0].tetrimino.cells();
+        state.players[1].tetrimino.cells().iter().any(|cell| cells.contains(cell))
+    }
+
+    #[test]
+    fn pieces_pushed_together_never_overlap() {
+        let mut state = CoopState::with_seed(3, RuleSet::standard());
+        for frame in 0..3000 {
+            state.apply(0, if frame % 3 == 0 { Action::RotateClockwise } else { Action::MoveRight });
+            state.apply(1, if frame % 4 == 0 { Action::RotateCounterClockwise } else { Action::MoveLeft });
+            assert!(!overlapping(&state), "the pieces overlap after a move on frame {}", frame);
+            state.step();
+            assert!(!overlapping(&state), "the pieces overlap after frame {}", frame);
+            if state.game_over {
+                break;
+            }
+        }
+        assert!(state.board.codes().iter().any(|code| *code != 0));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::world::{Board, Tetrimino};
+use crate::controls::Action;
+use crate::modes::rules::RotationSystem;
+use crate::pathfinding::{placements, footprint, Placement};
+
+/// Pieces in a practice session before the report is shown.
+pub const SESSION_PIECES: u32 = 40;
+
+const MOVES: [Action; 4] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::RotateClockwise,
+    Action::RotateCounterClockwise,
+];
+
+/// Compares the presses spent on each piece against the fewest that reach the same placement.
+/// Soft drops are free, and tucks and spins are judged against the shortest way into them too.
+#[derive(Clone)]
+pub struct Finesse {
+    pub strict: bool,
+    inputs: Vec<Action>,
+    /// Presses the active piece has spent beyond the fewest that take it where it would land now.
+    pub running_faults: u32,
+    pub pieces: u32,
+    pub clean_pieces: u32,
+    pub faults: u32,
+    pub last_faults: Option<u32>,
+}
+
+impl Finesse {
+    pub fn new(strict: bool) -> Finesse {
+        Finesse {
+            strict,
+            inputs: Vec::new(),
+            running_faults: 0,
+            pieces: 0,
+            clean_pieces: 0,
+            faults: 0,
+            last_faults: None,
+        }
+    }
+
+    pub fn record(&mut self, action: Action) {
+        if MOVES.contains(&action) {
+            self.inputs.push(action);
+        }
+    }
+
+    /// The piece was swapped out by hold, so the presses spent on it no longer count.
+    pub fn on_hold(&mut self) {
+        self.inputs.clear();
+        self.running_faults = 0;
+    }
+
+    /// Updates the running faults of the active piece, which entered the board as `start` and
+    /// would land at `landing` if dropped now.
+    pub fn track(&mut self, start: &Tetrimino, landing: &Tetrimino, board: &Board, rotation: RotationSystem) {
+        self.running_faults = match minimum_presses(start, landing, board, rotation) {
+            Some(minimum) if !self.inputs.is_empty() => (self.inputs.len() as u32).saturating_sub(minimum),
+            _ => 0,
+        };
+    }
+
+    /// Judges the piece about to lock at `placed`, having entered the board as `start`, and
+    /// returns its faults.
+    pub fn on_lock(&mut self, start: &Tetrimino, placed: &Tetrimino, board: &Board, rotation: RotationSystem) -> u32 {
+        let presses = self.inputs.len() as u32;
+        self.inputs.clear();
+        self.running_faults = 0;
+
+        let minimum = match minimum_presses(start, placed, board, rotation) {
+            Some(minimum) => minimum,
+            None => {
+                self.last_faults = None;
+                return 0;
+            }
+        };
+        let faults = presses.saturating_sub(minimum);
+        self.pieces += 1;
+        self.faults += faults;
+        if faults == 0 {
+            self.clean_pieces += 1;
+        }
+        self.last_faults = Some(faults);
+        faults
+    }
+
+    pub fn percentage(&self) -> f32 {
+        if self.pieces == 0 {
+            return 100.0;
+        }
+        self.clean_pieces as f32 * 100.0 / self.pieces as f32
+    }
+
+    pub fn finished(&self) -> bool {
+        self.pieces >= SESSION_PIECES
+    }
+}
+
+/// The fewest moves and rotations that take `start` to the cells `placed` covers, tucks and spins
+/// included, or None when it cannot get there.
+pub fn minimum_presses(start: &Tetrimino, placed: &Tetrimino, board: &Board, rotation: RotationSystem) -> Option<u32> {
+    let goal = footprint(placed);
+    placements(start, board, rotation).iter()
+        .find(|placement| footprint(&placement.tetrimino) == goal)
+        .map(Placement::presses)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::controls::Action;
+    use crate::game::{GameConfig, GamePlayState};
+    use crate::modes::GameMode;
+
+    fn practice() -> GamePlayState {
+        let mut config = GameConfig::new(GameMode::Finesse { strict: false });
+        config.seed = Some(2024);
+        config.rules.hold = true;
+        GamePlayState::new(config, None).unwrap()
+    }
+
+    fn drop_piece(state: &mut GamePlayState) {
+        let pieces = state.pieces;
+        while state.pieces == pieces {
+            state.apply(Action::SoftDrop);
+            state.step();
+        }
+    }
+
+    #[test]
+    fn counts_wasted_presses_while_the_piece_falls() {
+        let mut state = practice();
+        state.apply(Action::MoveLeft);
+        assert_eq!(state.finesse.as_ref().unwrap().running_faults, 0);
+        state.apply(Action::MoveRight);
+        assert_eq!(state.finesse.as_ref().unwrap().running_faults, 2);
+        drop_piece(&mut state);
+        let finesse = state.finesse.as_ref().unwrap();
+        assert_eq!(finesse.last_faults, Some(2));
+        assert_eq!(finesse.running_faults, 0);
+    }
+
+    #[test]
+    fn forgets_the_presses_on_a_held_piece() {
+        let mut state = practice();
+        state.apply(Action::MoveLeft);
+        state.apply(Action::MoveRight);
+        state.apply(Action::Hold);
+        assert_eq!(state.finesse.as_ref().unwrap().running_faults, 0);
+        drop_piece(&mut state);
+        assert_eq!(state.finesse.as_ref().unwrap().last_faults, Some(0));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::modes::{Timings, StackVisibility};
+use crate::world::ScoreBoard;
+
+pub const MAX_LEVEL: u16 = 999;
+const CREDIT_ROLL_FRAMES: u32 = 55 * 60;
+const FADE_FRAMES: u32 = 4 * 60;
+const GM_TIME_LIMIT_FRAMES: u32 = (8 * 60 + 45) * 60;
+
+/// Gravity per level in 1/256 G, as in the arcade internal gravity table.
+const GRAVITY_TABLE: [(u16, u32); 30] = [
+    (0, 4), (30, 6), (35, 8), (40, 10), (50, 12), (60, 16), (70, 32), (80, 48), (90, 64), (100, 80),
+    (120, 96), (140, 112), (160, 128), (170, 144), (200, 4), (220, 32), (230, 64), (233, 96), (236, 128), (239, 160),
+    (243, 192), (247, 224), (251, 256), (300, 512), (330, 768), (360, 1024), (400, 1280), (420, 1024), (450, 768), (500, 5120),
+];
+
+/// Speed sections as (starting level, ARE, line clear delay, lock delay), in frames.
+const DELAY_TABLE: [(u16, u32, u32, u32); 6] = [
+    (0, 25, 40, 30),
+    (500, 25, 25, 30),
+    (600, 25, 16, 30),
+    (700, 16, 12, 30),
+    (800, 12, 6, 30),
+    (900, 12, 6, 17),
+];
+
+const GRADE_NAMES: [&str; 19] = [
+    "9", "8", "7", "6", "5", "4", "3", "2", "1",
+    "S1", "S2", "S3", "S4", "S5", "S6", "S7", "S8", "S9", "GM",
+];
+const S9_GRADE: usize = 17;
+const GM_GRADE: usize = 18;
+
+/// Grade points awarded for 1 to 4 lines, banded by the current internal grade.
+const GRADE_POINTS: [[u32; 4]; 4] = [
+    [10, 20, 40, 50],
+    [5, 15, 30, 40],
+    [3, 10, 25, 35],
+    [2, 8, 20, 30],
+];
+
+/// Frames it takes to lose one grade point while not comboing, banded like `GRADE_POINTS`.
+const GRADE_DECAY: [u32; 4] = [125, 80, 50, 30];
+
+pub fn timings(level: u16) -> Timings {
+    let gravity = GRAVITY_TABLE.iter()
+        .rev()
+        .find(|(start, _)| level >= *start)
+        .map(|(_, gravity)| *gravity)
+        .unwrap_or(4);
+    let (_, are, line_clear, lock_delay) = *DELAY_TABLE.iter()
+        .rev()
+        .find(|(start, _, _, _)| level >= *start)
+        .unwrap_or(&DELAY_TABLE[0]);
+
+    Timings {
+        gravity: gravity as f32 / 256.0,
+        are,
+        line_clear,
+        lock_delay,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CreditRoll {
+    Fading,
+    Invisible,
+}
+
+#[derive(Clone)]
+pub struct Master {
+    pub frames: u32,
+    pub roll: Option<CreditRoll>,
+    pub roll_frames: u32,
+    pub finished: bool,
+    internal_grade: usize,
+    grade_points: u32,
+    decay_frames: u32,
+    combo: u32,
+}
+
+impl Master {
+    pub fn new() -> Master {
+        Master {
+            frames: 0,
+            roll: None,
+            roll_frames: 0,
+            finished: false,
+            internal_grade: 0,
+            grade_points: 0,
+            decay_frames: 0,
+            combo: 1,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if self.roll.is_some() {
+            self.roll_frames += 1;
+            if self.roll_frames >= CREDIT_ROLL_FRAMES {
+                self.finish();
+            }
+            return;
+        }
+
+        self.frames += 1;
+        if self.combo == 1 && self.grade_points > 0 {
+            self.decay_frames += 1;
+            if self.decay_frames >= GRADE_DECAY[self.grade_band()] {
+                self.grade_points -= 1;
+                self.decay_frames = 0;
+            }
+        }
+    }
+
+    /// A new piece entering the field moves the level counter up by one, except at a section stop.
+    pub fn on_spawn(&mut self, score: &mut ScoreBoard) {
+        if self.roll.is_none() && score.level % 100 != 99 && score.level < MAX_LEVEL - 1 {
+            score.level += 1;
+        }
+    }
+
+    /// Returns true when the lock started the credit roll, so the caller can clear the field.
+    pub fn on_lock(&mut self, score: &mut ScoreBoard, cleaned_lines: u8, board_empty: bool) -> bool {
+        if cleaned_lines == 0 {
+            self.combo = 1;
+            return false;
+        }
+
+        let lines = u32::from(cleaned_lines);
+        self.combo += 2 * lines - 2;
+        let bravo = if board_empty { 4 } else { 1 };
+        let level = u32::from(score.level);
+        score.score += (level + lines).div_ceil(4) * lines * self.combo * bravo;
+        score.lines += u16::from(cleaned_lines);
+
+        if self.roll.is_some() {
+            return false;
+        }
+
+        self.award_grade_points(lines, level);
+        score.level = (score.level + u16::from(cleaned_lines)).min(MAX_LEVEL);
+
+        if score.level == MAX_LEVEL {
+            self.roll = if self.internal_grade == S9_GRADE && self.frames <= GM_TIME_LIMIT_FRAMES {
+                Some(CreditRoll::Invisible)
+            } else {
+                Some(CreditRoll::Fading)
+            };
+            return true;
+        }
+        false
+    }
+
+    pub fn finish(&mut self) {
+        if self.roll == Some(CreditRoll::Invisible) && self.roll_frames >= CREDIT_ROLL_FRAMES {
+            self.internal_grade = GM_GRADE;
+        }
+        self.finished = true;
+    }
+
+    pub fn grade_name(&self) -> &str {
+        GRADE_NAMES[self.internal_grade]
+    }
+
+    pub fn stack_visibility(&self) -> StackVisibility {
+        match self.roll {
+            _ if self.finished => StackVisibility::Visible,
+            None => StackVisibility::Visible,
+            Some(CreditRoll::Invisible) => StackVisibility::Invisible,
+            Some(CreditRoll::Fading) => StackVisibility::Fading(FADE_FRAMES),
+        }
+    }
+
+    /// The level at which the current section stops the counter.
+    pub fn section_stop(&self, score: &ScoreBoard) -> u16 {
+        if score.level >= 900 {
+            MAX_LEVEL
+        } else {
+            score.level / 100 * 100 + 100
+        }
+    }
+
+    fn award_grade_points(&mut self, lines: u32, level: u32) {
+        if self.internal_grade >= S9_GRADE {
+            return;
+        }
+        let base = GRADE_POINTS[self.grade_band()][(lines - 1) as usize];
+        let combo_multiplier = 10 + (self.combo - 1).min(10);
+        let level_multiplier = 250 + level;
+        self.grade_points += base * combo_multiplier * level_multiplier / (10 * 250);
+        if self.grade_points >= 100 {
+            self.internal_grade += 1;
+            self.grade_points = 0;
+            self.decay_frames = 0;
+        }
+    }
+
+    fn grade_band(&self) -> usize {
+        (self.internal_grade / 5).min(3)
+    }
+}
+
+impl Default for Master {
+    fn default() -> Master {
+        Master::new()
+    }
+}
@@ -0,0 +1,102 @@
+use crate::world::Cell;
+use crate::modes::rules::RuleSet;
+
+pub mod coop;
+pub mod finesse;
+pub mod master;
+pub mod puzzle;
+pub mod rules;
+pub mod survival;
+pub mod versus;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameMode {
+    Marathon,
+    Sprint,
+    Ultra,
+    Master,
+    Invisible,
+    Fading(u32),
+    Puzzle,
+    Versus,
+    Coop,
+    Survival,
+    Finesse { strict: bool },
+}
+
+/// How locked minos are shown. `Fading` holds the number of frames a mino stays visible after locking.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackVisibility {
+    Visible,
+    Fading(u32),
+    Invisible,
+}
+
+const FADE_OUT_FRAMES: u32 = 30;
+const LOCK_FLASH_FRAMES: u32 = 6;
+
+impl StackVisibility {
+    pub fn alpha(&self, cell: &Cell, frame: u32) -> f32 {
+        let elapsed = frame.saturating_sub(cell.locked_at);
+        match self {
+            StackVisibility::Visible => 1.0,
+            StackVisibility::Invisible => 0.0,
+            StackVisibility::Fading(visible_frames) => {
+                let remaining = visible_frames.saturating_sub(elapsed).min(FADE_OUT_FRAMES);
+                remaining as f32 / FADE_OUT_FRAMES as f32
+            }
+        }
+    }
+
+    /// Whether the outline of a mino locked at `cell.locked_at` is still flashing.
+    pub fn flashing(&self, cell: &Cell, frame: u32) -> bool {
+        *self != StackVisibility::Visible
+            && *cell != Cell::LIMIT
+            && frame.saturating_sub(cell.locked_at) < LOCK_FLASH_FRAMES
+    }
+}
+
+/// Per-frame timing rules for the active piece. `gravity` is measured in cells per frame,
+/// so 20.0 is 20G; the delays are measured in frames.
+#[derive(Debug, Clone, Copy)]
+pub struct Timings {
+    pub gravity: f32,
+    pub are: u32,
+    pub line_clear: u32,
+    pub lock_delay: u32,
+}
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Marathon => "MARATHON",
+            GameMode::Sprint => "SPRINT",
+            GameMode::Ultra => "ULTRA",
+            GameMode::Master => "MASTER",
+            GameMode::Invisible => "INVISIBLE",
+            GameMode::Fading(_) => "FADING",
+            GameMode::Puzzle => "PUZZLE",
+            GameMode::Versus => "VERSUS",
+            GameMode::Coop => "CO-OP",
+            GameMode::Survival => "SURVIVAL",
+            GameMode::Finesse { .. } => "FINESSE",
+        }
+    }
+
+    /// Master keeps its own arcade timings; every other mode follows the gravity curve and lock
+    /// delay of the rule set.
+    pub fn timings(&self, level: u16, rules: &RuleSet) -> Timings {
+        match self {
+            GameMode::Master => master::timings(level),
+            _ => rules.timings(level),
+        }
+    }
+
+    pub fn stack_visibility(&self) -> StackVisibility {
+        match self {
+            GameMode::Invisible => StackVisibility::Invisible,
+            GameMode::Fading(visible_frames) => StackVisibility::Fading(*visible_frames),
+            _ => StackVisibility::Visible,
+        }
+    }
+}
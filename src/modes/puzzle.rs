@@ -0,0 +1,313 @@
+use std::collections::{HashSet, VecDeque};
+use std::io::{Read, Write};
+use ggez::{Context, GameResult, GameError, filesystem};
+use crate::world::{TetriminoType, BoardType};
+
+pub const PUZZLES_DIR: &str = "/puzzles";
+const PROGRESS_FILE: &str = "/puzzle_progress.txt";
+
+/// Puzzle packs are plain text files. A pack starts with a `pack:` line and holds any number of
+/// puzzles, each one opened by a `puzzle:` line:
+///
+/// ```text
+/// pack: Basics
+/// puzzle: First Tetris
+/// difficulty: 1
+/// pieces: I T
+/// hold: empty
+/// goal: lines 4
+/// goal: pieces 1
+/// board:
+/// #########.
+/// #########.
+/// end
+/// ```
+///
+/// `hold:` takes a piece letter or `empty`; leaving it out disables hold. Board rows are ten
+/// characters wide and aligned to the bottom of the playfield: `.` is empty, `#` is garbage and a
+/// piece letter is a block of that piece. Goals are `lines N`, `tspin-double`, `perfect-clear` and
+/// `pieces N`, the last one being a limit rather than something to achieve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Goal {
+    ClearLines(u16),
+    TSpinDouble,
+    PerfectClear,
+    WithinPieces(u16),
+}
+
+impl Goal {
+    pub fn describe(&self) -> String {
+        match self {
+            Goal::ClearLines(lines) => format!("CLEAR {} LINES", lines),
+            Goal::TSpinDouble => String::from("T-SPIN DOUBLE"),
+            Goal::PerfectClear => String::from("PERFECT CLEAR"),
+            Goal::WithinPieces(pieces) => format!("WITHIN {} PIECES", pieces),
+        }
+    }
+
+    fn parse(value: &str) -> GameResult<Goal> {
+        let mut words = value.split_whitespace();
+        let goal = match (words.next(), words.next()) {
+            (Some("lines"), Some(lines)) => Goal::ClearLines(parse_number(lines)?),
+            (Some("tspin-double"), None) => Goal::TSpinDouble,
+            (Some("perfect-clear"), None) => Goal::PerfectClear,
+            (Some("pieces"), Some(pieces)) => Goal::WithinPieces(parse_number(pieces)?),
+            _ => return Err(parse_error(format!("Unknown goal '{}'", value))),
+        };
+        Ok(goal)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Puzzle {
+    pub title: String,
+    pub difficulty: u8,
+    pub rows: Vec<[u8; 10]>,
+    pub pieces: Vec<TetriminoType>,
+    pub hold_enabled: bool,
+    pub hold: Option<TetriminoType>,
+    pub goals: Vec<Goal>,
+}
+
+impl Puzzle {
+    fn new(title: &str) -> Puzzle {
+        Puzzle {
+            title: String::from(title),
+            difficulty: 1,
+            rows: Vec::new(),
+            pieces: Vec::new(),
+            hold_enabled: false,
+            hold: None,
+            goals: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PuzzlePack {
+    pub title: String,
+    pub puzzles: Vec<Puzzle>,
+}
+
+impl PuzzlePack {
+    pub fn load(ctx: &mut Context, path: &str) -> GameResult<PuzzlePack> {
+        let mut source = String::new();
+        filesystem::open(ctx, path)?.read_to_string(&mut source)?;
+        PuzzlePack::parse(&source)
+    }
+
+    /// Loads every `.pack` file in the puzzles resource directory, skipping the ones that fail to parse.
+    pub fn load_all(ctx: &mut Context) -> Vec<PuzzlePack> {
+        let mut paths: Vec<_> = match filesystem::read_dir(ctx, PUZZLES_DIR) {
+            Ok(paths) => paths
+                .filter(|path| path.extension().is_some_and(|extension| extension == "pack"))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        paths.sort();
+        paths.iter()
+            .filter_map(|path| path.to_str().map(String::from))
+            .filter_map(|path| PuzzlePack::load(ctx, &path).ok())
+            .collect()
+    }
+
+    pub fn parse(source: &str) -> GameResult<PuzzlePack> {
+        let mut pack = PuzzlePack {
+            title: String::new(),
+            puzzles: Vec::new(),
+        };
+        let mut reading_board = false;
+
+        for line in source.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with("//")) {
+            if reading_board {
+                let puzzle = pack.puzzles.last_mut().unwrap();
+                if line == "end" {
+                    reading_board = false;
+                } else {
+                    puzzle.rows.push(parse_row(line)?);
+                }
+                continue;
+            }
+
+            let (key, value) = match line.find(':') {
+                Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+                None => return Err(parse_error(format!("Expected 'key: value', got '{}'", line))),
+            };
+
+            if key == "pack" {
+                pack.title = String::from(value);
+                continue;
+            }
+            if key == "puzzle" {
+                pack.puzzles.push(Puzzle::new(value));
+                continue;
+            }
+
+            let puzzle = match pack.puzzles.last_mut() {
+                Some(puzzle) => puzzle,
+                None => return Err(parse_error(format!("'{}' appears before any puzzle", key))),
+            };
+            match key {
+                "difficulty" => puzzle.difficulty = parse_number(value)? as u8,
+                "pieces" => puzzle.pieces = value.split_whitespace()
+                    .map(parse_piece)
+                    .collect::<GameResult<Vec<_>>>()?,
+                "hold" => {
+                    puzzle.hold_enabled = true;
+                    puzzle.hold = if value == "empty" { None } else { Some(parse_piece(value)?) };
+                }
+                "goal" => puzzle.goals.push(Goal::parse(value)?),
+                "board" => reading_board = true,
+                _ => return Err(parse_error(format!("Unknown key '{}'", key))),
+            }
+        }
+
+        if reading_board {
+            return Err(parse_error(String::from("Board is missing its 'end' line")));
+        }
+        if let Some(puzzle) = pack.puzzles.iter().find(|puzzle| puzzle.pieces.is_empty()) {
+            return Err(parse_error(format!("Puzzle '{}' has no pieces", puzzle.title)));
+        }
+        Ok(pack)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PuzzleOutcome {
+    Solved,
+    Failed,
+}
+
+/// Progress through a single attempt at a puzzle.
+#[derive(Clone)]
+pub struct PuzzleRun {
+    queue: VecDeque<TetriminoType>,
+    goals: Vec<Goal>,
+    pub pieces_locked: u16,
+    pub lines: u16,
+    pub t_spin_doubles: u16,
+    pub perfect_clears: u16,
+    pub outcome: Option<PuzzleOutcome>,
+}
+
+impl PuzzleRun {
+    pub fn new(puzzle: &Puzzle) -> PuzzleRun {
+        PuzzleRun {
+            queue: puzzle.pieces.iter().cloned().collect(),
+            goals: puzzle.goals.clone(),
+            pieces_locked: 0,
+            lines: 0,
+            t_spin_doubles: 0,
+            perfect_clears: 0,
+            outcome: None,
+        }
+    }
+
+    pub fn next_piece(&mut self) -> Option<TetriminoType> {
+        self.queue.pop_front()
+    }
+
+    /// Records a lock and decides the outcome once every goal is met or no pieces are left to play.
+    pub fn on_lock(&mut self, cleaned_lines: u8, t_spin: bool, perfect_clear: bool, pieces_left: bool) {
+        self.pieces_locked += 1;
+        self.lines += u16::from(cleaned_lines);
+        if t_spin && cleaned_lines == 2 {
+            self.t_spin_doubles += 1;
+        }
+        if perfect_clear && cleaned_lines > 0 {
+            self.perfect_clears += 1;
+        }
+
+        let out_of_pieces = !pieces_left || self.goals.iter().any(|goal| match goal {
+            Goal::WithinPieces(pieces) => self.pieces_locked >= *pieces,
+            _ => false,
+        });
+        if self.goals.iter().all(|goal| self.goal_met(goal)) {
+            self.outcome = Some(PuzzleOutcome::Solved);
+        } else if out_of_pieces {
+            self.outcome = Some(PuzzleOutcome::Failed);
+        }
+    }
+
+    pub fn goal_met(&self, goal: &Goal) -> bool {
+        match goal {
+            Goal::ClearLines(lines) => self.lines >= *lines,
+            Goal::TSpinDouble => self.t_spin_doubles > 0,
+            Goal::PerfectClear => self.perfect_clears > 0,
+            Goal::WithinPieces(pieces) => self.pieces_locked <= *pieces,
+        }
+    }
+
+    pub fn goals(&self) -> &[Goal] {
+        &self.goals
+    }
+}
+
+/// Titles of the puzzles completed on this machine, kept one per line in the user config directory.
+pub struct PuzzleProgress {
+    completed: HashSet<String>,
+}
+
+impl PuzzleProgress {
+    pub fn load(ctx: &mut Context) -> PuzzleProgress {
+        let mut source = String::new();
+        if let Ok(mut file) = filesystem::open(ctx, PROGRESS_FILE) {
+            if file.read_to_string(&mut source).is_err() {
+                source.clear();
+            }
+        }
+        PuzzleProgress {
+            completed: source.lines().map(String::from).collect(),
+        }
+    }
+
+    pub fn is_completed(&self, pack: &PuzzlePack, puzzle: &Puzzle) -> bool {
+        self.completed.contains(&PuzzleProgress::key(pack, puzzle))
+    }
+
+    pub fn complete(&mut self, ctx: &mut Context, pack: &PuzzlePack, puzzle: &Puzzle) -> GameResult {
+        if !self.completed.insert(PuzzleProgress::key(pack, puzzle)) {
+            return Ok(());
+        }
+        let mut keys: Vec<_> = self.completed.iter().cloned().collect();
+        keys.sort();
+        let mut file = filesystem::create(ctx, PROGRESS_FILE)?;
+        file.write_all(keys.join("\n").as_bytes())?;
+        Ok(())
+    }
+
+    fn key(pack: &PuzzlePack, puzzle: &Puzzle) -> String {
+        format!("{}/{}", pack.title, puzzle.title)
+    }
+}
+
+fn parse_row(line: &str) -> GameResult<[u8; 10]> {
+    let mut row = [0; 10];
+    if line.chars().count() != 10 {
+        return Err(parse_error(format!("Board row '{}' is not 10 cells wide", line)));
+    }
+    for (index, letter) in line.chars().enumerate() {
+        row[index] = match letter {
+            '.' => 0,
+            '#' => BoardType::GARBAGE_CODE,
+            _ => parse_piece(&letter.to_string())?.to_code(),
+        };
+    }
+    Ok(row)
+}
+
+fn parse_piece(value: &str) -> GameResult<TetriminoType> {
+    let mut letters = value.chars();
+    match (letters.next().and_then(TetriminoType::from_char), letters.next()) {
+        (Some(kind), None) => Ok(kind),
+        _ => Err(parse_error(format!("Unknown piece '{}'", value))),
+    }
+}
+
+fn parse_number(value: &str) -> GameResult<u16> {
+    value.parse().map_err(|_| parse_error(format!("Expected a number, got '{}'", value)))
+}
+
+fn parse_error(message: String) -> GameError {
+    GameError::ResourceLoadError(message)
+}
@@ -0,0 +1,572 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use ggez::{Context, GameResult, GameError, filesystem};
+use rand::Rng;
+use crate::constants::{FALL_TIME, POINTS_FOR_ONE_LINE, POINTS_FOR_TWO_LINES, POINTS_FOR_THREE_LINES, POINTS_FOR_MORE_THAN_THREE_LINES};
+use crate::modes::Timings;
+use crate::world::TetriminoType;
+
+pub const RULES_DIR: &str = "/rules";
+
+/// Gravity for levels 0 to 29 on the NES, in frames per cell.
+const NES_FRAMES_PER_CELL: [u32; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1,
+];
+const GUIDELINE_POINTS: [u32; 5] = [0, 100, 300, 500, 800];
+const GUIDELINE_T_SPIN_POINTS: [u32; 5] = [400, 800, 1200, 1600, 1600];
+/// 20G: the piece lands the frame it spawns.
+const MAX_GRAVITY: f32 = 20.0;
+const HISTORY_ROLLS: usize = 6;
+pub const MAX_PREVIEWS: u8 = 5;
+
+/// Which offsets a rotation tries when the rotated piece does not fit where it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RotationSystem {
+    /// No kicks: a blocked rotation fails.
+    Classic,
+    /// One cell right, then one cell left, as in the arcade games. The O piece does not rotate.
+    Arcade,
+    /// Wall kicks up to two cells and floor kicks one cell up, tried in the Guideline order.
+    /// The O piece does not rotate.
+    Guideline,
+}
+
+impl RotationSystem {
+    pub fn kicks(&self) -> &'static [(i8, i8)] {
+        match self {
+            RotationSystem::Classic => &[(0, 0)],
+            RotationSystem::Arcade => &[(0, 0), (1, 0), (-1, 0)],
+            RotationSystem::Guideline => &[(0, 0), (-1, 0), (1, 0), (0, -1), (-1, -1), (1, -1), (-2, 0), (2, 0)],
+        }
+    }
+
+    pub fn rotates(&self, kind: TetriminoType) -> bool {
+        *self == RotationSystem::Classic || kind != TetriminoType::O
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Randomizer {
+    /// Every piece is equally likely every time.
+    Random,
+    /// All seven pieces are dealt in a shuffled bag before any repeats.
+    Bag,
+    /// Rerolls a piece that is among the last four dealt, a few times at most.
+    History,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GravityCurve {
+    /// The original curve: one cell every `FALL_TIME` seconds, divided by the level plus one.
+    Standard,
+    Nes,
+    Guideline,
+}
+
+/// When enough lines have been cleared for the next level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LevelUp {
+    /// Once more than `lines-per-level` lines a level are cleared, as the game always has: with
+    /// 5 lines per level, level 1 starts at the sixth line.
+    After,
+    /// As soon as `lines-per-level` lines a level are cleared.
+    At,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockDelay {
+    /// Locks one gravity interval after landing; only falling further resets it.
+    Gravity,
+    /// Waits a fixed number of frames; only falling further resets it.
+    Step(u32),
+    /// Waits a fixed number of frames, reset by any move or rotation up to `limit` times a piece.
+    Move { frames: u32, limit: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scoring {
+    /// Fixed points per clear, whatever the level.
+    Standard,
+    /// The standard points multiplied by the level plus one.
+    Nes,
+    /// 100, 300, 500 and 800 per clear and more for T-spins, multiplied by the level plus one.
+    Guideline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpawnOrientation {
+    /// Flat side up, as the pieces were first drawn: the T points down.
+    PointDown,
+    /// Flat side down: the T points up.
+    PointUp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TopOut {
+    /// The game ends when a piece locks in the row it spawned in.
+    SpawnRow,
+    /// The game ends when a new piece spawns overlapping the stack.
+    BlockOut,
+}
+
+/// Every rule that differs between versions of the game. Rule sets are plain text files with one
+/// `key: value` per line:
+///
+/// ```text
+/// name: Guideline
+/// rotation: guideline
+/// randomizer: bag
+/// gravity: guideline
+/// lines-per-level: 10
+/// level-up: at
+/// max-level: 20
+/// lock-delay: move 30 15
+/// are: 6
+/// line-clear: 20
+/// hold: on
+/// previews: 5
+/// scoring: guideline
+/// spawn: point-up
+/// top-out: block-out
+/// ```
+///
+/// `rotation` is `classic`, `arcade` or `guideline`; `randomizer` is `random`, `bag` or `history`;
+/// `gravity` and `scoring` are `standard`, `nes` or `guideline`; `level-up` is `after` or `at`;
+/// `lock-delay` is `gravity`, `step FRAMES` or `move FRAMES LIMIT`; `are`, the entry delay before
+/// the next piece appears, and `line-clear`, the delay while cleared lines are taken away, are in
+/// frames; `spawn` is `point-down` or `point-up` and `top-out` is `spawn-row` or `block-out`. Keys
+/// left out keep the standard rules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleSet {
+    pub name: String,
+    pub rotation: RotationSystem,
+    pub randomizer: Randomizer,
+    pub gravity: GravityCurve,
+    pub lines_per_level: u16,
+    pub level_up: LevelUp,
+    pub max_level: u16,
+    pub lock_delay: LockDelay,
+    /// Frames between a piece locking and the next one appearing.
+    pub are: u32,
+    /// Frames cleared lines stay on the board before the stack drops.
+    pub line_clear: u32,
+    pub hold: bool,
+    pub previews: u8,
+    pub scoring: Scoring,
+    pub spawn: SpawnOrientation,
+    pub top_out: TopOut,
+}
+
+impl RuleSet {
+    /// The rules this game has always played by.
+    pub fn standard() -> RuleSet {
+        RuleSet {
+            name: String::from("Standard"),
+            rotation: RotationSystem::Classic,
+            randomizer: Randomizer::Random,
+            gravity: GravityCurve::Standard,
+            lines_per_level: 5,
+            level_up: LevelUp::After,
+            max_level: 9,
+            lock_delay: LockDelay::Gravity,
+            are: 0,
+            line_clear: 0,
+            hold: false,
+            previews: 1,
+            scoring: Scoring::Standard,
+            spawn: SpawnOrientation::PointDown,
+            top_out: TopOut::SpawnRow,
+        }
+    }
+
+    pub fn guideline() -> RuleSet {
+        RuleSet {
+            name: String::from("Guideline"),
+            rotation: RotationSystem::Guideline,
+            randomizer: Randomizer::Bag,
+            gravity: GravityCurve::Guideline,
+            lines_per_level: 10,
+            level_up: LevelUp::At,
+            max_level: 20,
+            lock_delay: LockDelay::Move { frames: 30, limit: 15 },
+            are: 6,
+            line_clear: 20,
+            hold: true,
+            previews: 5,
+            scoring: Scoring::Guideline,
+            spawn: SpawnOrientation::PointUp,
+            top_out: TopOut::BlockOut,
+        }
+    }
+
+    pub fn nes() -> RuleSet {
+        RuleSet {
+            name: String::from("Classic NES"),
+            rotation: RotationSystem::Classic,
+            randomizer: Randomizer::Random,
+            gravity: GravityCurve::Nes,
+            lines_per_level: 10,
+            level_up: LevelUp::At,
+            max_level: 29,
+            lock_delay: LockDelay::Gravity,
+            are: 10,
+            line_clear: 18,
+            hold: false,
+            previews: 1,
+            scoring: Scoring::Nes,
+            spawn: SpawnOrientation::PointDown,
+            top_out: TopOut::BlockOut,
+        }
+    }
+
+    pub fn arcade() -> RuleSet {
+        RuleSet {
+            name: String::from("Arcade"),
+            rotation: RotationSystem::Arcade,
+            randomizer: Randomizer::History,
+            gravity: GravityCurve::Standard,
+            lines_per_level: 10,
+            level_up: LevelUp::At,
+            max_level: 9,
+            lock_delay: LockDelay::Step(30),
+            are: 30,
+            line_clear: 41,
+            hold: false,
+            previews: 1,
+            scoring: Scoring::Standard,
+            spawn: SpawnOrientation::PointDown,
+            top_out: TopOut::BlockOut,
+        }
+    }
+
+    pub fn presets() -> Vec<RuleSet> {
+        vec![RuleSet::standard(), RuleSet::guideline(), RuleSet::nes(), RuleSet::arcade()]
+    }
+
+    /// The built-in presets followed by every `.rules` file in the rules directory, skipping the
+    /// ones that fail to parse.
+    pub fn load_all(ctx: &mut Context) -> Vec<RuleSet> {
+        let mut paths: Vec<_> = match filesystem::read_dir(ctx, RULES_DIR) {
+            Ok(paths) => paths
+                .filter(|path| path.extension().is_some_and(|extension| extension == "rules"))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        paths.sort();
+        let mut rule_sets = RuleSet::presets();
+        for path in paths {
+            let mut source = String::new();
+            let read = filesystem::open(ctx, &path)
+                .and_then(|mut file| Ok(file.read_to_string(&mut source)?));
+            if let Ok(rule_set) = read.and_then(|_| RuleSet::parse(&source)) {
+                rule_sets.push(rule_set);
+            }
+        }
+        rule_sets
+    }
+
+    pub fn save(&self, ctx: &mut Context) -> GameResult {
+        let file_name: String = self.name.to_lowercase()
+            .chars()
+            .map(|letter| if letter.is_ascii_alphanumeric() { letter } else { '-' })
+            .collect();
+        filesystem::create_dir(ctx, RULES_DIR)?;
+        let mut file = filesystem::create(ctx, format!("{}/{}.rules", RULES_DIR, file_name))?;
+        file.write_all(self.to_source().as_bytes())?;
+        Ok(())
+    }
+
+    pub fn parse(source: &str) -> GameResult<RuleSet> {
+        let mut rules = RuleSet::standard();
+        for line in source.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with("//")) {
+            let (key, value) = match line.find(':') {
+                Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+                None => return Err(parse_error(format!("Expected 'key: value', got '{}'", line))),
+            };
+            let words: Vec<&str> = value.split_whitespace().collect();
+            match (key, words.as_slice()) {
+                ("name", _) => rules.name = String::from(value),
+                ("rotation", ["classic"]) => rules.rotation = RotationSystem::Classic,
+                ("rotation", ["arcade"]) => rules.rotation = RotationSystem::Arcade,
+                ("rotation", ["guideline"]) => rules.rotation = RotationSystem::Guideline,
+                ("randomizer", ["random"]) => rules.randomizer = Randomizer::Random,
+                ("randomizer", ["bag"]) => rules.randomizer = Randomizer::Bag,
+                ("randomizer", ["history"]) => rules.randomizer = Randomizer::History,
+                ("gravity", ["standard"]) => rules.gravity = GravityCurve::Standard,
+                ("gravity", ["nes"]) => rules.gravity = GravityCurve::Nes,
+                ("gravity", ["guideline"]) => rules.gravity = GravityCurve::Guideline,
+                ("lines-per-level", [lines]) => rules.lines_per_level = parse_number(lines)?.max(1),
+                ("level-up", ["after"]) => rules.level_up = LevelUp::After,
+                ("level-up", ["at"]) => rules.level_up = LevelUp::At,
+                ("max-level", [level]) => rules.max_level = parse_number(level)?,
+                ("lock-delay", ["gravity"]) => rules.lock_delay = LockDelay::Gravity,
+                ("lock-delay", ["step", frames]) => rules.lock_delay = LockDelay::Step(u32::from(parse_number(frames)?)),
+                ("lock-delay", ["move", frames, limit]) => rules.lock_delay = LockDelay::Move {
+                    frames: u32::from(parse_number(frames)?),
+                    limit: parse_number(limit)?.min(u16::from(u8::MAX)) as u8,
+                },
+                ("are", [frames]) => rules.are = u32::from(parse_number(frames)?),
+                ("line-clear", [frames]) => rules.line_clear = u32::from(parse_number(frames)?),
+                ("hold", ["on"]) => rules.hold = true,
+                ("hold", ["off"]) => rules.hold = false,
+                ("previews", [previews]) => rules.previews = parse_number(previews)?.min(u16::from(MAX_PREVIEWS)) as u8,
+                ("scoring", ["standard"]) => rules.scoring = Scoring::Standard,
+                ("scoring", ["nes"]) => rules.scoring = Scoring::Nes,
+                ("scoring", ["guideline"]) => rules.scoring = Scoring::Guideline,
+                ("spawn", ["point-down"]) => rules.spawn = SpawnOrientation::PointDown,
+                ("spawn", ["point-up"]) => rules.spawn = SpawnOrientation::PointUp,
+                ("top-out", ["spawn-row"]) => rules.top_out = TopOut::SpawnRow,
+                ("top-out", ["block-out"]) => rules.top_out = TopOut::BlockOut,
+                _ => return Err(parse_error(format!("Unknown rule '{}'", line))),
+            }
+        }
+        Ok(rules)
+    }
+
+    pub fn to_source(&self) -> String {
+        let lock_delay = match self.lock_delay {
+            LockDelay::Gravity => String::from("gravity"),
+            LockDelay::Step(frames) => format!("step {}", frames),
+            LockDelay::Move { frames, limit } => format!("move {} {}", frames, limit),
+        };
+        let lines = [
+            format!("name: {}", self.name),
+            format!("rotation: {}", self.rotation.describe()),
+            format!("randomizer: {}", self.randomizer.describe()),
+            format!("gravity: {}", self.gravity.describe()),
+            format!("lines-per-level: {}", self.lines_per_level),
+            format!("level-up: {}", self.level_up.describe()),
+            format!("max-level: {}", self.max_level),
+            format!("lock-delay: {}", lock_delay),
+            format!("are: {}", self.are),
+            format!("line-clear: {}", self.line_clear),
+            format!("hold: {}", if self.hold { "on" } else { "off" }),
+            format!("previews: {}", self.previews),
+            format!("scoring: {}", self.scoring.describe()),
+            format!("spawn: {}", self.spawn.describe()),
+            format!("top-out: {}", self.top_out.describe()),
+        ];
+        lines.join("\n") + "\n"
+    }
+
+    pub fn timings(&self, level: u16) -> Timings {
+        let frames_per_cell = match self.gravity {
+            GravityCurve::Standard => FALL_TIME * 60.0 / f32::from(level + 1),
+            GravityCurve::Nes => NES_FRAMES_PER_CELL[usize::from(level).min(NES_FRAMES_PER_CELL.len() - 1)] as f32,
+            GravityCurve::Guideline => {
+                let level = f32::from(level.min(19));
+                (0.8 - level * 0.007).powf(level) * 60.0
+            }
+        };
+        let lock_delay = match self.lock_delay {
+            LockDelay::Gravity => (frames_per_cell as u32).max(1),
+            LockDelay::Step(frames) | LockDelay::Move { frames, .. } => frames,
+        };
+        Timings {
+            gravity: (1.0 / frames_per_cell).min(MAX_GRAVITY),
+            are: self.are,
+            line_clear: self.line_clear,
+            lock_delay,
+        }
+    }
+
+    pub fn level(&self, start_level: u16, lines: u16) -> u16 {
+        let levels = match self.level_up {
+            LevelUp::After => lines.saturating_sub(1) / self.lines_per_level,
+            LevelUp::At => lines / self.lines_per_level,
+        };
+        levels.min(self.max_level).max(start_level)
+    }
+
+    pub fn points(&self, cleaned_lines: u8, t_spin: bool, level: u16) -> u32 {
+        let standard = match cleaned_lines {
+            0 => 0,
+            1 => POINTS_FOR_ONE_LINE,
+            2 => POINTS_FOR_TWO_LINES,
+            3 => POINTS_FOR_THREE_LINES,
+            _ => POINTS_FOR_MORE_THAN_THREE_LINES,
+        };
+        let multiplier = u32::from(level) + 1;
+        let lines = usize::from(cleaned_lines.min(4));
+        match self.scoring {
+            Scoring::Standard => standard,
+            Scoring::Nes => standard * multiplier,
+            Scoring::Guideline if t_spin => GUIDELINE_T_SPIN_POINTS[lines] * multiplier,
+            Scoring::Guideline => GUIDELINE_POINTS[lines] * multiplier,
+        }
+    }
+}
+
+impl RotationSystem {
+    pub const ALL: [RotationSystem; 3] = [RotationSystem::Classic, RotationSystem::Arcade, RotationSystem::Guideline];
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            RotationSystem::Classic => "classic",
+            RotationSystem::Arcade => "arcade",
+            RotationSystem::Guideline => "guideline",
+        }
+    }
+}
+
+impl Randomizer {
+    pub const ALL: [Randomizer; 3] = [Randomizer::Random, Randomizer::Bag, Randomizer::History];
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Randomizer::Random => "random",
+            Randomizer::Bag => "bag",
+            Randomizer::History => "history",
+        }
+    }
+}
+
+impl GravityCurve {
+    pub const ALL: [GravityCurve; 3] = [GravityCurve::Standard, GravityCurve::Nes, GravityCurve::Guideline];
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            GravityCurve::Standard => "standard",
+            GravityCurve::Nes => "nes",
+            GravityCurve::Guideline => "guideline",
+        }
+    }
+}
+
+impl Scoring {
+    pub const ALL: [Scoring; 3] = [Scoring::Standard, Scoring::Nes, Scoring::Guideline];
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Scoring::Standard => "standard",
+            Scoring::Nes => "nes",
+            Scoring::Guideline => "guideline",
+        }
+    }
+}
+
+impl SpawnOrientation {
+    pub const ALL: [SpawnOrientation; 2] = [SpawnOrientation::PointDown, SpawnOrientation::PointUp];
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            SpawnOrientation::PointDown => "point-down",
+            SpawnOrientation::PointUp => "point-up",
+        }
+    }
+}
+
+impl LevelUp {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            LevelUp::After => "after",
+            LevelUp::At => "at",
+        }
+    }
+}
+
+impl TopOut {
+    pub const ALL: [TopOut; 2] = [TopOut::SpawnRow, TopOut::BlockOut];
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            TopOut::SpawnRow => "spawn-row",
+            TopOut::BlockOut => "block-out",
+        }
+    }
+}
+
+/// Deals pieces according to a `Randomizer`.
+#[derive(Debug, Clone)]
+pub struct PieceGenerator {
+    randomizer: Randomizer,
+    bag: Vec<TetriminoType>,
+    history: VecDeque<TetriminoType>,
+}
+
+impl PieceGenerator {
+    pub fn new(randomizer: Randomizer) -> PieceGenerator {
+        PieceGenerator {
+            randomizer,
+            bag: Vec::new(),
+            history: vec![TetriminoType::Z, TetriminoType::S, TetriminoType::Z, TetriminoType::S].into_iter().collect(),
+        }
+    }
+
+    pub fn next<R: Rng>(&mut self, rng: &mut R) -> TetriminoType {
+        match self.randomizer {
+            Randomizer::Random => random_piece(rng),
+            Randomizer::Bag => {
+                if self.bag.is_empty() {
+                    self.bag = (1..=7).filter_map(TetriminoType::from_code).collect();
+                    rng.shuffle(&mut self.bag);
+                }
+                self.bag.pop().unwrap()
+            }
+            Randomizer::History => {
+                let mut piece = random_piece(rng);
+                for _ in 1..HISTORY_ROLLS {
+                    if !self.history.contains(&piece) {
+                        break;
+                    }
+                    piece = random_piece(rng);
+                }
+                self.history.pop_front();
+                self.history.push_back(piece);
+                piece
+            }
+        }
+    }
+}
+
+fn random_piece<R: Rng>(rng: &mut R) -> TetriminoType {
+    TetriminoType::from_code(rng.gen_range(1, 8)).unwrap()
+}
+
+fn parse_number(value: &str) -> GameResult<u16> {
+    value.parse().map_err(|_| parse_error(format!("Expected a number, got '{}'", value)))
+}
+
+fn parse_error(message: String) -> GameError {
+    GameError::ResourceLoadError(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_levels_match_the_original_table() {
+        let rules = RuleSet::standard();
+        let original = |lines: u16| match lines {
+            0..=5 => 0,
+            6..=10 => 1,
+            11..=15 => 2,
+            16..=20 => 3,
+            21..=25 => 4,
+            26..=30 => 5,
+            31..=35 => 6,
+            36..=40 => 7,
+            41..=45 => 8,
+            _ => 9,
+        };
+        for lines in 0..=100 {
+            assert_eq!(rules.level(0, lines), original(lines), "after {} lines", lines);
+        }
+    }
+
+    #[test]
+    fn other_presets_level_up_on_the_line() {
+        let rules = RuleSet::guideline();
+        assert_eq!(rules.level(0, 9), 0);
+        assert_eq!(rules.level(0, 10), 1);
+        assert_eq!(rules.level(3, 10), 3);
+    }
+
+    #[test]
+    fn rules_round_trip_through_their_source() {
+        for rules in RuleSet::presets() {
+            assert_eq!(RuleSet::parse(&rules.to_source()).unwrap(), rules);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::world::ScoreBoard;
+
+/// Frames between garbage rows at the start and at the fastest.
+const START_INTERVAL_FRAMES: u32 = 10 * 60;
+const MIN_INTERVAL_FRAMES: u32 = 2 * 60;
+/// The interval drops by one second for every twenty seconds survived.
+const SPEED_UP_FRAMES: u32 = 20 * 60;
+
+/// Garbage rises from the bottom on a timer; the score is the seconds survived plus the lines cleared.
+#[derive(Clone)]
+pub struct Survival {
+    pub frames: u32,
+    pub next_rise: u32,
+}
+
+impl Survival {
+    pub fn new() -> Survival {
+        Survival {
+            frames: 0,
+            next_rise: START_INTERVAL_FRAMES,
+        }
+    }
+
+    /// Advances the timer by one frame and returns true when a garbage row should rise.
+    pub fn tick(&mut self, score_board: &mut ScoreBoard) -> bool {
+        self.frames += 1;
+        self.update_score(score_board);
+
+        self.next_rise = self.next_rise.saturating_sub(1);
+        if self.next_rise > 0 {
+            return false;
+        }
+        self.next_rise = self.interval();
+        true
+    }
+
+    pub fn update_score(&self, score_board: &mut ScoreBoard) {
+        score_board.score = self.frames / 60 + u32::from(score_board.lines);
+    }
+
+    pub fn interval(&self) -> u32 {
+        let speed_up = self.frames / SPEED_UP_FRAMES * 60;
+        START_INTERVAL_FRAMES.saturating_sub(speed_up).max(MIN_INTERVAL_FRAMES)
+    }
+}
+
+impl Default for Survival {
+    fn default() -> Survival {
+        Survival::new()
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use ggez::{Context, GameResult, GameError, filesystem};
+use rand::{Rng, SeedableRng, StdRng};
+
+const ATTACK_TABLE_FILE: &str = "/attack_table.txt";
+/// The most garbage rows that can rise after a single lock; the rest stay queued.
+const GARBAGE_CAP: u8 = 8;
+
+/// Lines of garbage sent per clear. Loaded from `attack_table.txt`, one `key: value` per line:
+///
+/// ```text
+/// single: 0
+/// double: 1
+/// triple: 2
+/// tetris: 4
+/// tspin-single: 2
+/// tspin-double: 4
+/// tspin-triple: 6
+/// back-to-back: 1
+/// perfect-clear: 10
+/// combo: 0 0 1 1 2 2 3 3 4 4 4 5
+/// ```
+///
+/// `combo` lists the bonus for the first, second, third... clear in a row; the last value repeats.
+#[derive(Debug, Clone)]
+pub struct AttackTable {
+    pub lines: [u8; 5],
+    pub t_spin: [u8; 4],
+    pub back_to_back: u8,
+    pub perfect_clear: u8,
+    pub combo: Vec<u8>,
+}
+
+impl AttackTable {
+    pub fn guideline() -> AttackTable {
+        AttackTable {
+            lines: [0, 0, 1, 2, 4],
+            t_spin: [0, 2, 4, 6],
+            back_to_back: 1,
+            perfect_clear: 10,
+            combo: vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+        }
+    }
+
+    /// Loads the attack table from the resources, falling back to the guideline table.
+    pub fn load(ctx: &mut Context) -> AttackTable {
+        let mut source = String::new();
+        let read = filesystem::open(ctx, ATTACK_TABLE_FILE)
+            .and_then(|mut file| Ok(file.read_to_string(&mut source)?));
+        if read.is_err() {
+            return AttackTable::guideline();
+        }
+        AttackTable::parse(&source).unwrap_or_else(|error| {
+            eprintln!("Could not read the attack table, using the default one: {}", error);
+            AttackTable::guideline()
+        })
+    }
+
+    pub fn parse(source: &str) -> GameResult<AttackTable> {
+        let mut table = AttackTable::guideline();
+        for line in source.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with("//")) {
+            let (key, value) = match line.find(':') {
+                Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+                None => return Err(parse_error(format!("Expected 'key: value', got '{}'", line))),
+            };
+            match key {
+                "single" => table.lines[1] = parse_number(value)?,
+                "double" => table.lines[2] = parse_number(value)?,
+                "triple" => table.lines[3] = parse_number(value)?,
+                "tetris" => table.lines[4] = parse_number(value)?,
+                "tspin-single" => table.t_spin[1] = parse_number(value)?,
+                "tspin-double" => table.t_spin[2] = parse_number(value)?,
+                "tspin-triple" => table.t_spin[3] = parse_number(value)?,
+                "back-to-back" => table.back_to_back = parse_number(value)?,
+                "perfect-clear" => table.perfect_clear = parse_number(value)?,
+                "combo" => table.combo = value.split_whitespace()
+                    .map(parse_number)
+                    .collect::<GameResult<Vec<_>>>()?,
+                _ => return Err(parse_error(format!("Unknown key '{}'", key))),
+            }
+        }
+        Ok(table)
+    }
+
+    /// `combo` counts the clears in a row before this one, so the first clear is combo 0.
+    pub fn attack(&self, cleaned_lines: u8, t_spin: bool, combo: usize, back_to_back: bool, perfect_clear: bool) -> u8 {
+        let lines = usize::from(cleaned_lines.min(4));
+        let mut attack = if t_spin {
+            self.t_spin[lines.min(3)]
+        } else {
+            self.lines[lines]
+        };
+        if back_to_back {
+            attack = attack.saturating_add(self.back_to_back);
+        }
+        attack = attack.saturating_add(self.combo.get(combo).or_else(|| self.combo.last()).cloned().unwrap_or(0));
+        if perfect_clear {
+            attack = attack.saturating_add(self.perfect_clear);
+        }
+        attack
+    }
+}
+
+/// One player's side of the garbage exchange: the combo and back-to-back state, the garbage
+/// waiting to rise and the lines waiting to be sent.
+#[derive(Clone)]
+pub struct VersusPlayer {
+    table: AttackTable,
+    incoming: VecDeque<(u8, usize)>,
+    outgoing: u8,
+    combo: Option<usize>,
+    back_to_back: bool,
+    pub lines_sent: u16,
+    /// Picks the hole column of each garbage batch received.
+    rng: StdRng,
+}
+
+impl VersusPlayer {
+    pub fn new(table: AttackTable) -> VersusPlayer {
+        VersusPlayer::with_seed(table, rand::thread_rng().gen())
+    }
+
+    /// Holes are picked from `seed`, so games replayed from the same inputs get the same garbage.
+    pub fn with_seed(table: AttackTable, seed: u32) -> VersusPlayer {
+        VersusPlayer {
+            table,
+            incoming: VecDeque::new(),
+            outgoing: 0,
+            combo: None,
+            back_to_back: false,
+            lines_sent: 0,
+            rng: StdRng::from_seed(&[seed as usize]),
+        }
+    }
+
+    /// Clears cancel queued garbage before anything is sent. A lock that clears nothing lets the
+    /// queued garbage rise, returned as (rows, hole column) pairs.
+    pub fn on_lock(&mut self, cleaned_lines: u8, t_spin: bool, perfect_clear: bool) -> Vec<(u8, usize)> {
+        if cleaned_lines == 0 {
+            self.combo = None;
+            return self.take_rising();
+        }
+
+        let combo = self.combo.map_or(0, |combo| combo + 1);
+        self.combo = Some(combo);
+        let difficult = cleaned_lines >= 4 || t_spin;
+        let mut attack = self.table.attack(cleaned_lines, t_spin, combo, difficult && self.back_to_back, perfect_clear);
+        self.back_to_back = difficult;
+
+        while attack > 0 {
+            match self.incoming.front_mut() {
+                Some((lines, _)) if *lines > attack => {
+                    *lines -= attack;
+                    attack = 0;
+                }
+                Some((lines, _)) => {
+                    attack -= *lines;
+                    self.incoming.pop_front();
+                }
+                None => break,
+            }
+        }
+        self.outgoing = self.outgoing.saturating_add(attack);
+        self.lines_sent = self.lines_sent.saturating_add(u16::from(attack));
+        Vec::new()
+    }
+
+    /// Queues `lines` of garbage with a hole in one of the `columns` columns of the board.
+    pub fn receive(&mut self, lines: u8, columns: usize) {
+        if lines > 0 {
+            let hole = self.rng.gen_range(1, columns + 1);
+            self.incoming.push_back((lines, hole));
+        }
+    }
+
+    pub fn take_outgoing(&mut self) -> u8 {
+        let outgoing = self.outgoing;
+        self.outgoing = 0;
+        outgoing
+    }
+
+    pub fn pending(&self) -> u16 {
+        self.incoming.iter().map(|(lines, _)| u16::from(*lines)).sum()
+    }
+
+    fn take_rising(&mut self) -> Vec<(u8, usize)> {
+        let mut rising = Vec::new();
+        let mut room = GARBAGE_CAP;
+        while room > 0 {
+            match self.incoming.front_mut() {
+                Some((lines, hole)) if *lines > room => {
+                    rising.push((room, *hole));
+                    *lines -= room;
+                    room = 0;
+                }
+                Some((lines, hole)) => {
+                    rising.push((*lines, *hole));
+                    room -= *lines;
+                    self.incoming.pop_front();
+                }
+                None => break,
+            }
+        }
+        rising
+    }
+}
+
+fn parse_number(value: &str) -> GameResult<u8> {
+    value.parse().map_err(|_| parse_error(format!("Expected a number, got '{}'", value)))
+}
+
+fn parse_error(message: String) -> GameError {
+    GameError::ResourceLoadError(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holes_stay_on_the_board() {
+        let mut player = VersusPlayer::with_seed(AttackTable::guideline(), 7);
+        for _ in 0..100 {
+            player.receive(1, 5);
+        }
+        while player.pending() > 0 {
+            for (_, hole) in player.on_lock(0, false, false) {
+                assert!((1..=5).contains(&hole));
+            }
+        }
+    }
+
+    #[test]
+    fn attacks_saturate() {
+        let mut table = AttackTable::guideline();
+        table.lines[4] = 200;
+        table.perfect_clear = 200;
+        assert_eq!(table.attack(4, false, 0, true, true), u8::MAX);
+
+        let mut player = VersusPlayer::with_seed(table, 7);
+        player.on_lock(4, false, true);
+        player.on_lock(4, false, true);
+        assert_eq!(player.take_outgoing(), u8::MAX);
+    }
+}
@@ -0,0 +1,263 @@
+//! Finding games on the local network. Hosts and broadcasts announce themselves once a second with
+//! a UDP broadcast, and a `Browser` lists the ones it has heard from recently, so nobody has to
+//! type an address. The announcement format is documented in `docs/online-protocol.md`.
+
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+use ggez::GameResult;
+use crate::tbp::json::Json;
+use super::protocol::{number, string, PROTOCOL_VERSION};
+
+pub const DISCOVERY_PORT: u16 = 7880;
+/// Only one socket on a machine can listen on a port, so a browser takes the first free one of
+/// this many from `DISCOVERY_PORT` on and games are announced to all of them.
+const DISCOVERY_PORTS: u16 = 4;
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+/// A host not heard from for this long has gone.
+const HOST_TIMEOUT: Duration = Duration::from_secs(4);
+const MAX_DATAGRAM: usize = 1024;
+
+/// What a host offers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Versus,
+    Coop,
+    /// A broadcast to watch, from `spectate::Broadcaster`.
+    Spectate,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Versus => "VERSUS",
+            Kind::Coop => "CO-OP",
+            Kind::Spectate => "WATCH",
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Kind::Versus => "versus",
+            Kind::Coop => "coop",
+            Kind::Spectate => "spectate",
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Kind> {
+        match code {
+            "versus" => Some(Kind::Versus),
+            "coop" => Some(Kind::Coop),
+            "spectate" => Some(Kind::Spectate),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Announcement {
+    pub kind: Kind,
+    /// The game's name, which is the host's name or the names of the players being watched.
+    pub name: String,
+    /// The name of the rule preset played.
+    pub rules: String,
+    pub players: u8,
+    /// How many players the game takes, or 0 when anyone may join, as for spectating.
+    pub capacity: u8,
+    /// The TCP port to connect to.
+    pub port: u16,
+}
+
+impl Announcement {
+    pub fn full(&self) -> bool {
+        self.capacity > 0 && self.players >= self.capacity
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("type", Json::string("announce")),
+            ("version", Json::Number(f64::from(PROTOCOL_VERSION))),
+            ("kind", Json::string(self.kind.code())),
+            ("name", Json::string(&self.name)),
+            ("rules", Json::string(&self.rules)),
+            ("players", Json::Number(f64::from(self.players))),
+            ("capacity", Json::Number(f64::from(self.capacity))),
+            ("port", Json::Number(f64::from(self.port))),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Result<Announcement, String> {
+        if string(json, "type")? != "announce" {
+            return Err(String::from("Not an announcement"));
+        }
+        let version = number(json, "version")? as u32;
+        if version != PROTOCOL_VERSION {
+            return Err(format!("An announcement for protocol version {}", version));
+        }
+        let kind = string(json, "kind")?;
+        Ok(Announcement {
+            kind: Kind::from_code(kind).ok_or_else(|| format!("Unknown game kind '{}'", kind))?,
+            name: string(json, "name")?.to_string(),
+            rules: string(json, "rules")?.to_string(),
+            players: number(json, "players")? as u8,
+            capacity: number(json, "capacity")? as u8,
+            port: number(json, "port")? as u16,
+        })
+    }
+}
+
+/// Announces a game on the local network at most once per `ANNOUNCE_INTERVAL`, however often
+/// it is called.
+pub struct Announcer {
+    socket: UdpSocket,
+    last: Option<Instant>,
+    /// Whether a failure to announce has been reported, so it is only reported once.
+    failed: bool,
+}
+
+impl Announcer {
+    pub fn new() -> GameResult<Announcer> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        Ok(Announcer { socket, last: None, failed: false })
+    }
+
+    pub fn announce(&mut self, announcement: &Announcement) {
+        if self.last.is_some_and(|last| last.elapsed() < ANNOUNCE_INTERVAL) {
+            return;
+        }
+        self.last = Some(Instant::now());
+        let datagram = announcement.to_json().to_string();
+        // Without a network to broadcast on, this machine can still find the game.
+        let sent = (DISCOVERY_PORT..DISCOVERY_PORT + DISCOVERY_PORTS)
+            .map(|port| self.socket.send_to(datagram.as_bytes(), (Ipv4Addr::BROADCAST, port))
+                .or_else(|_| self.socket.send_to(datagram.as_bytes(), (Ipv4Addr::LOCALHOST, port))))
+            .collect::<Result<Vec<_>, _>>();
+        if let Err(error) = sent {
+            if !self.failed {
+                eprintln!("Could not announce the game on the local network: {}", error);
+                self.failed = true;
+            }
+        }
+    }
+}
+
+/// A game heard on the local network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanHost {
+    pub ip: IpAddr,
+    pub announcement: Announcement,
+    seen: Instant,
+}
+
+impl LanHost {
+    /// The address to join or watch the game at.
+    pub fn address(&self) -> String {
+        SocketAddr::new(self.ip, self.announcement.port).to_string()
+    }
+}
+
+/// Listens for announcements and keeps the games heard from recently, in the order first heard.
+pub struct Browser {
+    socket: UdpSocket,
+    pub hosts: Vec<LanHost>,
+}
+
+impl Browser {
+    /// Listens on the first discovery port no other browser on this machine has taken.
+    pub fn listen() -> GameResult<Browser> {
+        let mut result = Browser::listen_on(DISCOVERY_PORT);
+        for port in DISCOVERY_PORT + 1..DISCOVERY_PORT + DISCOVERY_PORTS {
+            if result.is_ok() {
+                break;
+            }
+            result = Browser::listen_on(port);
+        }
+        result
+    }
+
+    pub fn listen_on(port: u16) -> GameResult<Browser> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        socket.set_nonblocking(true)?;
+        Ok(Browser { socket, hosts: Vec::new() })
+    }
+
+    /// Takes in the announcements that have arrived and forgets hosts that have gone quiet.
+    pub fn update(&mut self) -> GameResult {
+        let mut buffer = [0; MAX_DATAGRAM];
+        loop {
+            let (length, sender) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error.into()),
+            };
+            let announcement = match std::str::from_utf8(&buffer[..length]).map_err(|error| error.to_string())
+                .and_then(Json::parse)
+                .and_then(|json| Announcement::from_json(&json)) {
+                Ok(announcement) => announcement,
+                Err(_) => continue,
+            };
+            let ip = sender.ip();
+            let host = LanHost { ip, announcement, seen: Instant::now() };
+            let known = self.hosts.iter_mut()
+                .find(|known| known.ip == ip && known.announcement.port == host.announcement.port);
+            match known {
+                Some(known) => *known = host,
+                None => self.hosts.push(host),
+            }
+        }
+        self.hosts.retain(|host| host.seen.elapsed() < HOST_TIMEOUT);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announcement() -> Announcement {
+        Announcement {
+            kind: Kind::Versus,
+            name: String::from("ALICE"),
+            rules: String::from("Standard"),
+            players: 1,
+            capacity: 2,
+            port: 7878,
+        }
+    }
+
+    #[test]
+    fn reads_its_own_announcements() {
+        let json = Json::parse(&announcement().to_json().to_string()).unwrap();
+        assert_eq!(Announcement::from_json(&json), Ok(announcement()));
+    }
+
+    #[test]
+    fn ignores_other_versions_and_kinds() {
+        let source = announcement().to_json().to_string();
+        let version = format!("\"version\":{}", PROTOCOL_VERSION);
+        for (from, to) in [(version.as_str(), "\"version\":1"), ("\"kind\":\"versus\"", "\"kind\":\"battle\"")].iter() {
+            let changed = Json::parse(&source.replacen(from, to, 1)).unwrap();
+            assert!(Announcement::from_json(&changed).is_err(), "{}", to);
+        }
+    }
+
+    #[test]
+    fn browsers_on_one_machine_all_hear_a_game() {
+        let mut browsers = [Browser::listen().unwrap(), Browser::listen().unwrap()];
+        let mut announcer = Announcer::new().unwrap();
+        let started = Instant::now();
+        while browsers.iter().any(|browser| browser.hosts.is_empty()) {
+            assert!(started.elapsed() < Duration::from_secs(10), "a browser never heard the game");
+            announcer.last = None;
+            announcer.announce(&announcement());
+            for browser in browsers.iter_mut() {
+                browser.update().unwrap();
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        for browser in &browsers {
+            assert_eq!(browser.hosts[0].announcement, announcement());
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Lockstep netcode for online co-op. Each side runs the shared board from both players' inputs:
+//! local presses apply after a short delay, and a frame only runs once the other player's presses
+//! for it have arrived. Co-op pieces push against each other on every frame, so nothing is
+//! predicted and nothing is rolled back.
+
+use std::cmp::Ordering;
+use std::mem;
+use crate::controls::Action;
+use crate::game::GameEvent;
+use crate::modes::coop::CoopState;
+use crate::modes::rules::RuleSet;
+use super::rollback::INPUT_DELAY;
+
+pub struct Lockstep {
+    /// Which of the co-op players is played on this side.
+    local: usize,
+    pub state: CoopState,
+    /// The frame about to run.
+    frame: u32,
+    /// Each player's presses by frame, as far as they are known.
+    inputs: [Vec<Vec<Action>>; 2],
+    /// Local presses for the next frame.
+    pressed: Vec<Action>,
+    /// Local frames the other side has received.
+    acknowledged: u32,
+}
+
+impl Lockstep {
+    /// A co-op game dealt from `seed`, with this side playing `local`.
+    pub fn new(local: usize, seed: u32, rules: &RuleSet) -> Lockstep {
+        let mut inputs = [Vec::new(), Vec::new()];
+        inputs[local] = vec![Vec::new(); INPUT_DELAY as usize];
+        Lockstep {
+            local,
+            state: CoopState::with_seed(seed, rules.clone()),
+            frame: 0,
+            inputs,
+            pressed: Vec::new(),
+            acknowledged: 0,
+        }
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn press(&mut self, action: Action) {
+        self.pressed.push(action);
+    }
+
+    /// The other player's frames received so far.
+    pub fn confirmed(&self) -> u32 {
+        self.inputs[1 - self.local].len() as u32
+    }
+
+    /// Whether the next frame is waiting for the other player's presses.
+    pub fn waiting(&self) -> bool {
+        self.frame >= self.confirmed()
+    }
+
+    /// Takes the other player's presses from frame `first` on, along with how many local frames
+    /// they have. Frames already known are skipped; frames after a gap wait to be sent again.
+    pub fn receive(&mut self, first: u32, frames: Vec<Vec<Action>>, acknowledged: u32) {
+        self.acknowledged = self.acknowledged.max(acknowledged);
+        let remote = &mut self.inputs[1 - self.local];
+        for (frame, actions) in (first..).zip(frames) {
+            match (frame as usize).cmp(&remote.len()) {
+                Ordering::Less => continue,
+                Ordering::Greater => break,
+                Ordering::Equal => remote.push(actions),
+            }
+        }
+    }
+
+    /// The local presses the other side has not acknowledged, with the frame of the first.
+    pub fn unacknowledged(&self) -> (u32, Vec<Vec<Action>>) {
+        let local = &self.inputs[self.local];
+        let first = (self.acknowledged as usize).min(local.len());
+        (first as u32, local[first..].to_vec())
+    }
+
+    /// Runs the next frame once both players' presses for it are known, and returns what
+    /// happened on the board.
+    pub fn advance(&mut self) -> Vec<GameEvent> {
+        if self.waiting() {
+            return Vec::new();
+        }
+        let pressed = mem::take(&mut self.pressed);
+        self.inputs[self.local].push(pressed);
+        for (index, inputs) in self.inputs.iter().enumerate() {
+            for action in &inputs[self.frame as usize] {
+                self.state.apply(index, *action);
+            }
+        }
+        self.frame += 1;
+        self.state.step()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What `player` presses on `frame`: moves toward their own side and drops.
+    fn script(player: usize, frame: u32) -> Action {
+        match (frame + player as u32) % 5 {
+            0 if player == 0 => Action::MoveLeft,
+            0 => Action::MoveRight,
+            1 => Action::RotateClockwise,
+            _ => Action::SoftDrop,
+        }
+    }
+
+    #[test]
+    fn both_sides_play_the_same_game() {
+        const FRAMES: u32 = 600;
+        let mut games = [Lockstep::new(0, 7, &RuleSet::standard()), Lockstep::new(1, 7, &RuleSet::standard())];
+        let mut turns = 0;
+        while games.iter().any(|game| game.frame() < FRAMES) {
+            for player in 0..2 {
+                let game = &mut games[player];
+                if game.frame() < FRAMES && !game.waiting() {
+                    game.press(script(player, game.frame()));
+                    game.advance();
+                }
+                let (first, frames) = game.unacknowledged();
+                let ack = game.confirmed();
+                games[1 - player].receive(first, frames, ack);
+            }
+            turns += 1;
+            assert!(turns < FRAMES * 2, "the games stopped waiting for each other");
+        }
+
+        let (first, second) = (&games[0].state, &games[1].state);
+        assert!(first.board.codes().iter().any(|code| *code != 0));
+        assert_eq!(first.board.codes(), second.board.codes());
+        assert_eq!(first.score.score, second.score.score);
+        assert_eq!(first.game_over, second.game_over);
+        for (mine, theirs) in first.players.iter().zip(second.players.iter()) {
+            assert_eq!(mine.tetrimino.cells(), theirs.tetrimino.cells());
+        }
+    }
+}
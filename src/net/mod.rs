@@ -0,0 +1,218 @@
+//! Online versus and co-op over TCP. Each side sends `protocol::Message`s as JSON, one per line;
+//! the wire format is documented in `docs/online-protocol.md` for clients written in other
+//! languages. `session::Session` plays a match over a `Link`, which is a `Connection` or one seen
+//! through `simulator::SimulatedLink`; versus rounds are played with `rollback::Rollback` and
+//! co-op rounds with `lockstep::Lockstep`.
+
+pub mod discovery;
+pub mod lockstep;
+pub mod protocol;
+pub mod rollback;
+pub mod session;
+pub mod simulator;
+pub mod spectate;
+
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use ggez::{GameResult, GameError};
+use crate::tbp::json::Json;
+use self::protocol::Message;
+
+pub const DEFAULT_PORT: u16 = 7878;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// The longest line a peer may send. A peer that goes past it is cut off, rather than kept in
+/// memory until the game runs out.
+const MAX_LINE: usize = 1 << 20;
+
+/// Carries messages to and from the other player.
+pub trait Link {
+    fn send(&mut self, message: &Message) -> GameResult;
+
+    /// The next message, if one has arrived, or an error once the other side has gone.
+    fn poll(&mut self) -> GameResult<Option<Message>>;
+}
+
+/// One end of a connection. Messages are read on a separate thread so the game never waits on
+/// the network.
+pub struct Connection {
+    stream: TcpStream,
+    messages: Receiver<Message>,
+}
+
+impl Connection {
+    /// Connects to `address`, which is a host name or IP address with an optional port.
+    pub fn connect(address: &str) -> GameResult<Connection> {
+        Connection::from_stream(connect(address, DEFAULT_PORT)?)
+    }
+
+    pub fn from_stream(stream: TcpStream) -> GameResult<Connection> {
+        stream.set_nodelay(true)?;
+        let messages = read_lines(&stream, Message::from_json)?;
+        Ok(Connection { stream, messages })
+    }
+
+    pub fn peer_address(&self) -> Option<SocketAddr> {
+        self.stream.peer_addr().ok()
+    }
+}
+
+impl Link for Connection {
+    fn send(&mut self, message: &Message) -> GameResult {
+        writeln!(self.stream, "{}", message.to_json())?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    fn poll(&mut self) -> GameResult<Option<Message>> {
+        match self.messages.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(network_error(String::from("The other player left"))),
+        }
+    }
+}
+
+impl Drop for Connection {
+    /// Shuts the socket down for both ends, as the reading thread holds a second handle to it.
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Listens for a player to connect, without blocking the game while it waits.
+pub struct Host {
+    listener: TcpListener,
+}
+
+impl Host {
+    /// Listens on every interface on `port`, or on a free port when `port` is 0.
+    pub fn listen(port: u16) -> GameResult<Host> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Host { listener })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map_or(0, |address| address.port())
+    }
+
+    /// The player who has connected since the last call, if any.
+    pub fn accept(&self) -> GameResult<Option<Connection>> {
+        match self.listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                Ok(Some(Connection::from_stream(stream)?))
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+/// Connects to `address`, on `default_port` unless the address gives one.
+fn connect(address: &str, default_port: u16) -> GameResult<TcpStream> {
+    let socket_address = resolve(address, default_port)?;
+    Ok(TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT)?)
+}
+
+/// Finds `address`, on `default_port` unless the address gives one. Takes host names and IPv4 or
+/// IPv6 addresses, with IPv6 addresses in brackets when they give a port.
+pub fn resolve(address: &str, default_port: u16) -> GameResult<SocketAddr> {
+    if let Ok(socket_address) = address.parse::<SocketAddr>() {
+        return Ok(socket_address);
+    }
+    let host = address.strip_prefix('[').and_then(|host| host.strip_suffix(']')).unwrap_or(address);
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, default_port));
+    }
+    let found = match address.to_socket_addrs() {
+        Ok(found) => found,
+        Err(_) => (address, default_port).to_socket_addrs()?,
+    };
+    found.into_iter().next().ok_or_else(|| network_error(format!("Could not find {}", address)))
+}
+
+/// Reads JSON lines from `stream` on a separate thread, skipping the ones `parse` rejects. The
+/// receiver disconnects when the stream ends or sends a line longer than `MAX_LINE`.
+fn read_lines<T: Send + 'static>(stream: &TcpStream, parse: fn(&Json) -> Result<T, String>) -> GameResult<Receiver<T>> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let (sender, received) = mpsc::channel();
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        loop {
+            bytes.clear();
+            match reader.by_ref().take(MAX_LINE as u64 + 1).read_until(b'\n', &mut bytes) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => (),
+            }
+            if bytes.len() > MAX_LINE && !bytes.ends_with(b"\n") {
+                eprintln!("Disconnecting from a peer that sent a line over {} bytes", MAX_LINE);
+                break;
+            }
+            let line = String::from_utf8_lossy(&bytes);
+            match Json::parse(line.trim_end()).and_then(|json| parse(&json)) {
+                Ok(value) => {
+                    if sender.send(value).is_err() {
+                        break;
+                    }
+                }
+                Err(error) => eprintln!("Ignoring a bad message: {}", error),
+            }
+        }
+    });
+    Ok(received)
+}
+
+fn network_error(message: String) -> GameError {
+    GameError::ResourceLoadError(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A connection and the raw stream at its other end.
+    fn connected() -> (Connection, TcpStream) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        (Connection::from_stream(stream).unwrap(), peer)
+    }
+
+    #[test]
+    fn resolves_addresses_with_and_without_ports() {
+        let found = |address| resolve(address, 7878).unwrap().to_string();
+        assert_eq!(found("127.0.0.1"), "127.0.0.1:7878");
+        assert_eq!(found("127.0.0.1:9000"), "127.0.0.1:9000");
+        assert_eq!(found("::1"), "[::1]:7878");
+        assert_eq!(found("[::1]"), "[::1]:7878");
+        assert_eq!(found("[fe80::1]:9000"), "[fe80::1]:9000");
+        assert!(found("localhost").ends_with(":7878"));
+        assert!(found("localhost:9000").ends_with(":9000"));
+    }
+
+    #[test]
+    fn skips_bad_lines() {
+        let (connection, mut peer) = connected();
+        writeln!(peer, "{}", "[".repeat(100_000)).unwrap();
+        writeln!(peer, "{}", Message::Ready.to_json()).unwrap();
+        let message = connection.messages.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message, Message::Ready);
+    }
+
+    #[test]
+    fn disconnects_a_peer_sending_an_endless_line() {
+        let (connection, mut peer) = connected();
+        let chunk = vec![b' '; 1 << 16];
+        for _ in 0..(MAX_LINE / chunk.len() + 2) {
+            if peer.write_all(&chunk).is_err() {
+                break;
+            }
+        }
+        let result = connection.messages.recv_timeout(Duration::from_secs(5));
+        assert_eq!(result, Err(mpsc::RecvTimeoutError::Disconnected));
+    }
+}
@@ -0,0 +1,247 @@
+//! The messages of the online versus and co-op protocol and their JSON form. See
+//! `docs/online-protocol.md`.
+
+use crate::controls::Action;
+use crate::game::GamePlayState;
+use crate::modes::GameMode;
+use crate::tbp::{piece_from_name, piece_name};
+use crate::tbp::json::Json;
+use crate::world::{Board, Cell, TetriminoType};
+
+/// Bumped whenever a message changes shape; both sides must speak the same version.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// The first message each side sends.
+    Hello { version: u32, name: String },
+    /// Host to guest: the rule set to play, in the `.rules` format, and whether the match is
+    /// versus or co-op.
+    Rules { source: String, mode: GameMode },
+    RulesAccepted,
+    RulesRejected { reason: String },
+    /// Either side: ready for the first round.
+    Ready,
+    /// Host to guest, once both are ready: a round begins with pieces dealt from `seed`.
+    Start { seed: u32 },
+    /// The sender's inputs for `round` from frame `first` on, one list of actions per frame, with
+    /// the number of the receiver's frames it has so far. Inputs are repeated until acknowledged.
+    Inputs { round: u32, ack: u32, first: u32, frames: Vec<Vec<Action>> },
+    /// Garbage lines sent to the other side.
+    Attack { lines: u8 },
+    /// How the sender's game looks now, for drawing it on the other side.
+    Board(Snapshot),
+    /// The sender topped out.
+    GameOver,
+    /// Either side: ready for another round.
+    Rematch,
+    /// The sender is leaving.
+    Bye { reason: String },
+}
+
+/// Enough of a game to draw it: the stack, the falling piece, hold, queue and counters. Cells are
+/// (column, row) from the top left of the playfield; the piece can be above it while it enters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub columns: usize,
+    pub rows: usize,
+    pub codes: Vec<u8>,
+    pub piece: Option<(TetriminoType, Vec<(i8, i8)>)>,
+    pub hold: Option<TetriminoType>,
+    pub queue: Vec<TetriminoType>,
+    pub pending: u16,
+    pub lines: u16,
+    pub score: u32,
+}
+
+impl Snapshot {
+    pub fn of(state: &GamePlayState) -> Snapshot {
+        let piece = if state.piece_active() {
+            let cells = state.tetrimino.cells().iter().map(|point| (point.x - 1, point.y)).collect();
+            Some((state.tetrimino.kind, cells))
+        } else {
+            None
+        };
+        Snapshot {
+            columns: state.board.columns(),
+            rows: state.board.rows(),
+            codes: state.board.codes(),
+            piece,
+            hold: state.hold,
+            queue: state.queue.iter().copied().collect(),
+            pending: state.versus.as_ref().map_or(0, |versus| versus.pending()),
+            lines: state.score.lines,
+            score: state.score.score,
+        }
+    }
+
+    /// The stack with the falling piece in it.
+    pub fn board(&self) -> Board {
+        let mut board = Board::from_codes(self.columns, self.rows, &self.codes);
+        if let Some((kind, cells)) = &self.piece {
+            for (x, y) in cells {
+                if *x >= 0 && *y >= 0 && (*x as usize) < self.columns {
+                    if let Some(cell) = board.data.get_mut((*y as usize, *x as usize + 1)) {
+                        *cell = Cell { code: kind.to_code(), locked_at: 0 };
+                    }
+                }
+            }
+        }
+        board
+    }
+
+    fn to_json(&self) -> Json {
+        let codes: String = self.codes.iter().map(|code| char::from(b'0' + (*code).min(9))).collect();
+        let piece = match &self.piece {
+            Some((kind, cells)) => Json::object(vec![
+                ("type", Json::string(piece_name(*kind))),
+                ("cells", Json::Array(cells.iter()
+                    .map(|(x, y)| Json::Array(vec![Json::Number(f64::from(*x)), Json::Number(f64::from(*y))]))
+                    .collect())),
+            ]),
+            None => Json::Null,
+        };
+        message("board", vec![
+            ("columns", Json::Number(self.columns as f64)),
+            ("rows", Json::Number(self.rows as f64)),
+            ("cells", Json::String(codes)),
+            ("piece", piece),
+            ("hold", self.hold.map_or(Json::Null, |kind| Json::string(piece_name(kind)))),
+            ("queue", Json::Array(self.queue.iter().map(|kind| Json::string(piece_name(*kind))).collect())),
+            ("pending", Json::Number(f64::from(self.pending))),
+            ("lines", Json::Number(f64::from(self.lines))),
+            ("score", Json::Number(f64::from(self.score))),
+        ])
+    }
+
+    fn from_json(json: &Json) -> Result<Snapshot, String> {
+        let columns = number(json, "columns")? as usize;
+        let rows = number(json, "rows")? as usize;
+        let codes: Vec<u8> = string(json, "cells")?.bytes()
+            .map(|digit| digit.wrapping_sub(b'0'))
+            .collect();
+        if columns == 0 || codes.len() != columns * rows || codes.iter().any(|code| *code > 9) {
+            return Err(String::from("Bad board cells"));
+        }
+        let piece = match json.get("piece") {
+            Some(Json::Null) | None => None,
+            Some(piece) => {
+                let kind = piece.get("type").and_then(Json::as_str).and_then(piece_from_name)
+                    .ok_or("Bad piece type")?;
+                let cells = piece.get("cells").and_then(Json::as_array).unwrap_or(&[]).iter()
+                    .map(|cell| match cell.as_array() {
+                        Some([x, y]) => match (x.as_i64(), y.as_i64()) {
+                            (Some(x), Some(y)) => Ok((x as i8, y as i8)),
+                            _ => Err(String::from("Bad piece cell")),
+                        },
+                        _ => Err(String::from("Bad piece cell")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Some((kind, cells))
+            }
+        };
+        Ok(Snapshot {
+            columns,
+            rows,
+            codes,
+            piece,
+            hold: json.get("hold").and_then(Json::as_str).and_then(piece_from_name),
+            queue: json.get("queue").and_then(Json::as_array).unwrap_or(&[]).iter()
+                .filter_map(|kind| kind.as_str().and_then(piece_from_name))
+                .collect(),
+            pending: number(json, "pending")? as u16,
+            lines: number(json, "lines")? as u16,
+            score: number(json, "score")? as u32,
+        })
+    }
+}
+
+impl Message {
+    pub fn to_json(&self) -> Json {
+        match self {
+            Message::Hello { version, name } => message("hello", vec![
+                ("version", Json::Number(f64::from(*version))),
+                ("name", Json::string(name)),
+            ]),
+            Message::Rules { source, mode } => message("rules", vec![
+                ("rules", Json::string(source)),
+                ("mode", Json::string(if *mode == GameMode::Coop { "coop" } else { "versus" })),
+            ]),
+            Message::RulesAccepted => message("rules_accepted", Vec::new()),
+            Message::RulesRejected { reason } => message("rules_rejected", vec![("reason", Json::string(reason))]),
+            Message::Ready => message("ready", Vec::new()),
+            Message::Start { seed } => message("start", vec![("seed", Json::Number(f64::from(*seed)))]),
+            Message::Inputs { round, ack, first, frames } => message("inputs", vec![
+                ("round", Json::Number(f64::from(*round))),
+                ("ack", Json::Number(f64::from(*ack))),
+                ("first", Json::Number(f64::from(*first))),
+                ("frames", Json::Array(frames.iter()
+                    .map(|actions| Json::String(actions.iter().map(|action| action.letter()).collect()))
+                    .collect())),
+            ]),
+            Message::Attack { lines } => message("attack", vec![("lines", Json::Number(f64::from(*lines)))]),
+            Message::Board(snapshot) => snapshot.to_json(),
+            Message::GameOver => message("game_over", Vec::new()),
+            Message::Rematch => message("rematch", Vec::new()),
+            Message::Bye { reason } => message("bye", vec![("reason", Json::string(reason))]),
+        }
+    }
+
+    pub fn from_json(json: &Json) -> Result<Message, String> {
+        let kind = json.get("type").and_then(Json::as_str).ok_or("A message without a type")?;
+        Ok(match kind {
+            "hello" => Message::Hello {
+                version: number(json, "version")? as u32,
+                name: string(json, "name")?.to_string(),
+            },
+            "rules" => Message::Rules {
+                source: string(json, "rules")?.to_string(),
+                mode: match string(json, "mode")? {
+                    "versus" => GameMode::Versus,
+                    "coop" => GameMode::Coop,
+                    mode => return Err(format!("Unknown mode '{}'", mode)),
+                },
+            },
+            "rules_accepted" => Message::RulesAccepted,
+            "rules_rejected" => Message::RulesRejected { reason: string(json, "reason")?.to_string() },
+            "ready" => Message::Ready,
+            "start" => Message::Start { seed: number(json, "seed")? as u32 },
+            "inputs" => Message::Inputs {
+                round: number(json, "round")? as u32,
+                ack: number(json, "ack")? as u32,
+                first: number(json, "first")? as u32,
+                frames: json.get("frames").and_then(Json::as_array).ok_or("Expected a list of frames")?.iter()
+                    .map(|frame| frame.as_str().ok_or("Expected a string of actions")?
+                        .chars()
+                        .map(|letter| Action::from_letter(letter).ok_or(format!("Unknown action '{}'", letter)))
+                        .collect())
+                    .collect::<Result<_, String>>()?,
+            },
+            "attack" => Message::Attack { lines: number(json, "lines")?.min(255) as u8 },
+            "board" => Message::Board(Snapshot::from_json(json)?),
+            "game_over" => Message::GameOver,
+            "rematch" => Message::Rematch,
+            "bye" => Message::Bye { reason: string(json, "reason").unwrap_or("").to_string() },
+            _ => return Err(format!("Unknown message type '{}'", kind)),
+        })
+    }
+
+    /// Whether losing the message does no harm because later ones repeat it. Only these are
+    /// dropped by the network simulator.
+    pub fn is_redundant(&self) -> bool {
+        matches!(self, Message::Inputs { .. } | Message::Board(_))
+    }
+}
+
+fn message(kind: &str, mut fields: Vec<(&str, Json)>) -> Json {
+    fields.insert(0, ("type", Json::string(kind)));
+    Json::object(fields)
+}
+
+pub(super) fn number(json: &Json, key: &str) -> Result<i64, String> {
+    json.get(key).and_then(Json::as_i64).filter(|value| *value >= 0).ok_or(format!("Expected a number for '{}'", key))
+}
+
+pub(super) fn string<'a>(json: &'a Json, key: &str) -> Result<&'a str, String> {
+    json.get(key).and_then(Json::as_str).ok_or(format!("Expected a string for '{}'", key))
+}
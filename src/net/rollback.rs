@@ -0,0 +1,380 @@
+//! Rollback netcode. Each side runs both players' games from both players' inputs: local presses
+//! apply after a short delay, and the other player is predicted to press nothing until their
+//! inputs arrive. The games are kept from before every frame that ran on a prediction, so when an
+//! input turns out to differ they are put back to that frame and run forward again with it.
+
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::mem;
+use ggez::GameResult;
+use crate::controls::Action;
+use crate::game::{GameConfig, GameEvent, GamePlayState};
+use crate::modes::GameMode;
+use crate::modes::rules::RuleSet;
+use crate::modes::versus::{AttackTable, VersusPlayer};
+
+/// Frames between a press and the frame it applies to, which hides small delays without rollbacks.
+pub const INPUT_DELAY: u32 = 2;
+/// How many frames the games may run ahead of the other player's inputs before they wait.
+pub const MAX_ROLLBACK: u32 = 12;
+
+/// How a round ended for this side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Won,
+    Lost,
+    Draw,
+}
+
+pub struct Rollback {
+    /// Which of `players` is played on this side.
+    local: usize,
+    players: Vec<GamePlayState>,
+    /// The frame about to run.
+    frame: u32,
+    /// Each player's presses by frame, as far as they are known.
+    inputs: [Vec<Vec<Action>>; 2],
+    /// Local presses for the next frame.
+    pressed: Vec<Action>,
+    /// The games before each frame that ran on a prediction, oldest first.
+    snapshots: VecDeque<(u32, Vec<GamePlayState>)>,
+    /// The earliest frame whose prediction turned out wrong.
+    mispredicted: Option<u32>,
+    /// Local frames the other side has received.
+    acknowledged: u32,
+    pub rollbacks: u32,
+    /// Frames run again by rollbacks.
+    pub resimulated: u32,
+}
+
+impl Rollback {
+    /// Both players' games for a round dealt from `seed`, with this side playing `local`.
+    pub fn new(local: usize, seed: u32, rules: &RuleSet, attack_table: &AttackTable) -> GameResult<Rollback> {
+        let mut players = Vec::new();
+        for index in 0..2 {
+            let mut config = GameConfig::new(GameMode::Versus);
+            config.rules = rules.clone();
+            config.seed = Some(seed);
+            let mut player = GamePlayState::new(config, None)?;
+            let hole_seed = seed.wrapping_add(index as u32 + 1);
+            player.versus = Some(VersusPlayer::with_seed(attack_table.clone(), hole_seed));
+            players.push(player);
+        }
+        let mut inputs = [Vec::new(), Vec::new()];
+        inputs[local] = vec![Vec::new(); INPUT_DELAY as usize];
+        Ok(Rollback {
+            local,
+            players,
+            frame: 0,
+            inputs,
+            pressed: Vec::new(),
+            snapshots: VecDeque::new(),
+            mispredicted: None,
+            acknowledged: 0,
+            rollbacks: 0,
+            resimulated: 0,
+        })
+    }
+
+    pub fn local(&self) -> &GamePlayState {
+        &self.players[self.local]
+    }
+
+    pub fn remote(&self) -> &GamePlayState {
+        &self.players[1 - self.local]
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn press(&mut self, action: Action) {
+        self.pressed.push(action);
+    }
+
+    /// The other player's frames received so far.
+    pub fn confirmed(&self) -> u32 {
+        self.inputs[1 - self.local].len() as u32
+    }
+
+    /// Whether the games are as far ahead of the other player as they may go.
+    pub fn waiting(&self) -> bool {
+        self.frame >= self.confirmed() + MAX_ROLLBACK
+    }
+
+    /// Takes the other player's presses from frame `first` on, along with how many local frames
+    /// they have. Frames already known are skipped; frames after a gap wait to be sent again.
+    pub fn receive(&mut self, first: u32, frames: Vec<Vec<Action>>, acknowledged: u32) {
+        self.acknowledged = self.acknowledged.max(acknowledged);
+        let remote = &mut self.inputs[1 - self.local];
+        for (frame, actions) in (first..).zip(frames) {
+            match (frame as usize).cmp(&remote.len()) {
+                Ordering::Less => continue,
+                Ordering::Greater => break,
+                Ordering::Equal => (),
+            }
+            if frame < self.frame && !actions.is_empty() {
+                self.mispredicted = Some(self.mispredicted.map_or(frame, |earliest| earliest.min(frame)));
+            }
+            remote.push(actions);
+        }
+    }
+
+    /// The local presses the other side has not acknowledged, with the frame of the first.
+    pub fn unacknowledged(&self) -> (u32, Vec<Vec<Action>>) {
+        let local = &self.inputs[self.local];
+        let first = (self.acknowledged as usize).min(local.len());
+        (first as u32, local[first..].to_vec())
+    }
+
+    /// Runs the next frame, after rolling back if a prediction was wrong, and returns what
+    /// happened to the local game. Does nothing while waiting for the other player.
+    pub fn advance(&mut self) -> Option<GameEvent> {
+        if self.waiting() {
+            return None;
+        }
+        if let Some(frame) = self.mispredicted.take() {
+            self.roll_back(frame);
+        }
+        let pressed = mem::take(&mut self.pressed);
+        self.inputs[self.local].push(pressed);
+        let mut events = self.run_frame();
+
+        let confirmed = self.confirmed();
+        while self.snapshots.front().is_some_and(|(frame, _)| *frame < confirmed) {
+            self.snapshots.pop_front();
+        }
+        events.swap_remove(self.local)
+    }
+
+    fn roll_back(&mut self, frame: u32) {
+        let position = match self.snapshots.iter().position(|(snapshot_frame, _)| *snapshot_frame == frame) {
+            Some(position) => position,
+            None => return,
+        };
+        let end = self.frame;
+        self.players = self.snapshots[position].1.clone();
+        self.snapshots.truncate(position);
+        self.frame = frame;
+        while self.frame < end {
+            self.run_frame();
+            self.resimulated += 1;
+        }
+        self.rollbacks += 1;
+    }
+
+    fn run_frame(&mut self) -> Vec<Option<GameEvent>> {
+        let frame = self.frame;
+        if frame >= self.confirmed() {
+            self.snapshots.push_back((frame, self.players.clone()));
+        }
+
+        let mut events = Vec::new();
+        for (player, inputs) in self.players.iter_mut().zip(self.inputs.iter()) {
+            if let Some(actions) = inputs.get(frame as usize) {
+                for action in actions {
+                    player.apply(*action);
+                }
+            }
+            events.push(player.step());
+        }
+
+        let sent: Vec<u8> = self.players.iter_mut()
+            .map(|player| player.versus.as_mut().map_or(0, VersusPlayer::take_outgoing))
+            .collect();
+        for (index, lines) in sent.iter().enumerate() {
+            let opponent = &mut self.players[1 - index];
+            let columns = opponent.board.columns();
+            if let Some(versus) = opponent.versus.as_mut() {
+                versus.receive(*lines, columns);
+            }
+        }
+        self.frame += 1;
+        events
+    }
+
+    /// The local and remote games as of the last frame whose inputs are all known, with that
+    /// frame. Unlike `local` and `remote`, these never change on a rollback.
+    pub fn settled(&self) -> Option<(u32, &GamePlayState, &GamePlayState)> {
+        let confirmed = self.confirmed();
+        let (frame, players) = if confirmed >= self.frame {
+            (self.frame, &self.players)
+        } else {
+            (confirmed, &self.snapshots.iter().find(|(frame, _)| *frame == confirmed)?.1)
+        };
+        Some((frame, &players[self.local], &players[1 - self.local]))
+    }
+
+    /// How the round ended, once every input up to the first top out is known. A player who tops
+    /// out on an earlier frame loses even if the other tops out before that is known.
+    pub fn outcome(&self) -> Option<Outcome> {
+        let (_, local, remote) = self.settled()?;
+        match (local.topped_out, remote.topped_out) {
+            (false, false) => None,
+            (true, false) => Some(Outcome::Lost),
+            (false, true) => Some(Outcome::Won),
+            (true, true) => Some(match local.frames.cmp(&remote.frames) {
+                Ordering::Less => Outcome::Lost,
+                Ordering::Greater => Outcome::Won,
+                Ordering::Equal => Outcome::Draw,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+    use std::thread;
+    use std::time::Duration;
+    use ggez::GameResult;
+    use super::*;
+    use crate::net::{network_error, Link};
+    use crate::net::protocol::Message;
+    use crate::modes::rules::LockDelay;
+    use crate::net::simulator::{Conditions, SimulatedLink};
+
+    const SEED: u32 = 7;
+    const FRAMES: u32 = 1200;
+    /// Players stop pressing this many frames before the end, so the last frames are predicted
+    /// right.
+    const QUIET_FRAMES: u32 = 30;
+
+    /// One end of a link that never leaves the process.
+    struct MemoryLink {
+        sender: Sender<Message>,
+        receiver: Receiver<Message>,
+    }
+
+    impl Link for MemoryLink {
+        fn send(&mut self, message: &Message) -> GameResult {
+            self.sender.send(message.clone()).map_err(|_| network_error(String::from("Closed")))
+        }
+
+        fn poll(&mut self) -> GameResult<Option<Message>> {
+            match self.receiver.try_recv() {
+                Ok(message) => Ok(Some(message)),
+                Err(TryRecvError::Empty) => Ok(None),
+                Err(TryRecvError::Disconnected) => Err(network_error(String::from("Closed"))),
+            }
+        }
+    }
+
+    fn memory_links() -> (MemoryLink, MemoryLink) {
+        let (to_second, from_first) = mpsc::channel();
+        let (to_first, from_second) = mpsc::channel();
+        (
+            MemoryLink { sender: to_second, receiver: from_second },
+            MemoryLink { sender: to_first, receiver: from_first },
+        )
+    }
+
+    /// What `player` presses on `frame`: mostly soft drops with moves and turns between, so
+    /// pieces lock quickly, lines clear and garbage is sent both ways.
+    fn script(player: usize, frame: u32) -> Option<Action> {
+        const MOVES: [Action; 5] = [
+            Action::MoveLeft, Action::RotateClockwise, Action::MoveRight, Action::MoveRight, Action::MoveLeft,
+        ];
+        if frame + QUIET_FRAMES >= FRAMES {
+            None
+        } else if frame % 4 == player as u32 {
+            Some(MOVES[(frame as usize / 4 + player) % MOVES.len()])
+        } else {
+            Some(Action::SoftDrop)
+        }
+    }
+
+    /// Quick locks, for more pieces in a short game.
+    fn rules() -> RuleSet {
+        RuleSet { lock_delay: LockDelay::Step(2), ..RuleSet::standard() }
+    }
+
+    fn sync(game: &Rollback, link: &mut impl Link) {
+        let (first, frames) = game.unacknowledged();
+        link.send(&Message::Inputs { round: 1, ack: game.confirmed(), first, frames }).unwrap();
+    }
+
+    fn receive(game: &mut Rollback, link: &mut impl Link) {
+        while let Some(message) = link.poll().unwrap() {
+            if let Message::Inputs { ack, first, frames, .. } = message {
+                game.receive(first, frames, ack);
+            }
+        }
+    }
+
+    /// Both games run frame by frame from the same inputs, with nothing predicted.
+    fn lockstep(inputs: &[Vec<Vec<Action>>; 2], frames: u32) -> Vec<GamePlayState> {
+        let mut reference = Rollback::new(0, SEED, &rules(), &AttackTable::guideline()).unwrap();
+        reference.inputs = inputs.clone();
+        for _ in 0..frames {
+            reference.run_frame();
+        }
+        reference.players
+    }
+
+    #[test]
+    fn rollback_over_a_bad_network_matches_lockstep() {
+        let conditions = Conditions { delay: Duration::from_millis(15), jitter: Duration::from_millis(5), loss: 0.3 };
+        let (first, second) = memory_links();
+        let mut links = [SimulatedLink::new(first, conditions), SimulatedLink::new(second, conditions)];
+        let mut games = [
+            Rollback::new(0, SEED, &rules(), &AttackTable::guideline()).unwrap(),
+            Rollback::new(1, SEED, &rules(), &AttackTable::guideline()).unwrap(),
+        ];
+
+        // Run to the end, then keep talking until each side has all of the other's inputs.
+        while games.iter().any(|game| game.frame() < FRAMES || game.confirmed() < FRAMES) {
+            for (player, (game, link)) in games.iter_mut().zip(links.iter_mut()).enumerate() {
+                receive(game, link);
+                if game.frame() < FRAMES && !game.waiting() {
+                    if let Some(action) = script(player, game.frame()) {
+                        game.press(action);
+                    }
+                    game.advance();
+                }
+                sync(game, link);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        // One more, quiet, frame on each side rolls back any prediction still wrong.
+        for game in games.iter_mut() {
+            game.advance();
+        }
+
+        assert!(games.iter().any(|game| game.rollbacks > 0));
+        let inputs = [games[0].inputs[0].clone(), games[1].inputs[1].clone()];
+        for game in &games {
+            for (known, sent) in game.inputs.iter().zip(inputs.iter()) {
+                assert_eq!(known[..FRAMES as usize], sent[..FRAMES as usize]);
+            }
+        }
+        let expected = lockstep(&inputs, FRAMES + 1);
+        for game in &games {
+            for (player, expected) in game.players.iter().zip(expected.iter()) {
+                assert_eq!(player.board.codes(), expected.board.codes());
+                assert_eq!(player.score.score, expected.score.score);
+                assert_eq!(player.score.lines, expected.score.lines);
+                assert_eq!(player.topped_out, expected.topped_out);
+            }
+        }
+
+        let after_frames = lockstep(&inputs, FRAMES);
+        let expected_outcome = match (after_frames[0].topped_out, after_frames[1].topped_out) {
+            (false, false) => None,
+            (true, false) => Some(Outcome::Lost),
+            (false, true) => Some(Outcome::Won),
+            (true, true) => Some(match after_frames[0].frames.cmp(&after_frames[1].frames) {
+                Ordering::Less => Outcome::Lost,
+                Ordering::Greater => Outcome::Won,
+                Ordering::Equal => Outcome::Draw,
+            }),
+        };
+        let mirrored = expected_outcome.map(|outcome| match outcome {
+            Outcome::Won => Outcome::Lost,
+            Outcome::Lost => Outcome::Won,
+            Outcome::Draw => Outcome::Draw,
+        });
+        assert_eq!(games[0].outcome(), expected_outcome);
+        assert_eq!(games[1].outcome(), mirrored);
+    }
+}
@@ -0,0 +1,321 @@
+//! One online match over a `Link`: the handshake and rule agreement, readying up, the rounds and
+//! rematches. The host picks versus or co-op, deals the seed for each round and plays the first
+//! player; otherwise both sides behave the same. In versus, each side also reports its settled
+//! game as `board`, `attack` and `game_over` messages for clients that do not run it.
+
+use rand::Rng;
+use ggez::GameResult;
+use crate::controls::Action;
+use crate::game::{GameEvent, GamePlayState};
+use crate::modes::GameMode;
+use crate::modes::rules::RuleSet;
+use crate::modes::versus::AttackTable;
+use super::Link;
+use super::lockstep::Lockstep;
+use super::protocol::{Message, Snapshot, PROTOCOL_VERSION};
+use super::rollback::{Outcome, Rollback};
+
+/// Settled frames between `board` messages.
+const SNAPSHOT_INTERVAL: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Host,
+    Guest,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Phase {
+    /// Saying hello and agreeing on the rules.
+    Handshake,
+    /// Waiting for both players to be ready for the first round.
+    Lobby,
+    Playing,
+    /// Waiting for both players to want a rematch. A co-op round, which both players lose
+    /// together, ends in a draw.
+    RoundOver(Outcome),
+    /// The match is over, for the reason given.
+    Closed(String),
+}
+
+pub struct Session {
+    link: Box<dyn Link>,
+    pub role: Role,
+    pub phase: Phase,
+    pub opponent_name: Option<String>,
+    /// The host's rules and mode; the guest has them once the handshake is done.
+    pub rules: RuleSet,
+    pub mode: GameMode,
+    attack_table: AttackTable,
+    pub ready: bool,
+    pub opponent_ready: bool,
+    /// Counts the rounds started, so inputs left over from the last one are ignored.
+    round: u32,
+    /// The round being played, in versus.
+    pub game: Option<Rollback>,
+    /// The round being played, in co-op.
+    pub coop: Option<Lockstep>,
+    report: Report,
+    /// Rounds won by this side and by the other.
+    pub wins: [u32; 2],
+}
+
+impl Session {
+    /// Hosts a `mode` match, which is `GameMode::Versus` or `GameMode::Coop`.
+    pub fn host(link: Box<dyn Link>, name: &str, rules: RuleSet, mode: GameMode, attack_table: AttackTable) -> Session {
+        Session::new(link, Role::Host, name, rules, mode, attack_table)
+    }
+
+    pub fn guest(link: Box<dyn Link>, name: &str, attack_table: AttackTable) -> Session {
+        Session::new(link, Role::Guest, name, RuleSet::standard(), GameMode::Versus, attack_table)
+    }
+
+    fn new(link: Box<dyn Link>, role: Role, name: &str, rules: RuleSet, mode: GameMode, attack_table: AttackTable) -> Session {
+        let mut session = Session {
+            link,
+            role,
+            phase: Phase::Handshake,
+            opponent_name: None,
+            rules,
+            mode,
+            attack_table,
+            ready: false,
+            opponent_ready: false,
+            round: 0,
+            game: None,
+            coop: None,
+            report: Report::default(),
+            wins: [0, 0],
+        };
+        session.send(Message::Hello { version: PROTOCOL_VERSION, name: String::from(name) });
+        session
+    }
+
+    /// Ready for the first round, or for a rematch once a round is over.
+    pub fn ready_up(&mut self) {
+        let message = match self.phase {
+            Phase::Lobby => Message::Ready,
+            Phase::RoundOver(_) => Message::Rematch,
+            _ => return,
+        };
+        if !self.ready {
+            self.ready = true;
+            self.send(message);
+        }
+    }
+
+    pub fn apply(&mut self, action: Action) {
+        if self.phase != Phase::Playing {
+            return;
+        }
+        if let Some(game) = self.game.as_mut() {
+            game.press(action);
+        }
+        if let Some(coop) = self.coop.as_mut() {
+            coop.press(action);
+        }
+    }
+
+    /// Handles what the other side has sent and, during a round, runs a frame of the games and
+    /// sends the local inputs. Returns what happened to the local game, or to the shared board in
+    /// co-op.
+    pub fn update(&mut self) -> Option<GameEvent> {
+        loop {
+            match self.link.poll() {
+                Ok(Some(message)) => self.receive(message),
+                Ok(None) => break,
+                Err(_) => {
+                    self.close(String::from("The other player disconnected"));
+                    break;
+                }
+            }
+        }
+        if self.role == Role::Host && self.ready && self.opponent_ready {
+            let seed = rand::thread_rng().gen();
+            self.send(Message::Start { seed });
+            self.start_round(seed);
+        }
+        self.step()
+    }
+
+    fn receive(&mut self, message: Message) {
+        match message {
+            Message::Hello { version, name } => {
+                if version != PROTOCOL_VERSION {
+                    let reason = format!("Protocol version {} is not {}", version, PROTOCOL_VERSION);
+                    self.send(Message::Bye { reason: reason.clone() });
+                    self.close(reason);
+                    return;
+                }
+                self.opponent_name = Some(name);
+                if self.role == Role::Host {
+                    let source = self.rules.to_source();
+                    self.send(Message::Rules { source, mode: self.mode });
+                }
+            }
+            Message::Rules { source, mode } => match RuleSet::parse(&source) {
+                Ok(rules) => {
+                    self.rules = rules;
+                    self.mode = mode;
+                    self.send(Message::RulesAccepted);
+                    self.phase = Phase::Lobby;
+                }
+                Err(error) => {
+                    self.send(Message::RulesRejected { reason: error.to_string() });
+                    self.close(format!("Could not read the host's rules: {}", error));
+                }
+            },
+            Message::RulesAccepted => self.phase = Phase::Lobby,
+            Message::RulesRejected { reason } => self.close(format!("The rules were rejected: {}", reason)),
+            Message::Ready | Message::Rematch => self.opponent_ready = true,
+            Message::Start { seed } => self.start_round(seed),
+            Message::Inputs { round, ack, first, frames } => {
+                if round != self.round {
+                    return;
+                }
+                if let Some(game) = self.game.as_mut() {
+                    game.receive(first, frames, ack);
+                } else if let Some(coop) = self.coop.as_mut() {
+                    coop.receive(first, frames, ack);
+                }
+            }
+            // This side runs the other game from its inputs, so it has no use for the reports.
+            Message::Attack { .. } | Message::Board(_) | Message::GameOver => (),
+            Message::Bye { reason } => self.close(format!("The other player left: {}", reason)),
+        }
+    }
+
+    fn start_round(&mut self, seed: u32) {
+        let local = match self.role {
+            Role::Host => 0,
+            Role::Guest => 1,
+        };
+        if self.mode == GameMode::Coop {
+            self.coop = Some(Lockstep::new(local, seed, &self.rules));
+        } else {
+            match Rollback::new(local, seed, &self.rules, &self.attack_table) {
+                Ok(game) => self.game = Some(game),
+                Err(error) => return self.close(error.to_string()),
+            }
+        }
+        self.round += 1;
+        self.report = Report::default();
+        self.ready = false;
+        self.opponent_ready = false;
+        self.phase = Phase::Playing;
+    }
+
+    /// Runs a frame during a round and decides it once the inputs to the end are known. Inputs
+    /// are sent after the round too, until the other side has them all.
+    fn step(&mut self) -> Option<GameEvent> {
+        if self.coop.is_some() {
+            return self.step_coop();
+        }
+        let game = self.game.as_mut()?;
+        let mut event = None;
+        if self.phase == Phase::Playing {
+            event = game.advance();
+            if let Some(outcome) = game.outcome() {
+                self.phase = Phase::RoundOver(outcome);
+                match outcome {
+                    Outcome::Won => self.wins[0] += 1,
+                    Outcome::Lost => self.wins[1] += 1,
+                    Outcome::Draw => (),
+                }
+            }
+        }
+        if let Phase::Closed(_) = self.phase {
+            return event;
+        }
+        let (first, frames) = game.unacknowledged();
+        let mut messages = vec![Message::Inputs { round: self.round, ack: game.confirmed(), first, frames }];
+        if let Some((frame, local, _)) = game.settled() {
+            messages.extend(self.report.update(frame, local));
+        }
+        for message in messages {
+            self.send(message);
+        }
+        event
+    }
+
+    /// Runs a co-op frame once the other player's presses for it are known. The round is over
+    /// when the board tops out, which both sides see on the same frame.
+    fn step_coop(&mut self) -> Option<GameEvent> {
+        let coop = self.coop.as_mut()?;
+        let mut event = None;
+        if self.phase == Phase::Playing {
+            event = coop.advance().pop();
+            if coop.state.game_over {
+                self.phase = Phase::RoundOver(Outcome::Draw);
+            }
+        }
+        if let Phase::Closed(_) = self.phase {
+            return event;
+        }
+        let (first, frames) = coop.unacknowledged();
+        let inputs = Message::Inputs { round: self.round, ack: coop.confirmed(), first, frames };
+        self.send(inputs);
+        event
+    }
+
+    fn send(&mut self, message: Message) {
+        if self.link.send(&message).is_err() {
+            self.close(String::from("The connection was lost"));
+        }
+    }
+
+    fn close(&mut self, reason: String) {
+        if let Phase::Closed(_) = self.phase {
+            return;
+        }
+        self.phase = Phase::Closed(reason);
+    }
+
+    /// Tells the other side this one is leaving.
+    pub fn leave(&mut self) -> GameResult {
+        if let Phase::Closed(_) = self.phase {
+            return Ok(());
+        }
+        self.phase = Phase::Closed(String::from("Left the match"));
+        self.link.send(&Message::Bye { reason: String::from("Left the match") })
+    }
+}
+
+/// What the other side has been told of the settled local game.
+#[derive(Default)]
+struct Report {
+    frame: u32,
+    lines_sent: u16,
+    game_over: bool,
+}
+
+impl Report {
+    /// The messages that bring the other side up to date with the local game as of `frame`: the
+    /// garbage sent since the last report, a board every few frames, and the top out.
+    fn update(&mut self, frame: u32, local: &GamePlayState) -> Vec<Message> {
+        let mut messages = Vec::new();
+        if self.game_over {
+            return messages;
+        }
+        let lines_sent = local.versus.as_ref().map_or(0, |versus| versus.lines_sent);
+        if lines_sent > self.lines_sent {
+            messages.push(Message::Attack { lines: (lines_sent - self.lines_sent).min(255) as u8 });
+            self.lines_sent = lines_sent;
+        }
+        if frame >= self.frame + SNAPSHOT_INTERVAL || local.topped_out {
+            messages.push(Message::Board(Snapshot::of(local)));
+            self.frame = frame;
+        }
+        if local.topped_out {
+            messages.push(Message::GameOver);
+            self.game_over = true;
+        }
+        messages
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
@@ -0,0 +1,94 @@
+//! A bad network on top of a good one, for trying rollback over loopback or a LAN: outgoing
+//! messages are held back by a delay with some jitter, and redundant ones are sometimes lost.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use ggez::GameResult;
+use rand::Rng;
+use super::Link;
+use super::protocol::Message;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conditions {
+    /// One-way delay added to every message.
+    pub delay: Duration,
+    /// Up to this much more delay, picked at random per message.
+    pub jitter: Duration,
+    /// The chance, from 0 to 1, that a redundant message is dropped.
+    pub loss: f32,
+}
+
+impl Conditions {
+    /// A round trip of `lag`, half each way, with `loss` percent of redundant messages dropped.
+    pub fn new(lag: Duration, loss: f32) -> Conditions {
+        Conditions {
+            delay: lag / 2,
+            jitter: lag / 8,
+            loss: (loss / 100.0).clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// Sends through `link` as if over a network with the given conditions. Messages stay in order.
+pub struct SimulatedLink<L: Link> {
+    link: L,
+    conditions: Conditions,
+    queue: VecDeque<(Instant, Message)>,
+    /// Messages dropped so far.
+    pub lost: u32,
+}
+
+impl<L: Link> SimulatedLink<L> {
+    pub fn new(link: L, conditions: Conditions) -> SimulatedLink<L> {
+        SimulatedLink {
+            link,
+            conditions,
+            queue: VecDeque::new(),
+            lost: 0,
+        }
+    }
+
+    fn flush(&mut self) -> GameResult {
+        let now = Instant::now();
+        while let Some((due, _)) = self.queue.front() {
+            if *due > now {
+                break;
+            }
+            if let Some((_, message)) = self.queue.pop_front() {
+                self.link.send(&message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<L: Link> Link for SimulatedLink<L> {
+    fn send(&mut self, message: &Message) -> GameResult {
+        let mut rng = rand::thread_rng();
+        if message.is_redundant() && rng.gen::<f32>() < self.conditions.loss {
+            self.lost += 1;
+            return self.flush();
+        }
+        let jitter = self.conditions.jitter.mul_f32(rng.gen::<f32>());
+        let mut due = Instant::now() + self.conditions.delay + jitter;
+        if let Some((last, _)) = self.queue.back() {
+            due = due.max(*last);
+        }
+        self.queue.push_back((due, message.clone()));
+        self.flush()
+    }
+
+    fn poll(&mut self) -> GameResult<Option<Message>> {
+        self.flush()?;
+        self.link.poll()
+    }
+}
+
+impl<L: Link> Drop for SimulatedLink<L> {
+    /// Sends whatever is still held back, such as a goodbye.
+    fn drop(&mut self) {
+        for (_, message) in self.queue.drain(..) {
+            let _ = self.link.send(&message);
+        }
+    }
+}
@@ -0,0 +1,580 @@
+//! Live spectating. A `Broadcaster` lets spectators connect to a running game and sends them what
+//! happens in it as JSON lines: a snapshot of every player on joining, then events as pieces
+//! spawn, move, hold and lock and as the stack, score and garbage change. A `Spectator` mirrors
+//! the players from that stream. The format is documented in `docs/spectator-protocol.md`.
+
+use std::io::{ErrorKind, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread;
+use ggez::GameResult;
+use crate::game::GamePlayState;
+use crate::tbp::{piece_from_name, piece_name};
+use crate::tbp::json::Json;
+use crate::world::{Board, Cell, Tetrimino, TetriminoType};
+use super::{connect, network_error, read_lines};
+use super::discovery::{Announcement, Announcer, Kind};
+use super::protocol::{number, string};
+
+pub const SPECTATE_PORT: u16 = 7879;
+/// Lines a spectator can fall behind by before it is dropped.
+const SPECTATOR_BACKLOG: usize = 256;
+
+/// A piece and the cells it covers, as (column, row) from the top left of the playfield. Rows
+/// are negative above it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Piece {
+    pub kind: TetriminoType,
+    pub cells: Vec<(i8, i8)>,
+}
+
+impl Piece {
+    fn of(tetrimino: &Tetrimino) -> Piece {
+        Piece {
+            kind: tetrimino.kind,
+            cells: tetrimino.cells().iter().map(|point| (point.x - 1, point.y)).collect(),
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("type", Json::string(piece_name(self.kind))),
+            ("cells", Json::Array(self.cells.iter()
+                .map(|(x, y)| Json::Array(vec![Json::Number(f64::from(*x)), Json::Number(f64::from(*y))]))
+                .collect())),
+        ])
+    }
+
+    fn from_json(json: &Json) -> Result<Piece, String> {
+        let kind = json.get("type").and_then(Json::as_str).and_then(piece_from_name).ok_or("Bad piece type")?;
+        let cells = json.get("cells").and_then(Json::as_array).ok_or("Expected piece cells")?.iter()
+            .map(|cell| match cell.as_array() {
+                Some([x, y]) => match (x.as_i64(), y.as_i64()) {
+                    (Some(x), Some(y)) => Ok((x as i8, y as i8)),
+                    _ => Err(String::from("Bad piece cell")),
+                },
+                _ => Err(String::from("Bad piece cell")),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Piece { kind, cells })
+    }
+}
+
+/// All a spectator sees of one player.
+#[derive(Debug, Clone, PartialEq)]
+pub struct View {
+    pub name: String,
+    pub columns: usize,
+    pub rows: usize,
+    /// The locked stack as `Board::codes` lays it out.
+    pub codes: Vec<u8>,
+    pub piece: Option<Piece>,
+    pub hold: Option<TetriminoType>,
+    pub queue: Vec<TetriminoType>,
+    pub pending: u16,
+    pub score: u32,
+    pub lines: u16,
+    pub level: u16,
+    pub topped_out: bool,
+}
+
+impl View {
+    pub fn of(name: &str, state: &GamePlayState) -> View {
+        View {
+            name: String::from(name),
+            columns: state.board.columns(),
+            rows: state.board.rows(),
+            codes: state.board.codes(),
+            piece: if state.piece_active() { Some(Piece::of(&state.tetrimino)) } else { None },
+            hold: state.hold,
+            queue: state.queue.iter().copied().collect(),
+            pending: state.versus.as_ref().map_or(0, |versus| versus.pending()),
+            score: state.score.score,
+            lines: state.score.lines,
+            level: state.score.level,
+            topped_out: state.topped_out,
+        }
+    }
+
+    /// The stack with the falling piece in it.
+    pub fn board(&self) -> Board {
+        let mut board = Board::from_codes(self.columns, self.rows, &self.codes);
+        if let Some(piece) = &self.piece {
+            for (x, y) in &piece.cells {
+                if *x >= 0 && *y >= 0 && (*x as usize) < self.columns {
+                    if let Some(cell) = board.data.get_mut((*y as usize, *x as usize + 1)) {
+                        *cell = Cell { code: piece.kind.to_code(), locked_at: 0 };
+                    }
+                }
+            }
+        }
+        board
+    }
+
+    fn apply(&mut self, event: Event) {
+        match event {
+            Event::Spawn { piece, queue, .. } => {
+                self.piece = Some(piece);
+                self.queue = queue;
+            }
+            Event::Move { piece, .. } => self.piece = Some(piece),
+            Event::Hold { hold, .. } => self.hold = Some(hold),
+            Event::Lock { .. } => self.piece = None,
+            Event::Stack { codes, .. } => self.codes = codes,
+            Event::Score { score, lines, level, .. } => {
+                self.score = score;
+                self.lines = lines;
+                self.level = level;
+            }
+            Event::Garbage { pending, .. } => self.pending = pending,
+            Event::GameOver { .. } => {
+                self.piece = None;
+                self.topped_out = true;
+            }
+            Event::Snapshot(_) => (),
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        let codes: String = self.codes.iter().map(|code| char::from(b'0' + (*code).min(9))).collect();
+        Json::object(vec![
+            ("name", Json::string(&self.name)),
+            ("columns", Json::Number(self.columns as f64)),
+            ("rows", Json::Number(self.rows as f64)),
+            ("cells", Json::String(codes)),
+            ("piece", self.piece.as_ref().map_or(Json::Null, Piece::to_json)),
+            ("hold", self.hold.map_or(Json::Null, |kind| Json::string(piece_name(kind)))),
+            ("queue", queue_json(&self.queue)),
+            ("pending", Json::Number(f64::from(self.pending))),
+            ("score", Json::Number(f64::from(self.score))),
+            ("lines", Json::Number(f64::from(self.lines))),
+            ("level", Json::Number(f64::from(self.level))),
+            ("topped_out", Json::Bool(self.topped_out)),
+        ])
+    }
+
+    fn from_json(json: &Json) -> Result<View, String> {
+        let columns = number(json, "columns")? as usize;
+        let rows = number(json, "rows")? as usize;
+        let codes = codes(json)?;
+        if codes.len() != columns * rows {
+            return Err(String::from("Bad stack size"));
+        }
+        Ok(View {
+            name: string(json, "name")?.to_string(),
+            columns,
+            rows,
+            codes,
+            piece: match json.get("piece") {
+                Some(Json::Null) | None => None,
+                Some(piece) => Some(Piece::from_json(piece)?),
+            },
+            hold: json.get("hold").and_then(Json::as_str).and_then(piece_from_name),
+            queue: queue(json),
+            pending: number(json, "pending")? as u16,
+            score: number(json, "score")? as u32,
+            lines: number(json, "lines")? as u16,
+            level: number(json, "level")? as u16,
+            topped_out: json.get("topped_out").and_then(Json::as_bool).unwrap_or(false),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Every player, sent first to each spectator and again whenever a new game starts.
+    Snapshot(Vec<View>),
+    Spawn { player: usize, piece: Piece, queue: Vec<TetriminoType> },
+    Move { player: usize, piece: Piece },
+    Hold { player: usize, hold: TetriminoType },
+    /// A piece locked where it is, clearing `lines`.
+    Lock { player: usize, piece: Piece, lines: u8, t_spin: bool, perfect_clear: bool },
+    /// The locked stack changed, after a lock or rising garbage.
+    Stack { player: usize, codes: Vec<u8> },
+    Score { player: usize, score: u32, lines: u16, level: u16 },
+    /// Garbage waiting to rise.
+    Garbage { player: usize, pending: u16 },
+    GameOver { player: usize },
+}
+
+impl Event {
+    fn player(&self) -> Option<usize> {
+        match self {
+            Event::Snapshot(_) => None,
+            Event::Spawn { player, .. }
+            | Event::Move { player, .. }
+            | Event::Hold { player, .. }
+            | Event::Lock { player, .. }
+            | Event::Stack { player, .. }
+            | Event::Score { player, .. }
+            | Event::Garbage { player, .. }
+            | Event::GameOver { player } => Some(*player),
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        let (kind, mut fields) = match self {
+            Event::Snapshot(views) => {
+                return Json::object(vec![
+                    ("type", Json::string("snapshot")),
+                    ("players", Json::Array(views.iter().map(View::to_json).collect())),
+                ]);
+            }
+            Event::Spawn { piece, queue, .. } => ("spawn", vec![("piece", piece.to_json()), ("queue", queue_json(queue))]),
+            Event::Move { piece, .. } => ("move", vec![("piece", piece.to_json())]),
+            Event::Hold { hold, .. } => ("hold", vec![("hold", Json::string(piece_name(*hold)))]),
+            Event::Lock { piece, lines, t_spin, perfect_clear, .. } => ("lock", vec![
+                ("piece", piece.to_json()),
+                ("lines", Json::Number(f64::from(*lines))),
+                ("t_spin", Json::Bool(*t_spin)),
+                ("perfect_clear", Json::Bool(*perfect_clear)),
+            ]),
+            Event::Stack { codes, .. } => {
+                ("stack", vec![("cells", Json::String(codes.iter().map(|code| char::from(b'0' + (*code).min(9))).collect()))])
+            }
+            Event::Score { score, lines, level, .. } => ("score", vec![
+                ("score", Json::Number(f64::from(*score))),
+                ("lines", Json::Number(f64::from(*lines))),
+                ("level", Json::Number(f64::from(*level))),
+            ]),
+            Event::Garbage { pending, .. } => ("garbage", vec![("pending", Json::Number(f64::from(*pending)))]),
+            Event::GameOver { .. } => ("game_over", Vec::new()),
+        };
+        let player = self.player().unwrap_or(0);
+        fields.insert(0, ("player", Json::Number(player as f64)));
+        fields.insert(0, ("type", Json::string(kind)));
+        Json::object(fields)
+    }
+
+    pub fn from_json(json: &Json) -> Result<Event, String> {
+        let kind = string(json, "type")?;
+        if kind == "snapshot" {
+            let players = json.get("players").and_then(Json::as_array).ok_or("Expected players")?;
+            return Ok(Event::Snapshot(players.iter().map(View::from_json).collect::<Result<_, _>>()?));
+        }
+        let player = number(json, "player")? as usize;
+        let piece = || Piece::from_json(json.get("piece").ok_or("Expected a piece")?);
+        Ok(match kind {
+            "spawn" => Event::Spawn { player, piece: piece()?, queue: queue(json) },
+            "move" => Event::Move { player, piece: piece()? },
+            "hold" => Event::Hold {
+                player,
+                hold: json.get("hold").and_then(Json::as_str).and_then(piece_from_name).ok_or("Bad hold piece")?,
+            },
+            "lock" => Event::Lock {
+                player,
+                piece: piece()?,
+                lines: number(json, "lines")? as u8,
+                t_spin: json.get("t_spin").and_then(Json::as_bool).unwrap_or(false),
+                perfect_clear: json.get("perfect_clear").and_then(Json::as_bool).unwrap_or(false),
+            },
+            "stack" => Event::Stack { player, codes: codes(json)? },
+            "score" => Event::Score {
+                player,
+                score: number(json, "score")? as u32,
+                lines: number(json, "lines")? as u16,
+                level: number(json, "level")? as u16,
+            },
+            "garbage" => Event::Garbage { player, pending: number(json, "pending")? as u16 },
+            "game_over" => Event::GameOver { player },
+            _ => return Err(format!("Unknown event type '{}'", kind)),
+        })
+    }
+}
+
+/// What the broadcaster last told spectators about a player.
+struct Seen {
+    view: View,
+    frames: u32,
+    pieces: u32,
+    lock_frame: Option<u32>,
+}
+
+/// Publishes running games to spectators on a TCP port. Each spectator is written to on its own
+/// thread, so a slow one never holds up the game; one that falls too far behind is dropped.
+/// Games are announced on the local network while they are published.
+pub struct Broadcaster {
+    listener: TcpListener,
+    spectators: Vec<(TcpStream, SyncSender<String>)>,
+    seen: Vec<Seen>,
+    announcer: Option<Announcer>,
+}
+
+impl Broadcaster {
+    /// Listens on every interface on `port`, or on a free port when `port` is 0.
+    pub fn listen(port: u16) -> GameResult<Broadcaster> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Broadcaster { listener, spectators: Vec::new(), seen: Vec::new(), announcer: Announcer::new().ok() })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map_or(0, |address| address.port())
+    }
+
+    pub fn spectators(&self) -> usize {
+        self.spectators.len()
+    }
+
+    /// Sends what has changed in the named games since the last call, then lets in new spectators
+    /// with a snapshot. A different set of players, or a game that started over, sends a snapshot
+    /// to everyone.
+    pub fn publish(&mut self, players: &[(&str, &GamePlayState)]) {
+        let views: Vec<View> = players.iter().map(|(name, state)| View::of(name, state)).collect();
+        let restarted = players.len() != self.seen.len()
+            || players.iter().zip(self.seen.iter())
+                .any(|((name, state), seen)| *name != seen.view.name || state.frames < seen.frames);
+
+        let events = if restarted {
+            vec![Event::Snapshot(views.clone())]
+        } else {
+            players.iter().zip(views.iter()).zip(self.seen.iter()).enumerate()
+                .flat_map(|(player, (((_, state), view), seen))| changes(player, state, view, seen))
+                .collect()
+        };
+        for event in &events {
+            self.send(&event.to_json().to_string());
+        }
+
+        self.seen = players.iter().zip(views.iter())
+            .map(|((_, state), view)| Seen {
+                view: view.clone(),
+                frames: state.frames,
+                pieces: state.pieces,
+                lock_frame: state.last_lock.as_ref().map(|lock| lock.frame),
+            })
+            .collect();
+        self.accept(views);
+        self.announce(players);
+    }
+
+    fn announce(&mut self, players: &[(&str, &GamePlayState)]) {
+        let port = self.port();
+        let announcer = match self.announcer.as_mut() {
+            Some(announcer) => announcer,
+            None => return,
+        };
+        let names: Vec<&str> = players.iter().map(|(name, _)| *name).collect();
+        announcer.announce(&Announcement {
+            kind: Kind::Spectate,
+            name: names.join(" VS "),
+            rules: players.first().map_or_else(String::new, |(_, state)| state.config.rules.name.clone()),
+            players: players.len() as u8,
+            capacity: 0,
+            port,
+        });
+    }
+
+    fn send(&mut self, line: &str) {
+        self.spectators.retain(|(stream, sender)| match sender.try_send(String::from(line)) {
+            Ok(()) => true,
+            Err(_) => {
+                let _ = stream.shutdown(Shutdown::Both);
+                false
+            }
+        });
+    }
+
+    fn accept(&mut self, views: Vec<View>) {
+        let snapshot = Event::Snapshot(views).to_json().to_string();
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    eprintln!("Could not let a spectator in: {}", error);
+                    break;
+                }
+            };
+            match spectator_writer(&stream) {
+                Ok(sender) => {
+                    if sender.try_send(snapshot.clone()).is_ok() {
+                        self.spectators.push((stream, sender));
+                    }
+                }
+                Err(error) => eprintln!("Could not let a spectator in: {}", error),
+            }
+        }
+    }
+}
+
+impl Drop for Broadcaster {
+    fn drop(&mut self) {
+        for (stream, _) in &self.spectators {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// The events that take a spectator from what they last saw of a player to the game as it is.
+fn changes(player: usize, state: &GamePlayState, view: &View, seen: &Seen) -> Vec<Event> {
+    let mut events = Vec::new();
+    if let Some(lock) = &state.last_lock {
+        if seen.lock_frame != Some(lock.frame) {
+            events.push(Event::Lock {
+                player,
+                piece: Piece::of(&lock.tetrimino),
+                lines: lock.lines,
+                t_spin: lock.t_spin,
+                perfect_clear: lock.perfect_clear,
+            });
+        }
+    }
+    if view.codes != seen.view.codes {
+        events.push(Event::Stack { player, codes: view.codes.clone() });
+    }
+    if let (Some(hold), true) = (view.hold, view.hold != seen.view.hold) {
+        events.push(Event::Hold { player, hold });
+    }
+    if let Some(piece) = &view.piece {
+        let new_piece = seen.view.piece.as_ref().is_none_or(|seen_piece| seen_piece.kind != piece.kind);
+        if state.pieces != seen.pieces || new_piece || view.queue != seen.view.queue {
+            events.push(Event::Spawn { player, piece: piece.clone(), queue: view.queue.clone() });
+        } else if seen.view.piece.as_ref() != Some(piece) {
+            events.push(Event::Move { player, piece: piece.clone() });
+        }
+    }
+    if (view.score, view.lines, view.level) != (seen.view.score, seen.view.lines, seen.view.level) {
+        events.push(Event::Score { player, score: view.score, lines: view.lines, level: view.level });
+    }
+    if view.pending != seen.view.pending {
+        events.push(Event::Garbage { player, pending: view.pending });
+    }
+    if view.topped_out && !seen.view.topped_out {
+        events.push(Event::GameOver { player });
+    }
+    events
+}
+
+fn spectator_writer(stream: &TcpStream) -> GameResult<SyncSender<String>> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    let mut writer = stream.try_clone()?;
+    let (sender, lines) = mpsc::sync_channel::<String>(SPECTATOR_BACKLOG);
+    thread::spawn(move || {
+        for line in lines {
+            if writeln!(writer, "{}", line).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(sender)
+}
+
+/// Watches a broadcast, keeping a view of every player up to date.
+pub struct Spectator {
+    stream: TcpStream,
+    events: Receiver<Event>,
+    pub views: Vec<View>,
+}
+
+impl Spectator {
+    /// Connects to `address`, which is a host name or IP address with an optional port.
+    pub fn connect(address: &str) -> GameResult<Spectator> {
+        let stream = connect(address, SPECTATE_PORT)?;
+        let events = read_lines(&stream, Event::from_json)?;
+        Ok(Spectator { stream, events, views: Vec::new() })
+    }
+
+    /// Applies the events that have arrived, or fails once the broadcast has ended.
+    pub fn update(&mut self) -> GameResult {
+        loop {
+            match self.events.try_recv() {
+                Ok(Event::Snapshot(views)) => self.views = views,
+                Ok(event) => {
+                    if let Some(view) = event.player().and_then(|player| self.views.get_mut(player)) {
+                        view.apply(event);
+                    }
+                }
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => return Err(network_error(String::from("The broadcast has ended"))),
+            }
+        }
+    }
+}
+
+impl Drop for Spectator {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn queue_json(queue: &[TetriminoType]) -> Json {
+    Json::Array(queue.iter().map(|kind| Json::string(piece_name(*kind))).collect())
+}
+
+fn queue(json: &Json) -> Vec<TetriminoType> {
+    json.get("queue").and_then(Json::as_array).unwrap_or(&[]).iter()
+        .filter_map(|kind| kind.as_str().and_then(piece_from_name))
+        .collect()
+}
+
+/// The digit string of a stack.
+fn codes(json: &Json) -> Result<Vec<u8>, String> {
+    let codes: Vec<u8> = string(json, "cells")?.bytes().map(|digit| digit.wrapping_sub(b'0')).collect();
+    if codes.is_empty() || codes.iter().any(|code| *code > 9) {
+        return Err(String::from("Bad stack cells"));
+    }
+    Ok(codes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use crate::game::GameConfig;
+    use crate::modes::GameMode;
+    use crate::world::BoardType;
+    use std::time::{Duration, Instant};
+
+    const TIMEOUT: Duration = Duration::from_secs(20);
+
+    /// A broadcaster with a spectator that never reads and one that reads everything.
+    fn watched() -> (Broadcaster, TcpStream, TcpStream) {
+        let mut broadcaster = Broadcaster::listen(0).unwrap();
+        let address = ("127.0.0.1", broadcaster.port());
+        let (stalled, reading) = (TcpStream::connect(address).unwrap(), TcpStream::connect(address).unwrap());
+        let started = Instant::now();
+        while broadcaster.spectators() < 2 {
+            assert!(started.elapsed() < TIMEOUT);
+            broadcaster.publish(&[]);
+        }
+        (broadcaster, stalled, reading)
+    }
+
+    #[test]
+    fn drops_a_spectator_that_falls_behind() {
+        let (mut broadcaster, _stalled, mut reading) = watched();
+        thread::spawn(move || {
+            let mut buffer = [0; 1 << 16];
+            while matches!(reading.read(&mut buffer), Ok(read) if read > 0) {}
+        });
+
+        let line = "x".repeat(1 << 16);
+        let started = Instant::now();
+        while broadcaster.spectators() == 2 {
+            assert!(started.elapsed() < TIMEOUT, "the stalled spectator was never dropped");
+            broadcaster.send(&line);
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(broadcaster.spectators(), 1);
+    }
+
+    #[test]
+    fn big_mode_views_round_trip() {
+        let mut config = GameConfig::new(GameMode::Marathon);
+        config.big = true;
+        config.seed = Some(1);
+        let mut state = GamePlayState::new(config, None).unwrap();
+        state.board.data[(9, 1)] = Cell { code: BoardType::GARBAGE_CODE, locked_at: 0 };
+        let view = View::of("P1", &state);
+
+        let json = Json::parse(&view.to_json().to_string()).unwrap();
+        let read = View::from_json(&json).unwrap();
+        assert_eq!(read, view);
+        let board = read.board();
+        assert_eq!((board.columns(), board.rows()), (5, 10));
+        assert_eq!((board.width, board.height), (state.board.width, state.board.height));
+        assert_eq!(board.data[(9, 1)].code, BoardType::GARBAGE_CODE);
+    }
+}
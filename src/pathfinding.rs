@@ -0,0 +1,282 @@
+//! Every resting place a piece can reach from where it is, with the presses that get it there.
+//! The search follows the same moves a player has, under the active rotation system, so it finds
+//! tucks under overhangs and spins into slots as well as plain drops. Soft drops always go all the
+//! way down, so a piece is only moved and rotated where it enters and where it lands.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use crate::constants::TOP_BOUNDARY;
+use crate::controls::Action;
+use crate::modes::rules::{RotationSystem, RuleSet, SpawnOrientation};
+use crate::world::{Board, Tetrimino, TetriminoType};
+
+const MOVES: [Action; 5] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::RotateClockwise,
+    Action::RotateCounterClockwise,
+];
+
+/// The highest the search follows a piece. Kicks can lift a piece above where it enters, but on
+/// an open board they could keep lifting it forever.
+const CEILING: i8 = TOP_BOUNDARY - 2;
+
+/// A resting place for the piece and the board it leaves once it locks there.
+#[derive(Debug, Clone)]
+pub struct Placement {
+    pub tetrimino: Tetrimino,
+    /// The shortest way there: the fewest moves and rotations, then the fewest soft drops, with
+    /// the moves and rotations made as high up as they can be so gravity does not get in the way.
+    pub actions: Vec<Action>,
+    pub t_spin: bool,
+    pub board: Board,
+    pub cleaned_lines: u8,
+}
+
+impl Placement {
+    /// Moves and rotations only, leaving out the soft drops.
+    pub fn presses(&self) -> u32 {
+        self.actions.iter().filter(|action| **action != Action::SoftDrop).count() as u32
+    }
+}
+
+/// The piece of `kind` as it enters `board` under `rules`.
+pub fn spawn(kind: TetriminoType, board: &Board, rules: &RuleSet) -> Tetrimino {
+    let mut tetrimino = Tetrimino::from(&kind);
+    tetrimino.pos.x = board.spawn_column();
+    if rules.spawn == SpawnOrientation::PointUp {
+        tetrimino.point_up();
+    }
+    tetrimino
+}
+
+/// Every distinct resting place a newly spawned piece of `kind` can reach.
+pub fn placements_for(kind: TetriminoType, board: &Board, rules: &RuleSet) -> Vec<Placement> {
+    placements(&spawn(kind, board, rules), board, rules.rotation)
+}
+
+/// Every distinct resting place `start` can reach, cheapest first. Places covering the same cells
+/// count once, except that a T can be listed twice when it can get there both with and without
+/// a T-spin.
+pub fn placements(start: &Tetrimino, board: &Board, rotation: RotationSystem) -> Vec<Placement> {
+    let mut states: Vec<Step> = vec![(start.clone(), None)];
+    let mut costs: HashMap<StateKey, Cost> = HashMap::new();
+    costs.insert(state_key(start), (0, 0, 0));
+    let mut queue = BinaryHeap::new();
+    queue.push(Reverse(((0, 0, 0), 0)));
+    let mut found: Vec<(Vec<(i8, i8)>, bool)> = Vec::new();
+    let mut placements = Vec::new();
+
+    while let Some(Reverse((cost, index))) = queue.pop() {
+        let tetrimino = states[index].0.clone();
+        if costs.get(&state_key(&tetrimino)) != Some(&cost) {
+            continue;
+        }
+
+        if tetrimino.is_grounded(board) {
+            let t_spin = tetrimino.is_t_spin(board);
+            let spot = (footprint(&tetrimino), t_spin);
+            if !found.contains(&spot) {
+                found.push(spot);
+                let mut after = board.clone();
+                let cleaned_lines = after.update(&tetrimino, 0);
+                placements.push(Placement {
+                    tetrimino: tetrimino.clone(),
+                    actions: path(&states, index),
+                    t_spin,
+                    board: after,
+                    cleaned_lines,
+                });
+            }
+        }
+
+        for action in MOVES.iter() {
+            let mut next = tetrimino.clone();
+            let mut times = 1;
+            let moved = match action {
+                Action::MoveLeft => next.move_left(board),
+                Action::MoveRight => next.move_right(board),
+                Action::SoftDrop => {
+                    times = 0;
+                    while next.move_down(board) {
+                        times += 1;
+                    }
+                    times > 0
+                }
+                Action::RotateClockwise => rotation.rotates(next.kind) && next.rotate(true, board, rotation.kicks()),
+                _ => rotation.rotates(next.kind) && next.rotate(false, board, rotation.kicks()),
+            };
+            if !moved || !within_reach(&next, board) {
+                continue;
+            }
+            let (moves, drops, depth) = cost;
+            let cost = if *action == Action::SoftDrop {
+                (moves, drops + times, depth)
+            } else {
+                (moves + 1, drops, depth + (tetrimino.pos.y - CEILING) as u32)
+            };
+            let key = state_key(&next);
+            if costs.get(&key).is_none_or(|known| cost < *known) {
+                costs.insert(key, cost);
+                states.push((next, Some((index, *action, times))));
+                queue.push(Reverse((cost, states.len() - 1)));
+            }
+        }
+    }
+    placements
+}
+
+/// The cells a piece covers, sorted, so rotations that cover the same cells compare equal.
+pub fn footprint(tetrimino: &Tetrimino) -> Vec<(i8, i8)> {
+    let mut cells: Vec<(i8, i8)> = tetrimino.cells().iter().map(|point| (point.x, point.y)).collect();
+    cells.sort();
+    cells
+}
+
+/// Above the playfield there are no walls, so the search keeps pieces between them and below
+/// `CEILING` itself.
+fn within_reach(tetrimino: &Tetrimino, board: &Board) -> bool {
+    let columns = board.columns() as i8;
+    tetrimino.pos.y >= CEILING && tetrimino.cells().iter().all(|point| point.x >= 1 && point.x <= columns)
+}
+
+/// Moves and rotations, soft drops, and how far down the moves and rotations were made.
+type Cost = (u32, u32, u32);
+
+/// Position, orientation and whether the last move was a rotation, which decides T-spins.
+type StateKey = (i8, i8, [(i8, i8); 3], bool);
+
+fn state_key(tetrimino: &Tetrimino) -> StateKey {
+    let mut vectors = [(0, 0); 3];
+    for (vector, key) in tetrimino.vectors.iter().zip(vectors.iter_mut()) {
+        *key = (vector.x, vector.y);
+    }
+    (tetrimino.pos.x, tetrimino.pos.y, vectors, tetrimino.last_move_rotation)
+}
+
+/// Each state with the one it was reached from, the action taken and how many times in a row.
+type Step = (Tetrimino, Option<(usize, Action, u32)>);
+
+fn path(states: &[Step], mut index: usize) -> Vec<Action> {
+    let mut actions = Vec::new();
+    while let Some((parent, action, times)) = states[index].1 {
+        actions.extend((0..times).map(|_| action));
+        index = parent;
+    }
+    actions.reverse();
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::finesse::minimum_presses;
+
+    const KINDS: [(TetriminoType, usize); 7] = [
+        (TetriminoType::T, 34),
+        (TetriminoType::I, 17),
+        (TetriminoType::O, 9),
+        (TetriminoType::S, 17),
+        (TetriminoType::Z, 17),
+        (TetriminoType::L, 34),
+        (TetriminoType::J, 34),
+    ];
+
+    /// A T-spin double slot at the left wall, covered by a block the T has to rotate under.
+    fn t_slot() -> Board {
+        Board::from_rows(&[
+            [0, 0, 1, 1, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 1, 1, 1, 1, 1, 1, 1],
+            [1, 0, 1, 1, 1, 1, 1, 1, 1, 1],
+        ])
+    }
+
+    /// The fewest presses for the place `actions` take a newly spawned piece to on an empty board.
+    fn finesse(kind: TetriminoType, actions: &[Action]) -> Option<u32> {
+        let (board, rules) = (Board::new(), RuleSet::standard());
+        let start = spawn(kind, &board, &rules);
+        let mut placed = start.clone();
+        for action in actions {
+            match action {
+                Action::MoveLeft => assert!(placed.move_left(&board)),
+                Action::MoveRight => assert!(placed.move_right(&board)),
+                Action::RotateClockwise => assert!(placed.rotate(true, &board, rules.rotation.kicks())),
+                _ => assert!(placed.rotate(false, &board, rules.rotation.kicks())),
+            }
+        }
+        while placed.move_down(&board) {}
+        minimum_presses(&start, &placed, &board, rules.rotation)
+    }
+
+    #[test]
+    fn places_every_piece_on_an_empty_board() {
+        for rules in [RuleSet::standard(), RuleSet::guideline()].iter() {
+            for (kind, count) in KINDS.iter() {
+                let placements = placements_for(*kind, &Board::new(), rules);
+                assert_eq!(placements.len(), *count, "{:?}", kind);
+                assert!(placements.iter().all(|placement| placement.tetrimino.is_grounded(&Board::new())));
+            }
+        }
+    }
+
+    #[test]
+    fn spins_a_t_under_an_overhang() {
+        let board = t_slot();
+        for rules in [RuleSet::standard(), RuleSet::guideline()].iter() {
+            let placements = placements_for(TetriminoType::T, &board, rules);
+            let spin = placements.iter()
+                .find(|placement| footprint(&placement.tetrimino) == vec![(1, 18), (2, 18), (2, 19), (3, 18)])
+                .expect("the T fits the slot");
+            assert!(spin.t_spin);
+            assert_eq!(spin.cleaned_lines, 2);
+            assert_eq!(spin.board.codes().iter().filter(|code| **code != 0).count(), 2);
+
+            let first_drop = spin.actions.iter().position(|action| *action == Action::SoftDrop).unwrap();
+            let last = spin.actions.last().unwrap();
+            assert!(*last == Action::RotateClockwise || *last == Action::RotateCounterClockwise);
+            assert!(first_drop < spin.actions.len() - 1, "the rotation happens under the overhang");
+        }
+    }
+
+    #[test]
+    fn tucks_an_o_under_an_overhang() {
+        let board = Board::from_rows(&[
+            [1, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        ]);
+        for rules in [RuleSet::standard(), RuleSet::guideline()].iter() {
+            let placements = placements_for(TetriminoType::O, &board, rules);
+            let tuck = placements.iter()
+                .find(|placement| footprint(&placement.tetrimino) == vec![(1, 18), (1, 19), (2, 18), (2, 19)])
+                .expect("the O slides under the overhang");
+            assert_eq!(tuck.cleaned_lines, 0);
+
+            let last_drop = tuck.actions.iter().rposition(|action| *action == Action::SoftDrop).unwrap();
+            assert_eq!(&tuck.actions[last_drop + 1..], &[Action::MoveLeft, Action::MoveLeft]);
+            assert!(tuck.actions[..last_drop].iter().all(|action| *action != Action::MoveRight));
+
+            let mut placed = spawn(TetriminoType::O, &board, rules);
+            for action in &tuck.actions {
+                match action {
+                    Action::MoveLeft => assert!(placed.move_left(&board)),
+                    Action::SoftDrop => assert!(placed.move_down(&board)),
+                    _ => panic!("an O only moves and drops, not {:?}", action),
+                }
+            }
+            assert_eq!(footprint(&placed), footprint(&tuck.tetrimino));
+        }
+    }
+
+    #[test]
+    fn counts_the_fewest_presses() {
+        assert_eq!(finesse(TetriminoType::O, &[]), Some(0));
+        assert_eq!(finesse(TetriminoType::O, &[Action::MoveLeft; 4]), Some(4));
+        assert_eq!(finesse(TetriminoType::I, &[Action::MoveRight; 3]), Some(3));
+        assert_eq!(finesse(TetriminoType::I, &[Action::RotateClockwise]), Some(1));
+        assert_eq!(finesse(TetriminoType::T, &[Action::RotateClockwise, Action::RotateClockwise]), Some(2));
+        assert_eq!(finesse(TetriminoType::T, &[Action::RotateCounterClockwise; 3]), Some(1));
+        assert_eq!(finesse(TetriminoType::L, &[Action::MoveRight, Action::MoveLeft, Action::MoveLeft]), Some(1));
+    }
+}
@@ -0,0 +1,202 @@
+//! Replays: a game's settings and seed with every input, frame by frame. Playing the inputs back
+//! under the same rules gives the same game, which is how the leaderboard server checks scores.
+
+use std::mem;
+use ggez::{GameResult, GameError};
+use crate::controls::Action;
+use crate::game::{GameConfig, GamePlayState};
+use crate::modes::GameMode;
+use crate::modes::rules::RuleSet;
+use crate::tbp::json::Json;
+
+pub const REPLAY_VERSION: u32 = 1;
+/// The longest replay read, an hour of play.
+pub const MAX_FRAMES: u32 = 60 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    /// The settings the game was played with, always with its seed.
+    pub config: GameConfig,
+    /// The presses applied before each frame, one entry per frame played.
+    pub frames: Vec<Vec<Action>>,
+    /// Presses for the frame being played.
+    pressed: Vec<Action>,
+}
+
+impl Replay {
+    /// An empty recording of `state`, which has not been played yet.
+    pub fn of(state: &GamePlayState) -> Replay {
+        let mut config = state.config.clone();
+        config.seed = Some(state.seed);
+        Replay { config, frames: Vec::new(), pressed: Vec::new() }
+    }
+
+    /// Records a press applied to the game before its next frame.
+    pub fn press(&mut self, action: Action) {
+        self.pressed.push(action);
+    }
+
+    /// Ends the frame about to be played, with the presses since the last one.
+    pub fn step(&mut self) {
+        self.frames.push(mem::take(&mut self.pressed));
+    }
+
+    /// Plays the game again from its inputs, until they run out or the game ends.
+    pub fn play(&self) -> GameResult<GamePlayState> {
+        let mut state = GamePlayState::new(self.config.clone(), None)?;
+        for actions in &self.frames {
+            if state.game_over {
+                break;
+            }
+            for action in actions {
+                state.apply(*action);
+            }
+            state.step();
+        }
+        Ok(state)
+    }
+
+    /// Frames with presses are listed as `[frame, letters]`, with the letters of `Action::letter`;
+    /// every other frame had none.
+    pub fn to_json(&self) -> Json {
+        let inputs = self.frames.iter().enumerate()
+            .filter(|(_, actions)| !actions.is_empty())
+            .map(|(frame, actions)| Json::Array(vec![
+                Json::Number(frame as f64),
+                Json::String(actions.iter().map(Action::letter).collect()),
+            ]))
+            .collect();
+        Json::object(vec![
+            ("version", Json::Number(f64::from(REPLAY_VERSION))),
+            ("mode", Json::String(mode_code(self.config.mode))),
+            ("start_level", Json::Number(f64::from(self.config.start_level))),
+            ("seed", Json::Number(f64::from(self.config.seed.unwrap_or(0)))),
+            ("garbage_rows", Json::Number(f64::from(self.config.garbage_rows))),
+            ("big", Json::Bool(self.config.big)),
+            ("rules", Json::String(self.config.rules.to_source())),
+            ("frames", Json::Number(self.frames.len() as f64)),
+            ("inputs", Json::Array(inputs)),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Result<Replay, String> {
+        let number = |key: &str| json.get(key).and_then(Json::as_i64).ok_or(format!("Expected a number for '{}'", key));
+        let version = number("version")?;
+        if version != i64::from(REPLAY_VERSION) {
+            return Err(format!("Cannot read version {} replays", version));
+        }
+        let mode = json.get("mode").and_then(Json::as_str).ok_or("Expected a mode")?;
+        let mut config = GameConfig::new(mode_from_code(mode).ok_or_else(|| format!("Unknown mode '{}'", mode))?);
+        config.start_level = number("start_level")? as u16;
+        config.seed = Some(number("seed")? as u32);
+        config.garbage_rows = number("garbage_rows")? as u8;
+        config.big = json.get("big").and_then(Json::as_bool).unwrap_or(false);
+        let rules = json.get("rules").and_then(Json::as_str).ok_or("Expected rules")?;
+        config.rules = RuleSet::parse(rules).map_err(|error| error_text(&error))?;
+
+        let length = number("frames")?;
+        if length < 0 || length > i64::from(MAX_FRAMES) {
+            return Err(format!("Replays are at most {} frames long", MAX_FRAMES));
+        }
+        let mut frames = vec![Vec::new(); length as usize];
+        for input in json.get("inputs").and_then(Json::as_array).ok_or("Expected inputs")? {
+            let (frame, letters) = match input.as_array() {
+                Some([frame, letters]) => (frame.as_i64(), letters.as_str()),
+                _ => (None, None),
+            };
+            let (frame, letters) = frame.zip(letters).ok_or("Bad input")?;
+            let actions = frames.get_mut(frame as usize).ok_or_else(|| format!("Input after the last frame at {}", frame))?;
+            for letter in letters.chars() {
+                actions.push(Action::from_letter(letter).ok_or_else(|| format!("Unknown action '{}'", letter))?);
+            }
+        }
+        Ok(Replay { config, frames, pressed: Vec::new() })
+    }
+}
+
+/// The name a mode goes by in replays and leaderboards.
+pub fn mode_code(mode: GameMode) -> String {
+    match mode {
+        GameMode::Fading(frames) => format!("fading-{}", frames),
+        GameMode::Finesse { strict: true } => String::from("finesse-strict"),
+        GameMode::Coop => String::from("coop"),
+        _ => mode.name().to_lowercase(),
+    }
+}
+
+pub fn mode_from_code(code: &str) -> Option<GameMode> {
+    Some(match code {
+        "marathon" => GameMode::Marathon,
+        "sprint" => GameMode::Sprint,
+        "ultra" => GameMode::Ultra,
+        "master" => GameMode::Master,
+        "invisible" => GameMode::Invisible,
+        "puzzle" => GameMode::Puzzle,
+        "versus" => GameMode::Versus,
+        "coop" => GameMode::Coop,
+        "survival" => GameMode::Survival,
+        "finesse" => GameMode::Finesse { strict: false },
+        "finesse-strict" => GameMode::Finesse { strict: true },
+        _ => GameMode::Fading(code.strip_prefix("fading-")?.parse().ok()?),
+    })
+}
+
+/// The message of an error from reading rules, without the prefix `GameError` puts on it.
+fn error_text(error: &GameError) -> String {
+    match error {
+        GameError::ResourceLoadError(message) => message.clone(),
+        error => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ai::{AiController, Difficulty};
+    use crate::controls::Controller;
+
+    /// A game of `mode` dealt from a fixed seed and played by the AI for up to `frames` frames,
+    /// with its recording.
+    pub(crate) fn record(mode: GameMode, frames: u32) -> (GamePlayState, Replay) {
+        let mut config = GameConfig::new(mode);
+        config.seed = Some(2024);
+        let mut state = GamePlayState::new(config, None).unwrap();
+        let mut replay = Replay::of(&state);
+        let mut ai = AiController::with_difficulty(Difficulty::Normal);
+        while state.frames < frames && !state.game_over {
+            if let Some(action) = ai.next_action(&state) {
+                replay.press(action);
+                state.apply(action);
+            }
+            replay.step();
+            state.step();
+        }
+        (state, replay)
+    }
+
+    #[test]
+    fn replays_round_trip_to_the_same_game() {
+        let (state, replay) = record(GameMode::Marathon, 20_000);
+        assert!(state.score.lines > 0);
+
+        let json = Json::parse(&replay.to_json().to_string()).unwrap();
+        let read = Replay::from_json(&json).unwrap();
+        assert_eq!(read, replay);
+
+        let played = read.play().unwrap();
+        assert_eq!(played.score.score, state.score.score);
+        assert_eq!(played.score.lines, state.score.lines);
+        assert_eq!(played.frames, state.frames);
+        assert_eq!(played.board.codes(), state.board.codes());
+    }
+
+    #[test]
+    fn rejects_unreadable_replays() {
+        let (_, replay) = record(GameMode::Marathon, 120);
+        let source = replay.to_json().to_string();
+        let version = source.replacen("\"version\":1", "\"version\":2", 1);
+        assert!(Replay::from_json(&Json::parse(&version).unwrap()).is_err());
+        let too_long = source.replacen("\"frames\":120", &format!("\"frames\":{}", MAX_FRAMES + 1), 1);
+        assert!(Replay::from_json(&Json::parse(&too_long).unwrap()).is_err());
+    }
+}
@@ -1,60 +1,78 @@
-use ggez::{graphics, Context, GameResult, audio};
-use ggez::graphics::{Image, Font};
-use crate::world::{TetriminoType, BoardType};
+use ggez::{Context, GameResult, audio};
+use ggez::audio::SoundSource;
+use crate::skins::{Manifest, Skin, Sprite, BUILT_IN_MUSIC};
+
+pub struct SoundEffects {
+    pub fall: audio::Source,
+    pub game_over: audio::Source,
+    pub line: audio::Source,
+}
+
+impl SoundEffects {
+    pub(crate) fn new(ctx: &mut Context) -> GameResult<Self> {
+        let sound_effects = SoundEffects {
+            fall: audio::Source::new(ctx, "/fall.ogg")?,
+            line: audio::Source::new(ctx, "/line.ogg")?,
+            game_over: audio::Source::new(ctx, "/game_over.ogg")?,
+        };
+        Ok(sound_effects)
+    }
+}
 
 pub struct Assets {
-    i_block_image: graphics::Image,
-    j_block_image: graphics::Image,
-    l_block_image: graphics::Image,
-    o_block_image: graphics::Image,
-    s_block_image: graphics::Image,
-    t_block_image: graphics::Image,
-    z_block_image: graphics::Image,
-    b_block_image: graphics::Image,
-    pub font: Font,
-    pub theme: audio::Source
+    built_in: Skin,
+    pub skin: Skin,
+    /// Every installed skin, the built-in one first.
+    pub skins: Vec<Manifest>,
+    /// The position of `skin` in `skins`.
+    pub skin_index: usize,
+    pub theme: audio::Source,
+    /// Where `theme` was loaded from, so switching to a skin with the same music leaves it playing.
+    theme_path: String,
 }
 
 impl Assets {
     pub(crate) fn new(ctx: &mut Context) -> GameResult<Assets> {
+        let built_in = Skin::built_in(ctx)?;
         Ok(Assets {
-            i_block_image: graphics::Image::new(ctx, "/i_block.png")?,
-            j_block_image: graphics::Image::new(ctx, "/j_block.png")?,
-            l_block_image: graphics::Image::new(ctx, "/l_block.png")?,
-            o_block_image: graphics::Image::new(ctx, "/o_block.png")?,
-            s_block_image: graphics::Image::new(ctx, "/s_block.png")?,
-            t_block_image: graphics::Image::new(ctx, "/t_block.png")?,
-            z_block_image: graphics::Image::new(ctx, "/z_block.png")?,
-            b_block_image: graphics::Image::new(ctx, "/b_block.png")?,
-            font: Font::new(ctx, "/PressStart2P-Regular.ttf")?,
-            theme: audio::Source::new(ctx, "/Tetris_theme.ogg")?,
+            skin: built_in.clone(),
+            built_in,
+            skins: Manifest::load_all(ctx),
+            skin_index: 0,
+            theme: audio::Source::new(ctx, BUILT_IN_MUSIC)?,
+            theme_path: String::from(BUILT_IN_MUSIC),
         })
     }
 
-    pub(crate) fn block_image(&mut self, code: u8) -> Option<&mut Image> {
-        match TetriminoType::from_code(code) {
-            Some(tetrimino_type) => {
-                match tetrimino_type {
-                    TetriminoType::I => Option::from(&mut self.i_block_image),
-                    TetriminoType::J => Option::from(&mut self.j_block_image),
-                    TetriminoType::L => Option::from(&mut self.l_block_image),
-                    TetriminoType::O => Option::from(&mut self.o_block_image),
-                    TetriminoType::S => Option::from(&mut self.s_block_image),
-                    TetriminoType::T => Option::from(&mut self.t_block_image),
-                    TetriminoType::Z => Option::from(&mut self.z_block_image),
-                }
-            }
-            None => {
-                match BoardType::from_code(code) {
-                    None => Option::None,
-                    Some(board_type) => {
-                        match board_type {
-                            BoardType::LIMIT => Option::from(&mut self.b_block_image),
-                            _ => Option::None,
-                        }
-                    }
-                }
+    /// Switches to `skins[index]`. New music carries on from the old: it starts if the old music
+    /// was playing.
+    pub(crate) fn use_skin(&mut self, ctx: &mut Context, index: usize) -> GameResult {
+        let manifest = match self.skins.get(index) {
+            Some(manifest) => manifest,
+            None => return Ok(()),
+        };
+        self.skin = Skin::load(ctx, manifest, &self.built_in);
+        self.skin_index = index;
+
+        let theme_path = manifest.music_path();
+        if theme_path != self.theme_path {
+            let theme = audio::Source::new(ctx, &theme_path).or_else(|error| {
+                eprintln!("Skin '{}' could not load {}, using the built-in music: {}", manifest.name, theme_path, error);
+                audio::Source::new(ctx, BUILT_IN_MUSIC)
+            })?;
+            let playing = self.theme.playing();
+            self.theme.stop();
+            self.theme = theme;
+            self.theme.set_repeat(true);
+            if playing {
+                self.theme.play()?;
             }
+            self.theme_path = theme_path;
         }
+        Ok(())
+    }
+
+    pub(crate) fn block(&self, code: u8) -> Option<&Sprite> {
+        self.skin.block(code)
     }
 }
@@ -0,0 +1,306 @@
+//! The C ABI for the training environment, declared in `include/tetris_env.h`. Environments are
+//! opaque pointers owned by the caller between `tetris_env_new` and `tetris_env_free`; nothing is
+//! shared between them. Bump `TETRIS_ENV_ABI_VERSION` whenever a signature or struct changes.
+
+use std::convert::TryFrom;
+use std::ptr;
+use crate::controls::Action;
+use crate::modes::rules::{RuleSet, MAX_PREVIEWS};
+use crate::world::{Board, TetriminoType};
+use super::{ActionSpace, EnvAction, EnvConfig, Environment, Observation, Rewards, StepInfo};
+
+pub const TETRIS_ENV_ABI_VERSION: u32 = 1;
+pub const TETRIS_ENV_ROWS: usize = Board::ROWS;
+pub const TETRIS_ENV_COLUMNS: usize = Board::COLUMNS;
+
+const INPUTS: [Action; 6] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::RotateClockwise,
+    Action::RotateCounterClockwise,
+    Action::Hold,
+];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TetrisEnvConfig {
+    /// 0 for one press per frame, 1 for one placement per piece.
+    pub action_space: u32,
+    /// 0 Standard, 1 Guideline, 2 Classic NES, 3 Arcade.
+    pub rules: u32,
+    pub line_reward: f32,
+    pub score_reward: f32,
+    pub survival_reward: f32,
+    pub game_over_reward: f32,
+}
+
+/// Block codes as in `Observation`, with 0 meaning no piece for `hold` and the unused `queue` slots.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TetrisObservation {
+    pub board: [u8; TETRIS_ENV_ROWS * TETRIS_ENV_COLUMNS],
+    pub piece: u8,
+    pub piece_cells: [[i8; 2]; 4],
+    pub queue: [u8; MAX_PREVIEWS as usize],
+    pub queue_length: u8,
+    pub hold: u8,
+    pub hold_available: u8,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TetrisStepInfo {
+    pub cleaned_lines: u32,
+    pub lines: u32,
+    pub score: u32,
+    pub level: u32,
+    pub pieces: u32,
+    pub frames: u32,
+}
+
+#[no_mangle]
+pub extern "C" fn tetris_env_abi_version() -> u32 {
+    TETRIS_ENV_ABI_VERSION
+}
+
+/// Returns null when the configuration names an unknown action space or rule set, or cannot start
+/// a game.
+///
+/// # Safety
+///
+/// `config` must be null or point to a valid `TetrisEnvConfig`.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_env_new(config: *const TetrisEnvConfig) -> *mut Environment {
+    let config = match config.as_ref() {
+        Some(config) => config,
+        None => return ptr::null_mut(),
+    };
+    let action_space = match config.action_space {
+        0 => ActionSpace::Input,
+        1 => ActionSpace::Placement,
+        _ => return ptr::null_mut(),
+    };
+    let rules = match RuleSet::presets().into_iter().nth(config.rules as usize) {
+        Some(rules) => rules,
+        None => return ptr::null_mut(),
+    };
+    let mut env_config = EnvConfig::new(action_space);
+    env_config.rules = rules;
+    env_config.rewards = Rewards {
+        lines: config.line_reward,
+        score: config.score_reward,
+        survival: config.survival_reward,
+        game_over: config.game_over_reward,
+    };
+    match Environment::new(env_config) {
+        Ok(env) => Box::into_raw(Box::new(env)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `env` must be null or a pointer from `tetris_env_new` that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_env_free(env: *mut Environment) {
+    if !env.is_null() {
+        drop(Box::from_raw(env));
+    }
+}
+
+/// Returns 0, or -1 when `env` is null or the game cannot start. `observation` may be null.
+///
+/// # Safety
+///
+/// `env` must be null or come from `tetris_env_new`, and `observation` must be null or point to
+/// writable memory for a `TetrisObservation`.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_env_reset(env: *mut Environment, seed: u32, observation: *mut TetrisObservation) -> i32 {
+    let env = match env.as_mut() {
+        Some(env) => env,
+        None => return -1,
+    };
+    let next = match env.reset(seed) {
+        Ok(next) => next,
+        Err(_) => return -1,
+    };
+    if let Some(observation) = observation.as_mut() {
+        *observation = to_c_observation(&next);
+    }
+    0
+}
+
+/// `action` is a press from 0 to 5 (left, right, soft drop, rotate clockwise, rotate counter
+/// clockwise, hold) or -1 for none in the input action space, and a placement index in the
+/// placement action space. Returns 1 when the game is over, 0 when it goes on and -1 when `env`
+/// is null. The output pointers may be null.
+///
+/// # Safety
+///
+/// `env` must come from `tetris_env_new`; the output pointers must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_env_step(
+    env: *mut Environment,
+    action: i32,
+    observation: *mut TetrisObservation,
+    reward: *mut f32,
+    info: *mut TetrisStepInfo,
+) -> i32 {
+    let env = match env.as_mut() {
+        Some(env) => env,
+        None => return -1,
+    };
+    let action = match env.config().action_space {
+        ActionSpace::Input => EnvAction::Input(usize::try_from(action).ok().and_then(|index| INPUTS.get(index)).copied()),
+        ActionSpace::Placement => EnvAction::Placement(usize::try_from(action).unwrap_or(usize::MAX)),
+    };
+    let (next, step_reward, done, step_info) = env.step(action);
+    if let Some(observation) = observation.as_mut() {
+        *observation = to_c_observation(&next);
+    }
+    if let Some(reward) = reward.as_mut() {
+        *reward = step_reward;
+    }
+    if let Some(info) = info.as_mut() {
+        *info = to_c_info(&step_info);
+    }
+    if done { 1 } else { 0 }
+}
+
+/// The number of placements on offer in the placement action space, or 0.
+///
+/// # Safety
+///
+/// `env` must be null or come from `tetris_env_new`.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_env_placement_count(env: *const Environment) -> u32 {
+    env.as_ref().map_or(0, |env| env.placements().len() as u32)
+}
+
+/// Writes the board a placement would leave, in the layout of `TetrisObservation::board`.
+/// Returns 0, or -1 when `env` or `board` is null or the index is out of range.
+///
+/// # Safety
+///
+/// `env` must come from `tetris_env_new`, and `board` must point to writable memory for
+/// `TETRIS_ENV_ROWS * TETRIS_ENV_COLUMNS` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_env_placement_board(env: *const Environment, index: u32, board: *mut u8) -> i32 {
+    let placement = match env.as_ref().and_then(|env| env.placements().get(index as usize)) {
+        Some(placement) => placement,
+        None => return -1,
+    };
+    if board.is_null() {
+        return -1;
+    }
+    let cells = placement.board.codes();
+    let length = cells.len().min(TETRIS_ENV_ROWS * TETRIS_ENV_COLUMNS);
+    ptr::copy_nonoverlapping(cells.as_ptr(), board, length);
+    0
+}
+
+fn to_c_observation(observation: &Observation) -> TetrisObservation {
+    let code = |kind: Option<&TetriminoType>| kind.map_or(0, TetriminoType::to_code);
+    let mut c_observation = TetrisObservation {
+        board: [0; TETRIS_ENV_ROWS * TETRIS_ENV_COLUMNS],
+        piece: observation.piece.to_code(),
+        piece_cells: [[0; 2]; 4],
+        queue: [0; MAX_PREVIEWS as usize],
+        queue_length: observation.queue.len().min(MAX_PREVIEWS as usize) as u8,
+        hold: code(observation.hold.as_ref()),
+        hold_available: observation.hold_available as u8,
+    };
+    let length = observation.board.len().min(c_observation.board.len());
+    c_observation.board[..length].copy_from_slice(&observation.board[..length]);
+    for (cell, (x, y)) in c_observation.piece_cells.iter_mut().zip(observation.piece_cells.iter()) {
+        *cell = [*x, *y];
+    }
+    for (slot, kind) in c_observation.queue.iter_mut().zip(observation.queue.iter()) {
+        *slot = code(Some(kind));
+    }
+    c_observation
+}
+
+fn to_c_info(info: &StepInfo) -> TetrisStepInfo {
+    TetrisStepInfo {
+        cleaned_lines: u32::from(info.cleaned_lines),
+        lines: u32::from(info.lines),
+        score: info.score,
+        level: u32::from(info.level),
+        pieces: info.pieces,
+        frames: info.frames,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(action_space: u32) -> TetrisEnvConfig {
+        TetrisEnvConfig {
+            action_space,
+            rules: 0,
+            line_reward: 1.0,
+            score_reward: 0.0,
+            survival_reward: 0.0,
+            game_over_reward: 0.0,
+        }
+    }
+
+    fn info() -> TetrisStepInfo {
+        TetrisStepInfo { cleaned_lines: 0, lines: 0, score: 0, level: 0, pieces: 0, frames: 0 }
+    }
+
+    #[test]
+    fn null_pointers_are_refused() {
+        unsafe {
+            assert!(tetris_env_new(ptr::null()).is_null());
+            assert_eq!(tetris_env_reset(ptr::null_mut(), 0, ptr::null_mut()), -1);
+            assert_eq!(tetris_env_step(ptr::null_mut(), 0, ptr::null_mut(), ptr::null_mut(), ptr::null_mut()), -1);
+            assert_eq!(tetris_env_placement_count(ptr::null()), 0);
+            assert_eq!(tetris_env_placement_board(ptr::null(), 0, [0; TETRIS_ENV_ROWS * TETRIS_ENV_COLUMNS].as_mut_ptr()), -1);
+            tetris_env_free(ptr::null_mut());
+
+            let env = tetris_env_new(&config(1));
+            assert!(!env.is_null());
+            assert_eq!(tetris_env_reset(env, 0, ptr::null_mut()), 0);
+            assert_eq!(tetris_env_step(env, 0, ptr::null_mut(), ptr::null_mut(), ptr::null_mut()), 0);
+            assert_eq!(tetris_env_placement_board(env, 0, ptr::null_mut()), -1);
+            tetris_env_free(env);
+        }
+    }
+
+    #[test]
+    fn unknown_configurations_are_refused() {
+        let mut unknown_rules = config(0);
+        unknown_rules.rules = 99;
+        unsafe {
+            assert!(tetris_env_new(&config(2)).is_null());
+            assert!(tetris_env_new(&unknown_rules).is_null());
+        }
+    }
+
+    #[test]
+    fn out_of_range_actions_let_the_piece_fall() {
+        unsafe {
+            let env = tetris_env_new(&config(0));
+            let mut step_info = info();
+            for action in [-1, 6, 99, i32::MIN].iter() {
+                assert_eq!(tetris_env_step(env, *action, ptr::null_mut(), ptr::null_mut(), &mut step_info), 0);
+            }
+            assert_eq!(step_info.frames, 4);
+            tetris_env_free(env);
+
+            let env = tetris_env_new(&config(1));
+            let count = tetris_env_placement_count(env);
+            let mut board = [0; TETRIS_ENV_ROWS * TETRIS_ENV_COLUMNS];
+            assert_eq!(tetris_env_placement_board(env, count, board.as_mut_ptr()), -1);
+            assert_eq!(tetris_env_step(env, -1, ptr::null_mut(), ptr::null_mut(), &mut step_info), 0);
+            assert_eq!(step_info.pieces, 1);
+            assert_eq!(tetris_env_step(env, count as i32 + 1, ptr::null_mut(), ptr::null_mut(), &mut step_info), 0);
+            assert_eq!(step_info.pieces, 2);
+            tetris_env_free(env);
+        }
+    }
+}
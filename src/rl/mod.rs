@@ -0,0 +1,322 @@
+//! A headless training environment around `GamePlayState`, in the usual reset and step shape.
+//! Environments share nothing, so any number of them can run side by side, each on its own
+//! thread if need be. `ffi` exposes the same API to other languages through a C ABI.
+
+pub mod ffi;
+
+use ggez::GameResult;
+use crate::pathfinding::{placements, Placement};
+use crate::controls::Action;
+use crate::game::{GameConfig, GamePlayState};
+use crate::modes::GameMode;
+use crate::modes::rules::RuleSet;
+use crate::world::TetriminoType;
+
+/// Frames a placement may take to lock before the environment gives up waiting on it.
+const MAX_LOCK_FRAMES: u32 = 60 * 60;
+
+/// What a single step does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ActionSpace {
+    /// One step is one frame, with at most one press in it.
+    Input,
+    /// One step is one piece, dropped at one of the placements on offer.
+    Placement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnvAction {
+    /// A press, or `None` to let the frame pass. Only for the input action space.
+    Input(Option<Action>),
+    /// An index into `Environment::placements`. Only for the placement action space.
+    Placement(usize),
+}
+
+/// How much each outcome of a step is worth. Set a weight to zero to leave it out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rewards {
+    /// Per line cleared.
+    pub lines: f32,
+    /// Per point scored.
+    pub score: f32,
+    /// Per step the game survives.
+    pub survival: f32,
+    /// Once, when the game ends by topping out.
+    pub game_over: f32,
+}
+
+impl Rewards {
+    pub fn lines() -> Rewards {
+        Rewards { lines: 1.0, score: 0.0, survival: 0.0, game_over: 0.0 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvConfig {
+    pub mode: GameMode,
+    pub rules: RuleSet,
+    pub action_space: ActionSpace,
+    pub rewards: Rewards,
+}
+
+impl EnvConfig {
+    pub fn new(action_space: ActionSpace) -> EnvConfig {
+        EnvConfig {
+            mode: GameMode::Marathon,
+            rules: RuleSet::standard(),
+            action_space,
+            rewards: Rewards::lines(),
+        }
+    }
+}
+
+/// Everything an agent sees. Cells are row by row from the top of the playfield, each a block
+/// code: 0 for empty, 1 to 7 for the pieces and 8 for garbage. Piece cells are (column, row) in
+/// the same grid and can be above it while the piece enters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub rows: usize,
+    pub columns: usize,
+    pub board: Vec<u8>,
+    pub piece: TetriminoType,
+    pub piece_cells: [(i8, i8); 4],
+    pub queue: Vec<TetriminoType>,
+    pub hold: Option<TetriminoType>,
+    pub hold_available: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepInfo {
+    pub cleaned_lines: u8,
+    pub lines: u16,
+    pub score: u32,
+    pub level: u16,
+    pub pieces: u32,
+    pub frames: u32,
+}
+
+pub struct Environment {
+    config: EnvConfig,
+    state: GamePlayState,
+    placements: Vec<Placement>,
+}
+
+impl Environment {
+    /// Fails when the configuration cannot start a game, as for puzzle mode without a puzzle.
+    pub fn new(config: EnvConfig) -> GameResult<Environment> {
+        let state = GamePlayState::new(game_config(&config, 0), None)?;
+        let mut environment = Environment {
+            config,
+            state,
+            placements: Vec::new(),
+        };
+        environment.update_placements();
+        Ok(environment)
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.config
+    }
+
+    /// Starts a new game. The same seed deals the same pieces.
+    pub fn reset(&mut self, seed: u32) -> GameResult<Observation> {
+        self.state = GamePlayState::new(game_config(&self.config, seed), None)?;
+        self.update_placements();
+        Ok(self.observe())
+    }
+
+    /// Plays one step and returns what the agent sees next, the reward, whether the game is over
+    /// and the running totals. Actions from the other action space, and placement indexes out of
+    /// range, let the piece drop where it is.
+    pub fn step(&mut self, action: EnvAction) -> (Observation, f32, bool, StepInfo) {
+        let (lines, score) = (self.state.score.lines, self.state.score.score);
+        let mut cleaned_lines = 0;
+        if !self.state.game_over {
+            match (self.config.action_space, action) {
+                (ActionSpace::Input, EnvAction::Input(input)) => {
+                    if let Some(input) = input {
+                        self.state.apply(input);
+                    }
+                    cleaned_lines = self.advance();
+                }
+                (ActionSpace::Placement, _) => {
+                    let actions = match action {
+                        EnvAction::Placement(index) => self.placements.get(index).map(|placement| placement.actions.clone()),
+                        _ => None,
+                    };
+                    for action in actions.unwrap_or_default() {
+                        self.state.apply(action);
+                    }
+                    while self.state.tetrimino.move_down(&self.state.board) {}
+                    cleaned_lines = self.play_out_piece();
+                }
+                (ActionSpace::Input, _) => cleaned_lines = self.advance(),
+            }
+            self.update_placements();
+        }
+
+        let rewards = &self.config.rewards;
+        let mut reward = rewards.lines * f32::from(self.state.score.lines - lines)
+            + rewards.score * (self.state.score.score.saturating_sub(score)) as f32;
+        if self.state.topped_out {
+            reward += rewards.game_over;
+        } else {
+            reward += rewards.survival;
+        }
+        let info = StepInfo {
+            cleaned_lines,
+            lines: self.state.score.lines,
+            score: self.state.score.score,
+            level: self.state.score.level,
+            pieces: self.state.pieces,
+            frames: self.state.frames,
+        };
+        (self.observe(), reward, self.state.game_over, info)
+    }
+
+    /// The placements the active piece can reach, in the order `EnvAction::Placement` indexes
+    /// them. Each comes with the board it would leave.
+    pub fn placements(&self) -> &[Placement] {
+        &self.placements
+    }
+
+    pub fn observe(&self) -> Observation {
+        let board = &self.state.board;
+        let (rows, columns) = (board.rows(), board.columns());
+        let mut piece_cells = [(0, 0); 4];
+        for (cell, point) in piece_cells.iter_mut().zip(self.state.tetrimino.cells().iter()) {
+            *cell = (point.x - 1, point.y);
+        }
+        Observation {
+            rows,
+            columns,
+            board: board.codes(),
+            piece: self.state.tetrimino.kind,
+            piece_cells,
+            queue: self.state.queue.iter().copied().collect(),
+            hold: self.state.hold,
+            hold_available: self.state.hold_enabled && !self.state.hold_used,
+        }
+    }
+
+    /// Runs one frame, returning the lines it cleared.
+    fn advance(&mut self) -> u8 {
+        let lines = self.state.score.lines;
+        self.state.step();
+        (self.state.score.lines - lines) as u8
+    }
+
+    /// Runs frames until the active piece locks and the next one is in play.
+    fn play_out_piece(&mut self) -> u8 {
+        let (lines, pieces) = (self.state.score.lines, self.state.pieces);
+        for _ in 0..MAX_LOCK_FRAMES {
+            if self.state.game_over || (self.state.pieces != pieces && self.state.piece_active()) {
+                break;
+            }
+            self.state.step();
+        }
+        (self.state.score.lines - lines) as u8
+    }
+
+    fn update_placements(&mut self) {
+        self.placements = match self.config.action_space {
+            ActionSpace::Placement if self.state.piece_active() => {
+                placements(&self.state.tetrimino, &self.state.board, self.config.rules.rotation)
+            }
+            _ => Vec::new(),
+        };
+    }
+}
+
+fn game_config(config: &EnvConfig, seed: u32) -> GameConfig {
+    let mut game_config = GameConfig::new(config.mode);
+    game_config.rules = config.rules.clone();
+    game_config.seed = Some(seed);
+    game_config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Cell;
+
+    const SEED: u32 = 11;
+
+    fn environment(action_space: ActionSpace) -> Environment {
+        let mut environment = Environment::new(EnvConfig::new(action_space)).unwrap();
+        environment.reset(SEED).unwrap();
+        environment
+    }
+
+    /// A press every few frames, for an input game that moves, turns and locks pieces.
+    fn input(step: usize) -> EnvAction {
+        const INPUTS: [Option<Action>; 4] = [Some(Action::MoveLeft), None, Some(Action::RotateClockwise), Some(Action::SoftDrop)];
+        EnvAction::Input(INPUTS[step % INPUTS.len()])
+    }
+
+    #[test]
+    fn reset_and_step_are_deterministic() {
+        for action_space in [ActionSpace::Input, ActionSpace::Placement].iter() {
+            let mut environments = [environment(*action_space), environment(*action_space)];
+            assert_eq!(environments[0].observe(), environments[1].observe());
+            for step in 0..300 {
+                let action = match action_space {
+                    ActionSpace::Input => input(step),
+                    ActionSpace::Placement => EnvAction::Placement(step * 7 % 5),
+                };
+                let first = environments[0].step(action);
+                let second = environments[1].step(action);
+                assert_eq!(first, second);
+                if first.2 {
+                    break;
+                }
+            }
+            assert_eq!(environments[0].reset(SEED).unwrap(), environment(*action_space).observe());
+        }
+    }
+
+    #[test]
+    fn placements_leave_the_board_their_inputs_do() {
+        let count = environment(ActionSpace::Placement).placements().len();
+        assert!(count > 0);
+        for index in 0..count {
+            let mut placed = environment(ActionSpace::Placement);
+            let placement = placed.placements()[index].clone();
+            if placement.actions.contains(&Action::SoftDrop) {
+                continue;
+            }
+            let (observation, ..) = placed.step(EnvAction::Placement(index));
+            assert_eq!(observation.board, placement.board.codes());
+
+            let mut pressed = environment(ActionSpace::Input);
+            for action in &placement.actions {
+                pressed.step(EnvAction::Input(Some(*action)));
+            }
+            let mut observation = pressed.observe();
+            while pressed.state.pieces == 0 || !pressed.state.piece_active() {
+                observation = pressed.step(EnvAction::Input(Some(Action::SoftDrop))).0;
+                assert!(pressed.state.frames < MAX_LOCK_FRAMES);
+            }
+            assert_eq!(observation.board, placement.board.codes(), "placement {}", index);
+        }
+    }
+
+    #[test]
+    fn clearing_a_line_is_rewarded() {
+        let mut environment = environment(ActionSpace::Placement);
+        let bottom = environment.state.board.rows() - 1;
+        let columns = environment.state.board.columns();
+        for column in 5..=columns {
+            environment.state.board.data[(bottom, column)] = Cell { code: 8, locked_at: 0 };
+        }
+        environment.state.tetrimino = environment.state.new_piece(&TetriminoType::I);
+        environment.update_placements();
+
+        let index = environment.placements().iter().position(|placement| placement.cleaned_lines == 1).unwrap();
+        let (observation, reward, done, info) = environment.step(EnvAction::Placement(index));
+        assert_eq!(reward, 1.0);
+        assert!(!done);
+        assert_eq!((info.cleaned_lines, info.lines), (1, 1));
+        assert!(observation.board.iter().all(|code| *code == 0));
+    }
+}
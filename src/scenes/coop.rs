@@ -0,0 +1,133 @@
+use ggez::{GameResult, Context, graphics, timer};
+use ggez_goodies::scene::{Scene, SceneSwitch};
+use ggez::event::KeyCode;
+use ggez::graphics::{BLACK, Text, Scale};
+use ggez::audio::SoundSource;
+use crate::SharedState;
+use crate::constants::BOARD_WIDTH;
+use crate::world::Tetrimino;
+use crate::drawing::{draw_tetrimino, draw_ghost, draw_background, draw_board, draw_centered};
+use crate::layout::{self, PREVIEW_Y_OFFSET};
+use crate::game::GameEvent;
+use crate::controls::KeyMap;
+use crate::modes::StackVisibility;
+use crate::modes::coop::{CoopState, COOP_COLUMNS};
+use crate::resources::{Assets, SoundEffects};
+
+pub struct CoopScene {
+    state: CoopState,
+    key_maps: [KeyMap; 2],
+    sound_effects: SoundEffects,
+    quit: bool,
+}
+
+impl CoopScene {
+    pub fn new(ctx: &mut Context) -> GameResult<Box<CoopScene>> {
+        let coop_scene = CoopScene {
+            state: CoopState::new(),
+            key_maps: [KeyMap::left_player(), KeyMap::right_player()],
+            sound_effects: SoundEffects::new(ctx)?,
+            quit: false,
+        };
+        Ok(Box::new(coop_scene))
+    }
+}
+
+impl Scene<SharedState, KeyCode> for CoopScene {
+    fn update(&mut self, _shared_state: &mut SharedState, ctx: &mut Context) -> SceneSwitch<SharedState, KeyCode> {
+        if self.quit {
+            return SceneSwitch::Pop;
+        }
+
+        const DESIRED_FPS: u32 = 60;
+        while timer::check_update_time(ctx, DESIRED_FPS) {
+            for event in self.state.step() {
+                match event {
+                    GameEvent::ToppedOut => {
+                        self.sound_effects.game_over.play().unwrap();
+                    }
+                    GameEvent::Locked(cleaned_lines) => {
+                        self.sound_effects.fall.play().unwrap();
+                        if cleaned_lines > 0 {
+                            self.sound_effects.line.play().unwrap();
+                        }
+                    }
+                }
+            }
+        }
+        SceneSwitch::None
+    }
+
+    fn draw(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx, BLACK);
+        draw_background(ctx, &shared_state.assets)?;
+        draw_coop(&mut shared_state.assets, ctx, &self.state)?;
+
+        if self.state.game_over {
+            draw_centered(ctx, shared_state.assets.skin.font, "GAME OVER", 20.0, layout::centered_line(9.0), graphics::WHITE)?;
+            draw_centered(ctx, shared_state.assets.skin.font, "SPACE TO PLAY AGAIN", 8.0, layout::centered_line(10.5), graphics::WHITE)?;
+        }
+
+        graphics::present(ctx)
+    }
+
+    fn input(&mut self, _shared_state: &mut SharedState, event: KeyCode, _started: bool) {
+        match event {
+            KeyCode::Escape => {
+                self.quit = true;
+            }
+            KeyCode::Space if self.state.game_over => {
+                self.state = CoopState::new();
+            }
+            _ => {
+                for (index, key_map) in self.key_maps.iter().enumerate() {
+                    if let Some(action) = key_map.action(event) {
+                        self.state.apply(index, action);
+                    }
+                }
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "CoopScene"
+    }
+
+    fn draw_previous(&self) -> bool {
+        false
+    }
+}
+
+/// Shifts the wide board left so it sits in the middle of the screen.
+fn board_offset() -> f32 {
+    -(BOARD_WIDTH / 12.0) * (COOP_COLUMNS - 10) as f32 / 2.0
+}
+
+/// The shared board, both falling pieces with their next pieces above them, and the score.
+pub fn draw_coop(assets: &mut Assets, ctx: &mut Context, state: &CoopState) -> GameResult {
+    let board_dimensions = (state.board.width, state.board.height);
+    let x_offset = board_offset();
+    for player in state.players.iter() {
+        if !state.game_over {
+            draw_ghost(assets, ctx, &player.tetrimino, &state.board, board_dimensions, Option::from((x_offset, 0.0)))?;
+            draw_tetrimino(assets, ctx, &player.tetrimino, board_dimensions, Option::from((x_offset, 0.0)))?;
+        }
+        let next = Tetrimino::from(&player.next_tetrimino);
+        let preview_offset = x_offset + f32::from(player.spawn_column - next.pos.x) * BOARD_WIDTH / 12.0;
+        draw_tetrimino(assets, ctx, &next, board_dimensions, Option::from((preview_offset, PREVIEW_Y_OFFSET)))?;
+    }
+    draw_board(assets, ctx, &state.board, board_dimensions, Option::from((x_offset, 0.0)), StackVisibility::Visible, state.frames)?;
+
+    let score_board = &state.score;
+    let lines = [
+        format!("LINES: {}", score_board.lines),
+        format!("SCORE: {}", score_board.score),
+        format!("LEVEL: {}", score_board.level),
+    ];
+    for (index, line) in lines.iter().enumerate() {
+        let mut text = Text::new(line.as_str());
+        text.set_font(assets.skin.font, Scale::uniform(8.0));
+        graphics::draw(ctx, &text, (layout::left_panel(2.0 + index as f32), graphics::WHITE))?;
+    }
+    Ok(())
+}
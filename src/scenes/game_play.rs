@@ -1,180 +1,320 @@
-use ggez::{GameResult, Context, graphics, timer, audio};
+use std::env;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use ggez::{GameResult, Context, graphics, timer};
 use ggez_goodies::scene::{Scene, SceneSwitch};
 use ggez::event::KeyCode;
-use rand::Rng;
-use num_traits::ToPrimitive;
 use crate::SharedState;
-use crate::constants::{BOARD_WIDTH, BOARD_HEIGHT, FALL_TIME, TOP_BOUNDARY};
-use crate::world::{Board, TetriminoType, Tetrimino, ScoreBoard};
-use crate::drawing::{draw_tetrimino, draw_board, draw_score_board};
+use crate::constants::{BOARD_WIDTH, BOARD_HEIGHT, SPRINT_LINES, ULTRA_FRAMES};
+use crate::drawing::{draw_tetrimino, draw_ghost, draw_background, draw_board, draw_line_clear, draw_score_board, draw_master_hud, draw_puzzle_hud, draw_hold, draw_survival_hud, draw_finesse_hud, draw_clock_hud, draw_previews, draw_hint, draw_assist_hud, draw_centered};
+use crate::game::{GamePlayState, GameEvent, GameConfig};
+use crate::controls::{KeyMap, Controller};
+use crate::ai::{AiController, Difficulty, Move, Weights, best_move};
+use crate::tbp::TbpController;
+use crate::world::Board;
+use crate::modes::GameMode;
+use crate::modes::puzzle::PuzzleOutcome;
+use crate::leaderboard::{self, Submission};
+use crate::replay::Replay;
+use crate::tbp::json::Json;
+use crate::resources::SoundEffects;
 use ggez::graphics::{BLACK, Text, Scale};
 use ggez::audio::SoundSource;
-use crate::types::ScreenPoint2;
+use crate::layout;
 use ggez::timer::TimeContext;
 
-
-struct GamePlayState {
-    board: Board,
-    fall_timeout: f32,
-    game_over: bool,
-    start_again:bool,
-    next_tetrimino: TetriminoType,
-    score: ScoreBoard,
-    tetrimino: Tetrimino,
-}
-
-impl GamePlayState {
-    fn new() -> GameResult<GamePlayState> {
-        let board = Board::new();
-        let tetrimino = Tetrimino::from(&GamePlayScene::get_random_tetrimino_type().unwrap());
-        let next_tetrimino = GamePlayScene::get_random_tetrimino_type().unwrap();
-        let score = ScoreBoard::new();
-
-        let game_play_state = GamePlayState {
-            board,
-            fall_timeout: FALL_TIME,
-            game_over: false,
-            next_tetrimino,
-            score,
-            start_again: false,
-            tetrimino,
-        };
-
-        Ok(game_play_state)
-    }
-
-    fn reset(&mut self, ctx: &mut Context) {
-        self.board = Board::new();
-        self.game_over = false;
-        self.score = ScoreBoard::new();
-        self.next_tetrimino = GamePlayScene::get_random_tetrimino_type().unwrap();
-        self.tetrimino = Tetrimino::from(&GamePlayScene::get_random_tetrimino_type().unwrap());
-
-        ctx.timer_context = TimeContext::new();
-    }
-}
-
-struct SoundEffects {
-    fall: audio::Source,
-    game_over: audio::Source,
-    line: audio::Source,
-}
-
-impl SoundEffects {
-    fn new(ctx: &mut Context) -> GameResult<Self> {
-        let sound_effects = SoundEffects {
-            fall: audio::Source::new(ctx, "/fall.ogg")?,
-            line: audio::Source::new(ctx, "/line.ogg")?,
-            game_over: audio::Source::new(ctx, "/game_over.ogg")?,
-        };
-        Ok(sound_effects)
-    }
-}
-
 pub struct GamePlayScene {
     state: GamePlayState,
-    sound_effects: SoundEffects
+    key_map: KeyMap,
+    sound_effects: SoundEffects,
+    ai: Option<Box<dyn Controller>>,
+    show_hint: bool,
+    hint: Option<Move>,
+    hinted_piece: Option<u32>,
+    /// Every input of the game so far, sent with the score to the leaderboard server.
+    replay: Replay,
+    submitted: bool,
+    /// The server's answer to the game just sent, while waiting for it.
+    submission: Option<Receiver<String>>,
+    /// How sending the last game to the leaderboard went.
+    leaderboard_status: Option<String>,
+    demo: bool,
+    quit: bool,
 }
 
 impl GamePlayScene {
-    pub fn new(ctx: &mut Context) -> GameResult<Box<GamePlayScene>> {
+    pub fn new(ctx: &mut Context, shared_state: &SharedState, config: GameConfig) -> GameResult<Box<GamePlayScene>> {
+        let state = GamePlayState::new(config, shared_state.current_puzzle())?;
         let game_play_scene = GamePlayScene {
-            state: GamePlayState::new()?,
-            sound_effects: SoundEffects::new(ctx)?
+            replay: Replay::of(&state),
+            submitted: false,
+            submission: None,
+            leaderboard_status: None,
+            state,
+            key_map: KeyMap::single_player(),
+            sound_effects: SoundEffects::new(ctx)?,
+            ai: None,
+            show_hint: false,
+            hint: None,
+            hinted_piece: None,
+            demo: false,
+            quit: false,
         };
         Ok(Box::new(game_play_scene))
     }
+
+    /// The attract mode: a marathon game played by the AI, at a pace people can follow, that any
+    /// key leaves.
+    pub fn demo(ctx: &mut Context, shared_state: &SharedState) -> GameResult<Box<GamePlayScene>> {
+        let mut game_play_scene = GamePlayScene::new(ctx, shared_state, GameConfig::new(GameMode::Marathon))?;
+        game_play_scene.ai = Some(Box::new(AiController::with_difficulty(Difficulty::Hard)));
+        game_play_scene.demo = true;
+        Ok(game_play_scene)
+    }
+
+    /// Sends the finished game to the leaderboard server given by `--leaderboard`, if any, on
+    /// another thread. Puzzles and assisted games are never sent.
+    fn submit(&mut self, shared_state: &SharedState) {
+        let url = match &shared_state.leaderboard_url {
+            Some(url) if !self.demo && self.state.puzzle.is_none() => url.clone(),
+            _ => return,
+        };
+        if self.state.assisted {
+            self.leaderboard_status = Some(String::from("ASSISTED GAMES ARE NOT RANKED"));
+            return;
+        }
+        if let Err(reason) = leaderboard::eligible(&self.state.config) {
+            self.leaderboard_status = Some(reason.to_uppercase());
+            return;
+        }
+        let name: String = env::var("USER").unwrap_or_else(|_| String::from("PLAYER")).to_uppercase().chars().take(16).collect();
+        let submission = Submission::of(&name, &self.state, self.replay.clone());
+        let (sender, answer) = mpsc::channel();
+        thread::spawn(move || {
+            let status = match leaderboard::submit(&url, &submission) {
+                Ok(entry) => {
+                    let rank = |key| entry.get(key).and_then(Json::as_i64).unwrap_or(0);
+                    format!("RANK {} OF {} ON THE LEADERBOARD", rank("rank"), rank("of"))
+                }
+                Err(error) => format!("NOT RANKED: {}", error.to_uppercase()),
+            };
+            let _ = sender.send(status);
+        });
+        self.leaderboard_status = Some(String::from("SENDING THE SCORE..."));
+        self.submission = Some(answer);
+    }
 }
 
 impl Scene<SharedState, KeyCode> for GamePlayScene {
-    fn update(&mut self, _shared_state: &mut SharedState, ctx: &mut Context) -> SceneSwitch<SharedState, KeyCode> {
+    fn update(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> SceneSwitch<SharedState, KeyCode> {
+        if self.quit {
+            return SceneSwitch::Pop;
+        }
+
         let scene_state = &mut self.state;
 
+        if self.demo && scene_state.game_over {
+            scene_state.start_again = true;
+        }
         if scene_state.start_again && scene_state.game_over {
-            scene_state.reset(ctx) ;
-            scene_state.start_again = false;
-            scene_state.game_over = false;
+            *scene_state = GamePlayState::new(scene_state.config.clone(), shared_state.current_puzzle()).unwrap();
+            self.replay = Replay::of(scene_state);
+            self.submitted = false;
+            self.leaderboard_status = None;
+            self.hinted_piece = None;
+            ctx.timer_context = TimeContext::new();
         }
 
         const DESIRED_FPS: u32 = 60;
         while timer::check_update_time(ctx, DESIRED_FPS) {
-            let seconds = 1.0 / (DESIRED_FPS as f32);
-
-            scene_state.fall_timeout -= seconds;
-            if scene_state.fall_timeout < 0.0 {
-                let can_move = scene_state.tetrimino.move_down(&scene_state.board);
-                if !can_move && scene_state.tetrimino.pos.y == TOP_BOUNDARY {
-                    if !self.state.game_over {
-                        self.state.game_over = true;
-                        self.sound_effects.game_over.play().unwrap();
-                    }
-                    return SceneSwitch::None;
-                } else if !can_move {
+            if let Some(action) = self.ai.as_mut().and_then(|ai| ai.next_action(scene_state)) {
+                scene_state.apply(action);
+                self.replay.press(action);
+            }
+            if !scene_state.game_over {
+                self.replay.step();
+            }
+            match scene_state.step() {
+                Some(GameEvent::ToppedOut) => {
+                    self.sound_effects.game_over.play().unwrap();
+                }
+                Some(GameEvent::Locked(cleaned_lines)) => {
                     self.sound_effects.fall.play().unwrap();
-                    if &scene_state.board.update(&scene_state.tetrimino, &mut scene_state.score) > &0 {
+                    if cleaned_lines > 0 {
                         self.sound_effects.line.play().unwrap();
-                    };
-                    scene_state.tetrimino = Tetrimino::from(&scene_state.next_tetrimino);
-                    scene_state.next_tetrimino = GamePlayScene::get_random_tetrimino_type().unwrap();
+                    }
                 }
-                scene_state.fall_timeout = FALL_TIME / (scene_state.score.level + 1).to_f32().unwrap();
+                None => (),
             }
         }
+        if let Some(broadcaster) = shared_state.broadcaster.as_mut() {
+            broadcaster.publish(&[("PLAYER", scene_state)]);
+        }
+
+        if self.show_hint && scene_state.piece_active() && self.hinted_piece != Some(scene_state.pieces) {
+            self.hint = best_move(scene_state, &Weights::new());
+            self.hinted_piece = Some(scene_state.pieces);
+            scene_state.assisted = true;
+        }
+
+        if let Some(PuzzleOutcome::Solved) = scene_state.puzzle.as_ref().and_then(|puzzle| puzzle.outcome) {
+            shared_state.complete_current_puzzle(ctx);
+        }
+
+        if self.state.game_over && !self.submitted {
+            self.submitted = true;
+            self.submit(shared_state);
+        }
+        match self.submission.as_ref().map(Receiver::try_recv) {
+            Some(Ok(status)) => {
+                self.leaderboard_status = Some(status);
+                self.submission = None;
+            }
+            Some(Err(TryRecvError::Disconnected)) => {
+                self.leaderboard_status = Some(String::from("COULD NOT SEND THE SCORE"));
+                self.submission = None;
+            }
+            Some(Err(TryRecvError::Empty)) | None => (),
+        }
         SceneSwitch::None
     }
 
     fn draw(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> GameResult<()> {
         let scene_state = &mut self.state;
         graphics::clear(ctx, BLACK);
+        draw_background(ctx, &shared_state.assets)?;
 
         {
             let assets = &mut shared_state.assets;
             let board_dimensions = (scene_state.board.width, scene_state.board.height);
+            let preview_dimensions = (BOARD_WIDTH, BOARD_HEIGHT);
 
-            draw_tetrimino(assets, ctx, &scene_state.tetrimino, board_dimensions, None)?;
-            draw_tetrimino(assets, ctx, &Tetrimino::from(&scene_state.next_tetrimino), board_dimensions, Option::from((BOARD_WIDTH, BOARD_HEIGHT / 2.0)))?;
-            draw_board(assets, ctx, &scene_state.board, board_dimensions)?;
+            let stack_visibility = scene_state.stack_visibility();
+
+            if scene_state.piece_active() {
+                draw_ghost(assets, ctx, &scene_state.tetrimino, &scene_state.board, board_dimensions, None)?;
+                draw_tetrimino(assets, ctx, &scene_state.tetrimino, board_dimensions, None)?;
+            }
+            draw_previews(assets, ctx, &scene_state.queue)?;
+            if scene_state.hold_enabled {
+                draw_hold(assets, ctx, scene_state.hold.as_ref(), preview_dimensions)?;
+            }
+            draw_board(assets, ctx, &scene_state.board, board_dimensions, None, stack_visibility, scene_state.frames)?;
+            if let (Some(progress), Some(lock)) = (scene_state.clear_progress(), &scene_state.last_lock) {
+                draw_line_clear(assets, ctx, &scene_state.board, lock, progress, board_dimensions, None)?;
+            }
+            let show_hint = self.show_hint && scene_state.piece_active();
+            let hint = self.hint.as_ref().filter(|_| show_hint);
+            if let Some(hint) = hint {
+                draw_hint(ctx, &hint.placement.tetrimino, board_dimensions)?;
+            }
             draw_score_board(ctx, &scene_state.score, shared_state)?;
+            if let Some(master) = &scene_state.master {
+                draw_master_hud(ctx, master, &scene_state.score, shared_state)?;
+            }
+            match scene_state.config.mode {
+                GameMode::Sprint => {
+                    let lines_left = SPRINT_LINES.saturating_sub(scene_state.score.lines);
+                    draw_clock_hud(ctx, scene_state.frames, &format!("LEFT: {}", lines_left), shared_state)?;
+                }
+                GameMode::Ultra => {
+                    draw_clock_hud(ctx, ULTRA_FRAMES.saturating_sub(scene_state.frames), "LEFT", shared_state)?;
+                }
+                _ => (),
+            }
+            if let Some(survival) = &scene_state.survival {
+                draw_survival_hud(ctx, survival, shared_state)?;
+            }
+            if let Some(finesse) = &scene_state.finesse {
+                draw_finesse_hud(ctx, finesse, shared_state)?;
+            }
+            if let (Some(puzzle_run), Some(puzzle)) = (&scene_state.puzzle, shared_state.current_puzzle()) {
+                draw_puzzle_hud(ctx, puzzle_run, puzzle, shared_state)?;
+            }
+
+            if scene_state.assisted && !self.demo {
+                draw_assist_hud(ctx, hint.is_some_and(|hint| hint.hold), shared_state)?;
+            }
+
+            if self.demo || self.ai.is_some() {
+                let label = if self.demo { "DEMO - PRESS ANY KEY" } else { "AI PLAYING - TAB TO TAKE OVER" };
+                let mut ai_text = Text::new(label);
+                ai_text.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+                graphics::draw(ctx, &ai_text, (layout::left_panel(9.0), graphics::WHITE))?;
+            }
 
             if scene_state.game_over {
-                let mut game_over_text = Text::new("GAME OVER");
-                game_over_text.set_font(shared_state.assets.font, Scale::uniform(50.0));
+                let mut game_over_text = match scene_state.puzzle.as_ref().and_then(|puzzle| puzzle.outcome) {
+                    Some(PuzzleOutcome::Solved) => Text::new("SOLVED!"),
+                    Some(PuzzleOutcome::Failed) => Text::new("FAILED"),
+                    None => match &scene_state.finesse {
+                        Some(finesse) if finesse.finished() => Text::new(format!("FINESSE {:.0}%", finesse.percentage())),
+                        _ if !scene_state.topped_out => Text::new("FINISHED"),
+                        _ => Text::new("GAME OVER"),
+                    },
+                };
+                game_over_text.set_font(shared_state.assets.skin.font, Scale::uniform(50.0));
+                let width = game_over_text.width(ctx) as f32;
                 graphics::draw(
                     ctx,
                     &game_over_text,
-                    (ScreenPoint2::new(180.0,30.0), graphics::WHITE)
+                    (layout::centered(width, 30.0), graphics::WHITE)
                 )?;
+
+                if let Some(status) = &self.leaderboard_status {
+                    draw_centered(ctx, shared_state.assets.skin.font, status, 8.0, layout::centered_line(-1.0), graphics::WHITE)?;
+                }
             }
         }
 
         graphics::present(ctx)
     }
 
-    fn input(&mut self, _game_world: &mut SharedState, event: KeyCode, _started: bool) {
+    fn input(&mut self, shared_state: &mut SharedState, event: KeyCode, _started: bool) {
         let scene_state = &mut self.state;
 
-        match event {
-            KeyCode::Left => {
-                scene_state.tetrimino.move_left(&scene_state.board);
-            }
-            KeyCode::Right => {
-                scene_state.tetrimino.move_right(&scene_state.board);
-            }
-            KeyCode::Down => {
-                scene_state.tetrimino.move_down(&scene_state.board);
-            }
-            KeyCode::Q => {
-                scene_state.tetrimino.rotate_counter_clockwise(&scene_state.board);
-            }
-            KeyCode::W => {
-                scene_state.tetrimino.rotate_clockwise(&scene_state.board);
-            }
-            KeyCode::Space => {
-                if scene_state.game_over {
-                    scene_state.start_again = true;
+        if event == KeyCode::Escape || self.demo {
+            self.quit = true;
+            return;
+        }
+        if event == KeyCode::Tab {
+            self.ai = match self.ai.take() {
+                Some(_) => None,
+                None => Some(controller(shared_state, &scene_state.board)),
+            };
+            scene_state.assisted = true;
+            return;
+        }
+        if event == KeyCode::H {
+            self.show_hint = !self.show_hint;
+            self.hinted_piece = None;
+            return;
+        }
+
+        if !scene_state.piece_active() {
+            match event {
+                KeyCode::Space => {
+                    if scene_state.game_over {
+                        scene_state.start_again = true;
+                    }
+                }
+                KeyCode::Return => {
+                    let solved = scene_state.puzzle.as_ref().and_then(|puzzle| puzzle.outcome) == Some(PuzzleOutcome::Solved);
+                    if solved && shared_state.next_puzzle() {
+                        scene_state.start_again = true;
+                    }
                 }
+                _ => ()
             }
-            _ => ()
+            return;
+        }
+
+        if self.ai.is_some() {
+            return;
+        }
+        if let Some(action) = self.key_map.action(event) {
+            scene_state.apply(action);
+            self.replay.press(action);
         }
     }
 
@@ -187,9 +327,18 @@ impl Scene<SharedState, KeyCode> for GamePlayScene {
     }
 }
 
-impl GamePlayScene {
-    fn get_random_tetrimino_type() -> Option<TetriminoType> {
-        let mut rng = rand::thread_rng();
-        TetriminoType::from_code(rng.gen_range(1, 8))
+/// The external bot given on the command line, or the built-in AI when there is none, it fails
+/// to start or the board is not as wide as the bot protocol's.
+fn controller(shared_state: &SharedState, board: &Board) -> Box<dyn Controller> {
+    if let Some(command) = &shared_state.bot_command {
+        if board.columns() != Board::COLUMNS {
+            eprintln!("The bot only plays boards {} columns wide", Board::COLUMNS);
+        } else {
+            match TbpController::new(command) {
+                Ok(bot) => return Box::new(bot),
+                Err(error) => eprintln!("Could not start the bot: {}", error),
+            }
+        }
     }
+    Box::new(AiController::new())
 }
@@ -0,0 +1,133 @@
+use ggez::{GameResult, Context, graphics};
+use ggez_goodies::scene::{Scene, SceneSwitch};
+use ggez::event::KeyCode;
+use ggez::graphics::{BLACK, Color};
+use crate::SharedState;
+use crate::net::discovery::{Browser, Kind, LanHost};
+use crate::drawing::draw_centered;
+use crate::layout;
+use crate::scenes::online::{Launch, OnlineScene};
+use crate::scenes::spectate::SpectateScene;
+
+const MAX_LISTED: usize = 10;
+
+/// Lists the games announced on the local network, to join or watch one without typing its
+/// address.
+pub struct LanScene {
+    browser: Option<Browser>,
+    selected: usize,
+    chosen: Option<LanHost>,
+    /// Why the list cannot be shown, or why the chosen game cannot be joined.
+    error: Option<String>,
+    quit: bool,
+}
+
+impl LanScene {
+    pub fn new() -> Box<LanScene> {
+        let (browser, error) = match Browser::listen() {
+            Ok(browser) => (Some(browser), None),
+            Err(error) => (None, Some(format!("COULD NOT LISTEN FOR GAMES: {}", error))),
+        };
+        Box::new(LanScene { browser, selected: 0, chosen: None, error, quit: false })
+    }
+
+    fn hosts(&self) -> &[LanHost] {
+        self.browser.as_ref().map_or(&[][..], |browser| &browser.hosts[..])
+    }
+}
+
+impl Scene<SharedState, KeyCode> for LanScene {
+    fn update(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> SceneSwitch<SharedState, KeyCode> {
+        if self.quit {
+            return SceneSwitch::Pop;
+        }
+
+        if let Some(browser) = self.browser.as_mut() {
+            if let Err(error) = browser.update() {
+                self.error = Some(error.to_string());
+            }
+        }
+        self.selected = self.selected.min(self.hosts().len().saturating_sub(1));
+
+        let host = match self.chosen.take() {
+            Some(host) => host,
+            None => return SceneSwitch::None,
+        };
+        match host.announcement.kind {
+            Kind::Spectate => SceneSwitch::Push(SpectateScene::new(&host.address())),
+            Kind::Versus | Kind::Coop => {
+                shared_state.online_launch = Some(Launch::Join(host.address()));
+                match OnlineScene::new(ctx, shared_state) {
+                    Ok(online_scene) => SceneSwitch::Push(online_scene),
+                    Err(_) => SceneSwitch::None,
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx, BLACK);
+        let font = shared_state.assets.skin.font;
+        let colors = shared_state.assets.skin.colors;
+
+        draw_centered(ctx, font, "LAN GAMES", 20.0, layout::centered_line(0.0), graphics::WHITE)?;
+        if self.browser.is_some() && self.hosts().is_empty() {
+            draw_centered(ctx, font, "LOOKING FOR GAMES...", 12.0, layout::centered_line(6.0), graphics::WHITE)?;
+        }
+        for (index, host) in self.hosts().iter().enumerate().take(MAX_LISTED) {
+            let announcement = &host.announcement;
+            let players = match announcement.capacity {
+                0 => format!("{} PLAYING", announcement.players),
+                capacity => format!("{}/{}", announcement.players, capacity),
+            };
+            let entry = format!(
+                "{} {} {} {}",
+                announcement.kind.name(),
+                announcement.name.to_uppercase(),
+                announcement.rules.to_uppercase(),
+                players,
+            );
+            let color = if index == self.selected {
+                colors.highlight
+            } else if announcement.full() {
+                Color::from_rgb(128, 128, 128)
+            } else {
+                graphics::WHITE
+            };
+            draw_centered(ctx, font, &entry, 10.0, layout::centered_line(4.0 + 1.2 * index as f32), color)?;
+        }
+
+        if let Some(error) = &self.error {
+            draw_centered(ctx, font, &error.to_uppercase(), 8.0, layout::footer(4.5), colors.warning)?;
+        }
+        draw_centered(ctx, font, "UP/DOWN TO CHOOSE, ENTER TO JOIN OR WATCH", 8.0, layout::footer(2.0), graphics::WHITE)?;
+        draw_centered(ctx, font, "ESCAPE TO GO BACK", 8.0, layout::footer(1.0), graphics::WHITE)?;
+
+        graphics::present(ctx)
+    }
+
+    fn input(&mut self, _shared_state: &mut SharedState, event: KeyCode, _started: bool) {
+        match event {
+            KeyCode::Escape => self.quit = true,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.hosts().len().min(MAX_LISTED).saturating_sub(1)),
+            KeyCode::Return => {
+                self.error = None;
+                match self.hosts().get(self.selected) {
+                    Some(host) if host.announcement.full() => self.error = Some(String::from("THAT GAME IS FULL")),
+                    Some(host) => self.chosen = Some(host.clone()),
+                    None => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "LanScene"
+    }
+
+    fn draw_previous(&self) -> bool {
+        false
+    }
+}
@@ -1,3 +1,14 @@
+use ggez::event::KeyCode;
+use crate::SharedState;
+
 pub mod start;
 pub mod game_play;
-pub mod game_over;
+pub mod pre_game;
+pub mod puzzle_select;
+pub mod versus;
+pub mod online;
+pub mod lan;
+pub mod spectate;
+pub mod coop;
+
+pub type SceneStack = ggez_goodies::scene::SceneStack<SharedState, KeyCode>;
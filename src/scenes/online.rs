@@ -0,0 +1,403 @@
+use std::env;
+use ggez::{GameResult, Context, graphics, timer};
+use ggez_goodies::scene::{Scene, SceneSwitch};
+use ggez::event::KeyCode;
+use ggez::graphics::{BLACK, Font};
+use ggez::audio::SoundSource;
+use crate::SharedState;
+use crate::world::Tetrimino;
+use crate::drawing::{draw_tetrimino, draw_ghost, draw_background, draw_board, draw_line_clear, draw_centered, draw_garbage_meter};
+use crate::game::{GameEvent, GamePlayState};
+use crate::controls::KeyMap;
+use crate::modes::GameMode;
+use crate::modes::rules::RuleSet;
+use crate::modes::versus::AttackTable;
+use crate::net::{Connection, Host, Link};
+use crate::net::discovery::{Announcement, Announcer, Kind};
+use crate::net::rollback::Outcome;
+use crate::net::session::{Phase, Role, Session};
+use crate::net::simulator::{Conditions, SimulatedLink};
+use crate::resources::{Assets, SoundEffects};
+use crate::skins::HudColors;
+use crate::scenes::coop::draw_coop;
+use crate::scenes::pre_game::digit_key;
+use crate::scenes::spectate::SpectateScene;
+use crate::layout::{self, SPLIT_BOARDS, PREVIEW_X_OFFSET, PREVIEW_Y_OFFSET};
+
+const MAX_ADDRESS_LENGTH: usize = 21;
+const MAX_NAME_LENGTH: usize = 10;
+
+/// What to do as soon as the scene opens, from `--host`, `--join <address>` or
+/// `--spectate <address>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Launch {
+    Host,
+    Join(String),
+    Watch(String),
+}
+
+enum Stage {
+    /// Choosing to host a game or join one.
+    Menu,
+    Listening(Host),
+    Match(Box<Session>),
+}
+
+/// Online versus and co-op: host a game on a port or join one by address, then play rounds with
+/// the other player for as long as both want rematches.
+pub struct OnlineScene {
+    stage: Stage,
+    /// 0 to host versus, 1 to join, 2 to watch, 3 to host co-op.
+    selected: usize,
+    rule_sets: Vec<RuleSet>,
+    rules: usize,
+    /// Versus or co-op, for the game hosted.
+    mode: GameMode,
+    address: String,
+    port: u16,
+    /// Network conditions to simulate on every connection, from `--lag` and `--loss`.
+    conditions: Option<Conditions>,
+    name: String,
+    attack_table: AttackTable,
+    key_map: KeyMap,
+    sound_effects: SoundEffects,
+    /// Why the last match or connection attempt ended.
+    error: Option<String>,
+    /// A broadcast to open a spectator scene for on the next update.
+    watch: Option<String>,
+    /// Tells the local network about the game while hosting.
+    announcer: Option<Announcer>,
+    quit: bool,
+}
+
+impl OnlineScene {
+    pub fn new(ctx: &mut Context, shared_state: &mut SharedState) -> GameResult<Box<OnlineScene>> {
+        let name = env::var("USER").unwrap_or_else(|_| String::from("PLAYER"));
+        let mut online_scene = OnlineScene {
+            stage: Stage::Menu,
+            selected: 0,
+            rule_sets: RuleSet::load_all(ctx),
+            rules: 0,
+            mode: GameMode::Versus,
+            address: String::from("127.0.0.1"),
+            port: shared_state.port,
+            conditions: shared_state.network_conditions,
+            name: name.to_uppercase().chars().take(MAX_NAME_LENGTH).collect(),
+            attack_table: AttackTable::load(ctx),
+            key_map: KeyMap::single_player(),
+            sound_effects: SoundEffects::new(ctx)?,
+            error: None,
+            watch: None,
+            announcer: None,
+            quit: false,
+        };
+        match shared_state.online_launch.take() {
+            Some(Launch::Host) => online_scene.host(GameMode::Versus),
+            Some(Launch::Join(address)) => {
+                online_scene.selected = 1;
+                online_scene.address = address;
+                online_scene.join();
+            }
+            Some(Launch::Watch(address)) => {
+                online_scene.selected = 2;
+                online_scene.watch = Some(address.clone());
+                online_scene.address = address;
+            }
+            None => (),
+        }
+        Ok(Box::new(online_scene))
+    }
+
+    fn host(&mut self, mode: GameMode) {
+        self.mode = mode;
+        match Host::listen(self.port) {
+            Ok(host) => self.stage = Stage::Listening(host),
+            Err(error) => self.error = Some(format!("COULD NOT LISTEN ON PORT {}: {}", self.port, error)),
+        }
+    }
+
+    fn join(&mut self) {
+        match Connection::connect(&self.address) {
+            Ok(connection) => {
+                let session = Session::guest(self.link(connection), &self.name, self.attack_table.clone());
+                self.stage = Stage::Match(Box::new(session));
+            }
+            Err(error) => self.error = Some(format!("COULD NOT CONNECT: {}", error)),
+        }
+    }
+
+    fn link(&self, connection: Connection) -> Box<dyn Link> {
+        match self.conditions {
+            Some(conditions) => Box::new(SimulatedLink::new(connection, conditions)),
+            None => Box::new(connection),
+        }
+    }
+
+    fn accept(&mut self) {
+        let accepted = match &self.stage {
+            Stage::Listening(host) => host.accept(),
+            _ => return,
+        };
+        match accepted {
+            Ok(Some(connection)) => {
+                let rules = self.rule_sets[self.rules].clone();
+                let session = Session::host(self.link(connection), &self.name, rules, self.mode, self.attack_table.clone());
+                self.stage = Stage::Match(Box::new(session));
+            }
+            Ok(None) => (),
+            Err(error) => {
+                self.error = Some(error.to_string());
+                self.stage = Stage::Menu;
+            }
+        }
+    }
+
+    /// Announces a hosted game, as open while waiting for a player and as full once matched.
+    fn announce(&mut self) {
+        let (port, players) = match &self.stage {
+            Stage::Listening(host) => (host.port(), 1),
+            Stage::Match(session) if session.role == Role::Host => (self.port, 2),
+            _ => return,
+        };
+        if self.announcer.is_none() {
+            self.announcer = Announcer::new().ok();
+        }
+        if let Some(announcer) = self.announcer.as_mut() {
+            announcer.announce(&Announcement {
+                kind: if self.mode == GameMode::Coop { Kind::Coop } else { Kind::Versus },
+                name: self.name.clone(),
+                rules: self.rule_sets[self.rules].name.clone(),
+                players,
+                capacity: 2,
+                port,
+            });
+        }
+    }
+
+    fn play_sound(&mut self, event: Option<GameEvent>) {
+        match event {
+            Some(GameEvent::ToppedOut) => {
+                self.sound_effects.game_over.play().unwrap();
+            }
+            Some(GameEvent::Locked(cleaned_lines)) => {
+                self.sound_effects.fall.play().unwrap();
+                if cleaned_lines > 0 {
+                    self.sound_effects.line.play().unwrap();
+                }
+            }
+            None => (),
+        }
+    }
+
+    fn draw_menu(&self, ctx: &mut Context, font: Font, colors: HudColors) -> GameResult {
+        draw_centered(ctx, font, "ONLINE", 20.0, layout::centered_line(0.0), graphics::WHITE)?;
+        let rules = self.rule_sets[self.rules].name.to_uppercase();
+        let entries = [
+            format!("HOST VERSUS ON PORT {} < {} >", self.port, rules),
+            format!("JOIN {}_", self.address),
+            format!("WATCH {}_", self.address),
+            format!("HOST CO-OP ON PORT {} < {} >", self.port, rules),
+        ];
+        for (index, entry) in entries.iter().enumerate() {
+            let color = if index == self.selected {
+                colors.highlight
+            } else {
+                graphics::WHITE
+            };
+            draw_centered(ctx, font, entry, 12.0, layout::centered_line(6.0 + 1.5 * index as f32), color)?;
+        }
+        if let Some(error) = &self.error {
+            draw_centered(ctx, font, &error.to_uppercase(), 8.0, layout::centered_line(12.0), colors.warning)?;
+        }
+        draw_centered(ctx, font, "LEFT/RIGHT FOR THE RULES, TYPE THE ADDRESS TO JOIN OR WATCH", 8.0, layout::footer(2.0), graphics::WHITE)?;
+        draw_centered(ctx, font, "ENTER TO START, ESCAPE TO GO BACK", 8.0, layout::footer(1.0), graphics::WHITE)
+    }
+
+    fn draw_lobby(&self, ctx: &mut Context, font: Font, session: &Session) -> GameResult {
+        let opponent = session.opponent_name.as_deref().unwrap_or("THE OTHER PLAYER").to_uppercase();
+        if session.phase == Phase::Handshake {
+            return draw_centered(ctx, font, &format!("CONNECTING TO {}...", opponent), 12.0, layout::centered_line(7.0), graphics::WHITE);
+        }
+        let players = match session.mode {
+            GameMode::Coop => format!("{} AND {}", self.name, opponent),
+            _ => format!("{} VS {}", self.name, opponent),
+        };
+        draw_centered(ctx, font, &players, 15.0, layout::centered_line(4.0), graphics::WHITE)?;
+        draw_centered(ctx, font, &format!("{} RULES", session.rules.name.to_uppercase()), 10.0, layout::centered_line(6.0), graphics::WHITE)?;
+        let ready = |ready: bool| if ready { "READY" } else { "NOT READY" };
+        draw_centered(ctx, font, &format!("YOU: {}", ready(session.ready)), 10.0, layout::centered_line(9.0), graphics::WHITE)?;
+        draw_centered(ctx, font, &format!("{}: {}", opponent, ready(session.opponent_ready)), 10.0, layout::centered_line(10.0), graphics::WHITE)?;
+        draw_centered(ctx, font, "SPACE WHEN READY, ESCAPE TO LEAVE", 8.0, layout::footer(1.0), graphics::WHITE)
+    }
+
+    fn draw_match(&self, shared_state: &mut SharedState, ctx: &mut Context, session: &Session) -> GameResult {
+        let font = shared_state.assets.skin.font;
+        if let Some(game) = &session.game {
+            draw_player(&mut shared_state.assets, ctx, game.local(), 0)?;
+            draw_player(&mut shared_state.assets, ctx, game.remote(), 1)?;
+            if game.waiting() {
+                draw_centered(ctx, font, "WAITING", 8.0, layout::centered_line(8.0), graphics::WHITE)?;
+            }
+            draw_centered(ctx, font, &format!("{} - {}", session.wins[0], session.wins[1]), 20.0, layout::centered_line(1.0), graphics::WHITE)?;
+        }
+        if let Some(coop) = &session.coop {
+            draw_coop(&mut shared_state.assets, ctx, &coop.state)?;
+            if coop.waiting() && session.phase == Phase::Playing {
+                draw_centered(ctx, font, "WAITING", 8.0, layout::centered_line(8.0), graphics::WHITE)?;
+            }
+        }
+
+        if let Phase::RoundOver(outcome) = session.phase {
+            let result = match (session.mode, outcome) {
+                (GameMode::Coop, _) => "GAME OVER",
+                (_, Outcome::Won) => "YOU WIN THE ROUND",
+                (_, Outcome::Lost) => "YOU LOSE THE ROUND",
+                (_, Outcome::Draw) => "THE ROUND IS A DRAW",
+            };
+            draw_centered(ctx, font, result, 8.0, layout::centered_line(9.0), graphics::WHITE)?;
+            let next = if session.ready { "WAITING FOR A REMATCH" } else { "SPACE FOR A REMATCH" };
+            draw_centered(ctx, font, next, 8.0, layout::centered_line(10.0), graphics::WHITE)?;
+        }
+        Ok(())
+    }
+}
+
+impl Scene<SharedState, KeyCode> for OnlineScene {
+    fn update(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> SceneSwitch<SharedState, KeyCode> {
+        if self.quit {
+            return SceneSwitch::Pop;
+        }
+        if let Some(address) = self.watch.take() {
+            return SceneSwitch::Push(SpectateScene::new(&address));
+        }
+
+        self.accept();
+        self.announce();
+        const DESIRED_FPS: u32 = 60;
+        while timer::check_update_time(ctx, DESIRED_FPS) {
+            let event = match &mut self.stage {
+                Stage::Match(session) => session.update(),
+                _ => continue,
+            };
+            self.play_sound(event);
+        }
+        if let (Some(broadcaster), Stage::Match(session)) = (shared_state.broadcaster.as_mut(), &self.stage) {
+            if let Some(game) = &session.game {
+                let opponent = session.opponent_name.as_deref().unwrap_or("OPPONENT");
+                broadcaster.publish(&[(&self.name, game.local()), (opponent, game.remote())]);
+            }
+        }
+        if let Stage::Match(session) = &self.stage {
+            if let Phase::Closed(reason) = &session.phase {
+                self.error = Some(reason.clone());
+                self.stage = Stage::Menu;
+            }
+        }
+        SceneSwitch::None
+    }
+
+    fn draw(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx, BLACK);
+        draw_background(ctx, &shared_state.assets)?;
+        let font = shared_state.assets.skin.font;
+
+        match &self.stage {
+            Stage::Menu => self.draw_menu(ctx, font, shared_state.assets.skin.colors)?,
+            Stage::Listening(host) => {
+                draw_centered(ctx, font, &format!("WAITING FOR A PLAYER ON PORT {}", host.port()), 12.0, layout::centered_line(7.0), graphics::WHITE)?;
+                draw_centered(ctx, font, "ESCAPE TO STOP", 8.0, layout::footer(1.0), graphics::WHITE)?;
+            }
+            Stage::Match(session) => match session.phase {
+                Phase::Playing | Phase::RoundOver(_) => self.draw_match(shared_state, ctx, session)?,
+                _ => self.draw_lobby(ctx, font, session)?,
+            },
+        }
+
+        graphics::present(ctx)
+    }
+
+    fn input(&mut self, _shared_state: &mut SharedState, event: KeyCode, _started: bool) {
+        match &mut self.stage {
+            Stage::Menu => match event {
+                KeyCode::Escape => self.quit = true,
+                KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Down => self.selected = (self.selected + 1).min(3),
+                KeyCode::Left if matches!(self.selected, 0 | 3) => {
+                    self.rules = (self.rules + self.rule_sets.len() - 1) % self.rule_sets.len();
+                }
+                KeyCode::Right if matches!(self.selected, 0 | 3) => {
+                    self.rules = (self.rules + 1) % self.rule_sets.len();
+                }
+                KeyCode::Return => {
+                    self.error = None;
+                    match self.selected {
+                        0 => self.host(GameMode::Versus),
+                        1 => self.join(),
+                        2 => self.watch = Some(self.address.clone()),
+                        _ => self.host(GameMode::Coop),
+                    }
+                }
+                KeyCode::Back if matches!(self.selected, 1 | 2) => {
+                    self.address.pop();
+                }
+                _ if matches!(self.selected, 1 | 2) && self.address.len() < MAX_ADDRESS_LENGTH => {
+                    let typed = match event {
+                        KeyCode::Period | KeyCode::Decimal => Some('.'),
+                        KeyCode::Colon | KeyCode::Semicolon => Some(':'),
+                        _ => digit_key(event),
+                    };
+                    if let Some(typed) = typed {
+                        self.address.push(typed);
+                    }
+                }
+                _ => (),
+            },
+            Stage::Listening(_) => {
+                if event == KeyCode::Escape {
+                    self.stage = Stage::Menu;
+                }
+            }
+            Stage::Match(session) => match event {
+                KeyCode::Escape => self.stage = Stage::Menu,
+                KeyCode::Space if session.phase != Phase::Playing => session.ready_up(),
+                _ => {
+                    if let Some(action) = self.key_map.action(event) {
+                        session.apply(action);
+                    }
+                }
+            },
+        }
+    }
+
+    fn name(&self) -> &str {
+        "OnlineScene"
+    }
+
+    fn draw_previous(&self) -> bool {
+        false
+    }
+}
+
+/// A player's game at their side of the screen, as in local versus.
+fn draw_player(assets: &mut Assets, ctx: &mut Context, player: &GamePlayState, index: usize) -> GameResult {
+    let x_offset = SPLIT_BOARDS[index];
+    let board_dimensions = (player.board.width, player.board.height);
+    if player.piece_active() {
+        draw_ghost(assets, ctx, &player.tetrimino, &player.board, board_dimensions, Option::from((x_offset, 0.0)))?;
+        draw_tetrimino(assets, ctx, &player.tetrimino, board_dimensions, Option::from((x_offset, 0.0)))?;
+    }
+    if let Some(next_tetrimino) = player.queue.front() {
+        draw_tetrimino(assets, ctx, &Tetrimino::from(next_tetrimino), board_dimensions, Option::from((x_offset + PREVIEW_X_OFFSET, PREVIEW_Y_OFFSET)))?;
+    }
+    if let Some(hold) = &player.hold {
+        draw_tetrimino(assets, ctx, &Tetrimino::from(hold), board_dimensions, Option::from((x_offset - PREVIEW_X_OFFSET, PREVIEW_Y_OFFSET)))?;
+    }
+    draw_board(assets, ctx, &player.board, board_dimensions, Option::from((x_offset, 0.0)), player.stack_visibility(), player.frames)?;
+    if let (Some(progress), Some(lock)) = (player.clear_progress(), &player.last_lock) {
+        draw_line_clear(assets, ctx, &player.board, lock, progress, board_dimensions, Option::from((x_offset, 0.0)))?;
+    }
+    if let Some(versus) = &player.versus {
+        draw_garbage_meter(ctx, versus.pending(), board_dimensions, layout::garbage_meter(x_offset, 1.0), 0.0)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,405 @@
+use ggez::{GameResult, Context, graphics};
+use ggez_goodies::scene::{Scene, SceneSwitch};
+use ggez::event::KeyCode;
+use ggez::graphics::{Text, BLACK, Scale};
+use ggez::audio::SoundSource;
+use ggez::timer::TimeContext;
+use crate::SharedState;
+use crate::layout;
+use crate::modes::GameMode;
+use crate::modes::rules::{RuleSet, RotationSystem, Randomizer, GravityCurve, LockDelay, Scoring, SpawnOrientation, TopOut, MAX_PREVIEWS};
+use crate::game::GameConfig;
+use crate::ai::Difficulty;
+use crate::constants::VERSUS_BEST_OF;
+use crate::scenes::game_play::GamePlayScene;
+use crate::scenes::versus::VersusScene;
+
+const MAX_START_LEVEL: u16 = 9;
+const MAX_GARBAGE_ROWS: u8 = 10;
+const MAX_SEED_DIGITS: usize = 9;
+const MAX_LINES_PER_LEVEL: u16 = 30;
+const LOCK_DELAYS: [LockDelay; 3] = [LockDelay::Gravity, LockDelay::Step(30), LockDelay::Move { frames: 30, limit: 15 }];
+const ENTRY_DELAYS: [u32; 5] = [0, 6, 10, 18, 30];
+const LINE_CLEAR_DELAYS: [u32; 5] = [0, 12, 20, 30, 41];
+const BEST_OF: [u8; 4] = [1, 3, 5, 7];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Setting {
+    StartLevel,
+    Seed,
+    GarbageRows,
+    Big,
+    Strict,
+    Rules,
+    Rotation,
+    Randomizer,
+    Gravity,
+    LinesPerLevel,
+    LockDelay,
+    EntryDelay,
+    LineClearDelay,
+    Hold,
+    Previews,
+    Scoring,
+    Spawn,
+    TopOut,
+    BestOf,
+}
+
+const RULE_SETTINGS: [Setting; 12] = [
+    Setting::Rotation,
+    Setting::Randomizer,
+    Setting::Gravity,
+    Setting::LinesPerLevel,
+    Setting::LockDelay,
+    Setting::EntryDelay,
+    Setting::LineClearDelay,
+    Setting::Hold,
+    Setting::Previews,
+    Setting::Scoring,
+    Setting::Spawn,
+    Setting::TopOut,
+];
+
+/// Settings for a single player game or a local versus match, chosen before it starts. The rules
+/// start from a preset or a saved rule set; changing any of them makes a custom set that can be
+/// saved with S.
+pub struct PreGameScene {
+    config: GameConfig,
+    /// Set for a versus match, with the computer's difficulty when it is the opponent.
+    versus: Option<Option<Difficulty>>,
+    best_of: u8,
+    rule_sets: Vec<RuleSet>,
+    message: Option<String>,
+    settings: Vec<Setting>,
+    selected: usize,
+    save: bool,
+    start: bool,
+    back: bool,
+}
+
+impl PreGameScene {
+    pub fn new(ctx: &mut Context, mode: GameMode) -> GameResult<Box<PreGameScene>> {
+        let mut settings = Vec::new();
+        if mode != GameMode::Master {
+            settings.push(Setting::StartLevel);
+        }
+        settings.push(Setting::Seed);
+        settings.push(Setting::GarbageRows);
+        settings.push(Setting::Big);
+        if let GameMode::Finesse { .. } = mode {
+            settings.push(Setting::Strict);
+        }
+        settings.push(Setting::Rules);
+        settings.extend_from_slice(&RULE_SETTINGS);
+        let pre_game_scene = PreGameScene {
+            config: GameConfig::new(mode),
+            versus: None,
+            best_of: VERSUS_BEST_OF,
+            rule_sets: RuleSet::load_all(ctx),
+            message: None,
+            settings,
+            selected: 0,
+            save: false,
+            start: false,
+            back: false,
+        };
+        Ok(Box::new(pre_game_scene))
+    }
+
+    /// The settings of a local versus match, against `difficulty` of computer when it is set.
+    pub fn versus(ctx: &mut Context, difficulty: Option<Difficulty>) -> GameResult<Box<PreGameScene>> {
+        let mut pre_game_scene = PreGameScene::new(ctx, GameMode::Versus)?;
+        pre_game_scene.versus = Some(difficulty);
+        pre_game_scene.settings = vec![Setting::BestOf];
+        Ok(pre_game_scene)
+    }
+
+    fn describe(&self, setting: Setting) -> String {
+        let on_off = |value: bool| if value { "ON" } else { "OFF" };
+        let rules = &self.config.rules;
+        match setting {
+            Setting::StartLevel => format!("START LEVEL   < {} >", self.config.start_level),
+            Setting::Seed => match self.config.seed {
+                Some(seed) => format!("SEED          {}", seed),
+                None => String::from("SEED          RANDOM"),
+            },
+            Setting::GarbageRows => format!("GARBAGE ROWS  < {} >", self.config.garbage_rows),
+            Setting::Big => format!("BIG MODE      < {} >", on_off(self.config.big)),
+            Setting::Strict => format!("STRICT        < {} >", on_off(self.strict())),
+            Setting::Rules => format!("RULES         < {} >", self.config.rules.name.to_uppercase()),
+            Setting::Rotation => format!("  ROTATION    < {} >", rules.rotation.describe().to_uppercase()),
+            Setting::Randomizer => format!("  RANDOMIZER  < {} >", rules.randomizer.describe().to_uppercase()),
+            Setting::Gravity => format!("  GRAVITY     < {} >", rules.gravity.describe().to_uppercase()),
+            Setting::LinesPerLevel => format!("  LINES/LEVEL < {} >", rules.lines_per_level),
+            Setting::LockDelay => match rules.lock_delay {
+                LockDelay::Gravity => String::from("  LOCK DELAY  < GRAVITY >"),
+                LockDelay::Step(frames) => format!("  LOCK DELAY  < STEP {} >", frames),
+                LockDelay::Move { frames, limit } => format!("  LOCK DELAY  < MOVE {} x{} >", frames, limit),
+            },
+            Setting::EntryDelay => format!("  ENTRY DELAY < {} >", rules.are),
+            Setting::LineClearDelay => format!("  CLEAR DELAY < {} >", rules.line_clear),
+            Setting::Hold => format!("  HOLD        < {} >", on_off(rules.hold)),
+            Setting::Previews => format!("  PREVIEWS    < {} >", rules.previews),
+            Setting::Scoring => format!("  SCORING     < {} >", rules.scoring.describe().to_uppercase()),
+            Setting::Spawn => format!("  SPAWN       < {} >", rules.spawn.describe().to_uppercase()),
+            Setting::TopOut => format!("  TOP OUT     < {} >", rules.top_out.describe().to_uppercase()),
+            Setting::BestOf => format!("BEST OF       < {} >", self.best_of),
+        }
+    }
+
+    fn strict(&self) -> bool {
+        match self.config.mode {
+            GameMode::Finesse { strict } => strict,
+            _ => false,
+        }
+    }
+
+    /// Left and right step through the values of the selected setting.
+    fn change(&mut self, forward: bool) {
+        let setting = match self.settings.get(self.selected) {
+            Some(setting) => *setting,
+            None => return,
+        };
+        if setting == Setting::Rules {
+            let index = self.rule_sets.iter().position(|rule_set| *rule_set == self.config.rules);
+            let count = self.rule_sets.len();
+            let index = match index {
+                Some(index) if forward => (index + 1) % count,
+                Some(index) => (index + count - 1) % count,
+                None => 0,
+            };
+            self.config.rules = self.rule_sets[index].clone();
+            return;
+        }
+        if RULE_SETTINGS.contains(&setting) {
+            self.change_rule(setting, forward);
+            return;
+        }
+
+        let strict = self.strict();
+        let config = &mut self.config;
+        match setting {
+            Setting::StartLevel => {
+                config.start_level = if forward {
+                    (config.start_level + 1).min(MAX_START_LEVEL)
+                } else {
+                    config.start_level.saturating_sub(1)
+                };
+            }
+            Setting::GarbageRows => {
+                config.garbage_rows = if forward {
+                    (config.garbage_rows + 1).min(MAX_GARBAGE_ROWS)
+                } else {
+                    config.garbage_rows.saturating_sub(1)
+                };
+            }
+            Setting::Big => config.big = !config.big,
+            Setting::Strict => config.mode = GameMode::Finesse { strict: !strict },
+            Setting::BestOf => self.best_of = cycle(&BEST_OF, self.best_of, forward),
+            _ => (),
+        }
+    }
+
+    /// Changing a single rule turns the rule set into a custom one.
+    fn change_rule(&mut self, setting: Setting, forward: bool) {
+        let rules = &mut self.config.rules;
+        match setting {
+            Setting::Rotation => rules.rotation = cycle(&RotationSystem::ALL, rules.rotation, forward),
+            Setting::Randomizer => rules.randomizer = cycle(&Randomizer::ALL, rules.randomizer, forward),
+            Setting::Gravity => rules.gravity = cycle(&GravityCurve::ALL, rules.gravity, forward),
+            Setting::LinesPerLevel => {
+                rules.lines_per_level = if forward {
+                    (rules.lines_per_level + 1).min(MAX_LINES_PER_LEVEL)
+                } else {
+                    (rules.lines_per_level - 1).max(1)
+                };
+            }
+            Setting::LockDelay => rules.lock_delay = cycle(&LOCK_DELAYS, rules.lock_delay, forward),
+            Setting::EntryDelay => rules.are = cycle(&ENTRY_DELAYS, rules.are, forward),
+            Setting::LineClearDelay => rules.line_clear = cycle(&LINE_CLEAR_DELAYS, rules.line_clear, forward),
+            Setting::Hold => rules.hold = !rules.hold,
+            Setting::Previews => {
+                rules.previews = if forward {
+                    (rules.previews + 1).min(MAX_PREVIEWS)
+                } else {
+                    rules.previews.saturating_sub(1)
+                };
+            }
+            Setting::Scoring => rules.scoring = cycle(&Scoring::ALL, rules.scoring, forward),
+            Setting::Spawn => rules.spawn = cycle(&SpawnOrientation::ALL, rules.spawn, forward),
+            Setting::TopOut => rules.top_out = cycle(&TopOut::ALL, rules.top_out, forward),
+            _ => return,
+        }
+        rules.name = String::from("Custom");
+        self.message = None;
+    }
+
+    /// Saves the current rules to the rules directory under a new name, unless they already are
+    /// one of the rule sets on offer.
+    fn save_rules(&mut self, ctx: &mut Context) {
+        if self.rule_sets.contains(&self.config.rules) {
+            return;
+        }
+        let custom_sets = self.rule_sets.len() - RuleSet::presets().len();
+        self.config.rules.name = format!("Custom {}", custom_sets + 1);
+        self.message = Some(match self.config.rules.save(ctx) {
+            Ok(()) => {
+                self.rule_sets.push(self.config.rules.clone());
+                format!("SAVED AS {}", self.config.rules.name.to_uppercase())
+            }
+            Err(error) => {
+                eprintln!("Could not save the rules: {}", error);
+                String::from("COULD NOT SAVE THE RULES")
+            }
+        });
+    }
+
+    /// The seed is typed in with the number keys; backspace removes the last digit.
+    fn edit_seed(&mut self, event: KeyCode) {
+        let mut digits = self.config.seed.map_or(String::new(), |seed| seed.to_string());
+        match digit_key(event) {
+            Some(digit) if digits.len() < MAX_SEED_DIGITS => digits.push(digit),
+            Some(_) => (),
+            None if event == KeyCode::Back => {
+                digits.pop();
+            }
+            None => return,
+        }
+        self.config.seed = digits.parse().ok();
+    }
+}
+
+impl Scene<SharedState, KeyCode> for PreGameScene {
+    fn update(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> SceneSwitch<SharedState, KeyCode> {
+        if self.back {
+            return SceneSwitch::Pop;
+        }
+        if self.save {
+            self.save = false;
+            self.save_rules(ctx);
+        }
+        if !self.start {
+            return SceneSwitch::None;
+        }
+
+        self.start = false;
+        if let Some(difficulty) = self.versus {
+            let versus_scene = match difficulty {
+                Some(difficulty) => VersusScene::against_cpu(ctx, difficulty, self.best_of),
+                None => VersusScene::new(ctx, self.best_of),
+            };
+            return match versus_scene {
+                Ok(versus_scene) => SceneSwitch::Replace(versus_scene),
+                Err(_) => SceneSwitch::None,
+            };
+        }
+        match GamePlayScene::new(ctx, shared_state, self.config.clone()) {
+            Ok(game_play_scene) => {
+                shared_state.assets.theme.stop();
+                ctx.timer_context = TimeContext::new();
+                SceneSwitch::Replace(game_play_scene)
+            }
+            Err(_) => SceneSwitch::None,
+        }
+    }
+
+    fn draw(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx, BLACK);
+
+        let mut title = Text::new(self.config.mode.name());
+        title.set_font(shared_state.assets.skin.font, Scale::uniform(20.0));
+        graphics::draw(ctx, &title, (layout::page(40.0), graphics::WHITE))?;
+
+        for (index, setting) in self.settings.iter().enumerate() {
+            let mut entry = Text::new(self.describe(*setting));
+            entry.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+            let color = if index == self.selected {
+                shared_state.assets.skin.colors.highlight
+            } else {
+                graphics::WHITE
+            };
+            graphics::draw(ctx, &entry, (layout::page(100.0 + 22.0 * index as f32), color))?;
+        }
+
+        if let Some(message) = &self.message {
+            let mut message = Text::new(message.as_str());
+            message.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+            graphics::draw(ctx, &message, (layout::page(layout::footer(3.0)), shared_state.assets.skin.colors.highlight))?;
+        }
+
+        let mut controls = Text::new("LEFT/RIGHT TO CHANGE, NUMBERS FOR THE SEED, ENTER TO PLAY");
+        controls.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+        graphics::draw(ctx, &controls, (layout::page(layout::footer(2.0)), graphics::WHITE))?;
+
+        let mut back = Text::new("S TO SAVE THE RULES, ESCAPE TO GO BACK");
+        back.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+        graphics::draw(ctx, &back, (layout::page(layout::footer(1.0)), graphics::WHITE))?;
+
+        graphics::present(ctx)
+    }
+
+    fn input(&mut self, _shared_state: &mut SharedState, event: KeyCode, _started: bool) {
+        match event {
+            KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                if self.selected + 1 < self.settings.len() {
+                    self.selected += 1;
+                }
+            }
+            KeyCode::Left => self.change(false),
+            KeyCode::Right => self.change(true),
+            KeyCode::Return | KeyCode::Space => {
+                self.start = true;
+            }
+            KeyCode::Escape => {
+                self.back = true;
+            }
+            KeyCode::S => {
+                self.save = true;
+            }
+            _ => {
+                if self.settings.get(self.selected) == Some(&Setting::Seed) {
+                    self.edit_seed(event);
+                }
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "PreGameScene"
+    }
+
+    fn draw_previous(&self) -> bool {
+        false
+    }
+}
+
+/// The digit typed by a number key, on the main keys or the keypad.
+pub(crate) fn digit_key(event: KeyCode) -> Option<char> {
+    let digit = match event {
+        KeyCode::Key0 | KeyCode::Numpad0 => '0',
+        KeyCode::Key1 | KeyCode::Numpad1 => '1',
+        KeyCode::Key2 | KeyCode::Numpad2 => '2',
+        KeyCode::Key3 | KeyCode::Numpad3 => '3',
+        KeyCode::Key4 | KeyCode::Numpad4 => '4',
+        KeyCode::Key5 | KeyCode::Numpad5 => '5',
+        KeyCode::Key6 | KeyCode::Numpad6 => '6',
+        KeyCode::Key7 | KeyCode::Numpad7 => '7',
+        KeyCode::Key8 | KeyCode::Numpad8 => '8',
+        KeyCode::Key9 | KeyCode::Numpad9 => '9',
+        _ => return None,
+    };
+    Some(digit)
+}
+
+fn cycle<T: Copy + PartialEq>(values: &[T], current: T, forward: bool) -> T {
+    let count = values.len();
+    match values.iter().position(|value| *value == current) {
+        Some(index) if forward => values[(index + 1) % count],
+        Some(index) => values[(index + count - 1) % count],
+        None => values[0],
+    }
+}
@@ -0,0 +1,131 @@
+use ggez::{GameResult, Context, graphics};
+use ggez_goodies::scene::{Scene, SceneSwitch};
+use ggez::event::KeyCode;
+use ggez::graphics::{Text, BLACK, Scale};
+use ggez::audio::SoundSource;
+use ggez::timer::TimeContext;
+use crate::SharedState;
+use crate::layout;
+use crate::modes::GameMode;
+use crate::modes::puzzle::PuzzlePack;
+use crate::game::GameConfig;
+use crate::scenes::game_play::GamePlayScene;
+
+const VISIBLE_ENTRIES: usize = 14;
+
+pub struct PuzzleSelectScene {
+    packs: Vec<PuzzlePack>,
+    entries: Vec<(usize, usize)>,
+    selected: usize,
+    chosen: bool,
+    done: bool,
+}
+
+impl PuzzleSelectScene {
+    pub fn new(ctx: &mut Context) -> GameResult<Box<PuzzleSelectScene>> {
+        let packs = PuzzlePack::load_all(ctx);
+        let entries = packs.iter()
+            .enumerate()
+            .flat_map(|(pack_index, pack)| (0..pack.puzzles.len()).map(move |puzzle_index| (pack_index, puzzle_index)))
+            .collect();
+        let puzzle_select_scene = PuzzleSelectScene {
+            packs,
+            entries,
+            selected: 0,
+            chosen: false,
+            done: false,
+        };
+        Ok(Box::new(puzzle_select_scene))
+    }
+}
+
+impl Scene<SharedState, KeyCode> for PuzzleSelectScene {
+    fn update(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> SceneSwitch<SharedState, KeyCode> {
+        if self.chosen {
+            self.chosen = false;
+            if let Ok(game_play_scene) = GamePlayScene::new(ctx, shared_state, GameConfig::new(GameMode::Puzzle)) {
+                shared_state.assets.theme.stop();
+                ctx.timer_context = TimeContext::new();
+                return SceneSwitch::Replace(game_play_scene);
+            }
+        }
+        if self.done {
+            SceneSwitch::Pop
+        } else {
+            SceneSwitch::None
+        }
+    }
+
+    fn draw(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx, BLACK);
+
+        let mut title = Text::new("SELECT A PUZZLE");
+        title.set_font(shared_state.assets.skin.font, Scale::uniform(20.0));
+        graphics::draw(ctx, &title, (layout::page(40.0), graphics::WHITE))?;
+
+        if self.entries.is_empty() {
+            let mut empty = Text::new("NO PUZZLE PACKS FOUND");
+            empty.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+            graphics::draw(ctx, &empty, (layout::page(100.0), graphics::WHITE))?;
+        }
+
+        let first_entry = self.selected.saturating_sub(VISIBLE_ENTRIES - 1);
+        for (row, (pack_index, puzzle_index)) in self.entries.iter().enumerate().skip(first_entry).take(VISIBLE_ENTRIES).map(|(index, entry)| (index - first_entry, entry)) {
+            let pack = &self.packs[*pack_index];
+            let puzzle = &pack.puzzles[*puzzle_index];
+            let completed = if shared_state.puzzle_progress.is_completed(pack, puzzle) { "DONE" } else { "" };
+            let difficulty = "*".repeat(puzzle.difficulty as usize);
+            let mut entry = Text::new(format!(
+                "{:<12} {:<28} {:<5} {}",
+                pack.title.to_uppercase(),
+                puzzle.title.to_uppercase(),
+                difficulty,
+                completed,
+            ));
+            entry.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+
+            let color = if first_entry + row == self.selected {
+                shared_state.assets.skin.colors.highlight
+            } else {
+                graphics::WHITE
+            };
+            graphics::draw(ctx, &entry, (layout::page(100.0 + 30.0 * row as f32), color))?;
+        }
+
+        let mut controls = Text::new("UP/DOWN TO CHOOSE, ENTER TO PLAY, ESCAPE TO GO BACK");
+        controls.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+        graphics::draw(ctx, &controls, (layout::page(layout::footer(1.0)), graphics::WHITE))?;
+
+        graphics::present(ctx)
+    }
+
+    fn input(&mut self, shared_state: &mut SharedState, event: KeyCode, _started: bool) {
+        match event {
+            KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            KeyCode::Down if self.selected + 1 < self.entries.len() => {
+                self.selected += 1;
+            }
+            KeyCode::Return => {
+                if let Some((pack_index, puzzle_index)) = self.entries.get(self.selected) {
+                    shared_state.puzzle_pack = Some(self.packs[*pack_index].clone());
+                    shared_state.puzzle_index = *puzzle_index;
+                    self.chosen = true;
+                }
+            }
+            KeyCode::Escape => {
+                self.done = true;
+            }
+            _ => ()
+        }
+    }
+
+    fn name(&self) -> &str {
+        "PuzzleSelectScene"
+    }
+
+    fn draw_previous(&self) -> bool {
+        false
+    }
+}
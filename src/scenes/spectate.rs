@@ -0,0 +1,110 @@
+use ggez::{GameResult, Context, graphics};
+use ggez_goodies::scene::{Scene, SceneSwitch};
+use ggez::event::KeyCode;
+use ggez::graphics::{BLACK, Text, Scale};
+use crate::SharedState;
+use crate::world::Tetrimino;
+use crate::drawing::{draw_tetrimino, draw_background, draw_board, draw_centered, draw_garbage_meter};
+use crate::modes::StackVisibility;
+use crate::net::spectate::{Spectator, View};
+use crate::resources::Assets;
+use crate::types::ScreenPoint2;
+use crate::layout::{self, SPLIT_BOARDS, PREVIEW_X_OFFSET, PREVIEW_Y_OFFSET};
+
+/// Watches a game someone else is broadcasting, as `--spectate <address>` or from the online menu.
+pub struct SpectateScene {
+    address: String,
+    spectator: Option<Spectator>,
+    /// Why the broadcast could not be watched, or why it ended.
+    error: Option<String>,
+    quit: bool,
+}
+
+impl SpectateScene {
+    pub fn new(address: &str) -> Box<SpectateScene> {
+        let (spectator, error) = match Spectator::connect(address) {
+            Ok(spectator) => (Some(spectator), None),
+            Err(error) => (None, Some(format!("COULD NOT CONNECT: {}", error))),
+        };
+        Box::new(SpectateScene { address: String::from(address), spectator, error, quit: false })
+    }
+}
+
+impl Scene<SharedState, KeyCode> for SpectateScene {
+    fn update(&mut self, _shared_state: &mut SharedState, _ctx: &mut Context) -> SceneSwitch<SharedState, KeyCode> {
+        if self.quit {
+            return SceneSwitch::Pop;
+        }
+
+        if let Some(spectator) = self.spectator.as_mut() {
+            if spectator.update().is_err() {
+                self.error = Some(String::from("THE BROADCAST HAS ENDED"));
+            }
+        }
+        SceneSwitch::None
+    }
+
+    fn draw(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx, BLACK);
+        draw_background(ctx, &shared_state.assets)?;
+        let font = shared_state.assets.skin.font;
+
+        let views = self.spectator.as_ref().map_or(&[][..], |spectator| &spectator.views[..]);
+        if views.is_empty() && self.error.is_none() {
+            draw_centered(ctx, font, &format!("WAITING FOR {}...", self.address), 12.0, layout::centered_line(7.0), graphics::WHITE)?;
+        }
+        for (index, view) in views.iter().enumerate() {
+            // A single player is drawn in the middle, like a solo game.
+            let x_offset = match views.len() {
+                1 => 0.0,
+                _ => SPLIT_BOARDS[index % 2],
+            };
+            draw_view(&mut shared_state.assets, ctx, view, x_offset)?;
+        }
+
+        if let Some(error) = &self.error {
+            draw_centered(ctx, font, &error.to_uppercase(), 8.0, layout::centered_line(9.0), shared_state.assets.skin.colors.warning)?;
+        }
+        draw_centered(ctx, font, "SPECTATING, ESCAPE TO LEAVE", 8.0, layout::footer(0.0), graphics::WHITE)?;
+
+        graphics::present(ctx)
+    }
+
+    fn input(&mut self, _shared_state: &mut SharedState, event: KeyCode, _started: bool) {
+        if event == KeyCode::Escape {
+            self.quit = true;
+        }
+    }
+
+    fn name(&self) -> &str {
+        "SpectateScene"
+    }
+
+    fn draw_previous(&self) -> bool {
+        false
+    }
+}
+
+fn draw_view(assets: &mut Assets, ctx: &mut Context, view: &View, x_offset: f32) -> GameResult {
+    let board = view.board();
+    let board_dimensions = (board.width, board.height);
+    if let Some(next_tetrimino) = view.queue.first() {
+        draw_tetrimino(assets, ctx, &Tetrimino::from(next_tetrimino), board_dimensions, Option::from((x_offset + PREVIEW_X_OFFSET, PREVIEW_Y_OFFSET)))?;
+    }
+    if let Some(hold) = &view.hold {
+        draw_tetrimino(assets, ctx, &Tetrimino::from(hold), board_dimensions, Option::from((x_offset - PREVIEW_X_OFFSET, PREVIEW_Y_OFFSET)))?;
+    }
+    draw_board(assets, ctx, &board, board_dimensions, Option::from((x_offset, 0.0)), StackVisibility::Visible, 0)?;
+    if view.pending > 0 {
+        draw_garbage_meter(ctx, view.pending, board_dimensions, layout::garbage_meter(x_offset, 1.0), 0.0)?;
+    }
+
+    let mut status = format!("{} {} LINES {}", view.name.to_uppercase(), view.score, view.lines);
+    if view.topped_out {
+        status.push_str(" OUT");
+    }
+    let mut text = Text::new(status);
+    text.set_font(assets.skin.font, Scale::uniform(8.0));
+    let position = layout::centered(text.width(ctx) as f32, layout::footer(1.5));
+    graphics::draw(ctx, &text, (ScreenPoint2::new(position.x + x_offset, position.y), graphics::WHITE))
+}
@@ -1,102 +1,246 @@
-use ggez::{GameResult, Context, graphics};
+use ggez::{GameResult, Context, graphics, timer};
 use ggez_goodies::scene::{Scene, SceneSwitch};
-use ggez::event::KeyCode;
+use ggez::event::{KeyCode, quit};
 use ggez::graphics::{Text, BLACK, Scale, TextFragment, Color, Drawable, DrawParam};
 use crate::SharedState;
 use crate::types::ScreenPoint2;
-use ggez::timer::TimeContext;
-use ggez::audio::SoundSource;
+use crate::layout;
+use crate::modes::GameMode;
+use crate::constants::{FADE_TIME, ATTRACT_TIME};
+use crate::scenes::pre_game::PreGameScene;
+use crate::scenes::puzzle_select::PuzzleSelectScene;
+use crate::scenes::coop::CoopScene;
+use crate::scenes::online::OnlineScene;
+use crate::scenes::lan::LanScene;
+use crate::scenes::game_play::GamePlayScene;
+use crate::ai::Difficulty;
 
-pub struct StartScene {}
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MenuEntry {
+    Game(GameMode),
+    Puzzles,
+    Versus,
+    VersusCpu,
+    Online,
+    Lan,
+    Coop,
+    Skin,
+}
+
+impl MenuEntry {
+    fn name(&self) -> &'static str {
+        match self {
+            MenuEntry::Game(mode) => mode.name(),
+            MenuEntry::Puzzles => "PUZZLES",
+            MenuEntry::Versus => GameMode::Versus.name(),
+            MenuEntry::VersusCpu => "VS CPU",
+            MenuEntry::Online => "ONLINE",
+            MenuEntry::Lan => "LAN",
+            MenuEntry::Coop => GameMode::Coop.name(),
+            MenuEntry::Skin => "SKIN",
+        }
+    }
+}
+
+/// The main menu: every mode with a one-line description. Left alone for `ATTRACT_TIME` seconds
+/// it shows a demo game played by the AI.
+pub struct StartScene {
+    entries: Vec<(MenuEntry, &'static str)>,
+    selected: usize,
+    difficulty: usize,
+    idle_time: f32,
+    chosen: Option<MenuEntry>,
+    /// The skin to switch to, which needs the context so waits for `update`.
+    skin: Option<usize>,
+    quit: bool,
+}
 
 impl StartScene {
     pub fn new() -> GameResult<Box<StartScene>> {
-        let start_scene = StartScene {};
+        let entries = vec![
+            (MenuEntry::Game(GameMode::Marathon), "CLEAR LINES AS THE SPEED RISES"),
+            (MenuEntry::Game(GameMode::Sprint), "CLEAR 40 LINES AS FAST AS YOU CAN"),
+            (MenuEntry::Game(GameMode::Ultra), "SCORE AS MUCH AS YOU CAN IN TWO MINUTES"),
+            (MenuEntry::Game(GameMode::Master), "20G ARCADE CHALLENGE WITH HIDDEN GRADES"),
+            (MenuEntry::Game(GameMode::Survival), "GARBAGE RISES FASTER AND FASTER"),
+            (MenuEntry::Game(GameMode::Invisible), "THE STACK DISAPPEARS AS SOON AS IT LOCKS"),
+            (MenuEntry::Game(GameMode::Fading((FADE_TIME * 60.0) as u32)), "THE STACK FADES AWAY AFTER A FEW SECONDS"),
+            (MenuEntry::Game(GameMode::Finesse { strict: false }), "PLACE EACH PIECE WITH THE FEWEST KEYS"),
+            (MenuEntry::Puzzles, "SOLVE HAND-MADE BOARDS WITH A FIXED QUEUE"),
+            (MenuEntry::Versus, "TWO PLAYERS SEND GARBAGE TO EACH OTHER"),
+            (MenuEntry::VersusCpu, "SEND GARBAGE TO THE COMPUTER, LEFT/RIGHT FOR DIFFICULTY"),
+            (MenuEntry::Online, "HOST, JOIN OR WATCH A GAME OVER THE NETWORK"),
+            (MenuEntry::Lan, "JOIN OR WATCH A GAME ON THE LOCAL NETWORK"),
+            (MenuEntry::Coop, "TWO PLAYERS SHARE ONE WIDE BOARD"),
+            (MenuEntry::Skin, "LEFT/RIGHT TO CHANGE THE LOOK AND MUSIC"),
+        ];
+        let start_scene = StartScene {
+            entries,
+            selected: 0,
+            difficulty: 1,
+            idle_time: 0.0,
+            chosen: None,
+            skin: None,
+            quit: false,
+        };
         Ok(Box::new(start_scene))
     }
 }
 
 impl Scene<SharedState, KeyCode> for StartScene {
     fn update(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> SceneSwitch<SharedState, KeyCode> {
-        if shared_state.game_started {
-            ctx.timer_context = TimeContext::new();
-            SceneSwitch::Pop
-        } else {
-            SceneSwitch::None
+        if self.quit {
+            quit(ctx);
+        }
+
+        self.idle_time += timer::duration_to_f64(timer::delta(ctx)) as f32;
+        if self.idle_time >= ATTRACT_TIME {
+            self.idle_time = 0.0;
+            if let Ok(demo) = GamePlayScene::demo(ctx, shared_state) {
+                return SceneSwitch::Push(demo);
+            }
+        }
+
+        if let Some(index) = self.skin.take() {
+            if let Err(error) = shared_state.assets.use_skin(ctx, index) {
+                eprintln!("Could not switch skin: {}", error);
+            }
+        }
+
+        let scene = match self.chosen.take() {
+            Some(MenuEntry::Game(mode)) => PreGameScene::new(ctx, mode).map(|scene| scene as Box<_>),
+            Some(MenuEntry::Puzzles) => PuzzleSelectScene::new(ctx).map(|scene| scene as Box<_>),
+            Some(MenuEntry::Versus) => PreGameScene::versus(ctx, None).map(|scene| scene as Box<_>),
+            Some(MenuEntry::VersusCpu) => PreGameScene::versus(ctx, Some(Difficulty::ALL[self.difficulty])).map(|scene| scene as Box<_>),
+            Some(MenuEntry::Online) => OnlineScene::new(ctx, shared_state).map(|scene| scene as Box<_>),
+            Some(MenuEntry::Lan) => Ok(LanScene::new() as Box<_>),
+            Some(MenuEntry::Coop) => CoopScene::new(ctx).map(|scene| scene as Box<_>),
+            Some(MenuEntry::Skin) | None => return SceneSwitch::None,
+        };
+        match scene {
+            Ok(scene) => SceneSwitch::Push(scene),
+            Err(_) => SceneSwitch::None,
         }
     }
 
     fn draw(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> GameResult<()> {
         graphics::clear(ctx, BLACK);
 
-        let scale = Some(Scale {x: 100.0, y: 300.0});
+        let scale = Some(Scale {x: 60.0, y: 150.0});
         let t_fragment = TextFragment {
             text: "T".to_string(),
             color: Some(Color::from_rgb(156, 16, 229)),
-            font: Some(shared_state.assets.font),
+            font: Some(shared_state.assets.skin.font),
             scale
         };
 
         let e_fragment = TextFragment {
             text: "E".to_string(),
             color: Some(Color::from_rgb(0, 229, 37)),
-            font: Some(shared_state.assets.font),
+            font: Some(shared_state.assets.skin.font),
             scale,
         };
 
         let tt_fragment = TextFragment {
             text: "T".to_string(),
             color: Some(Color::from_rgb(156, 16, 229)),
-            font: Some(shared_state.assets.font),
+            font: Some(shared_state.assets.skin.font),
             scale,
         };
 
         let r_fragment = TextFragment {
             text: "R".to_string(),
             color: Some(Color::from_rgb(230, 0, 36)),
-            font: Some(shared_state.assets.font),
+            font: Some(shared_state.assets.skin.font),
             scale,
         };
 
         let i_fragment = TextFragment {
             text: "I".to_string(),
             color: Some(Color::from_rgb(229, 223, 36)),
-            font: Some(shared_state.assets.font),
+            font: Some(shared_state.assets.skin.font),
             scale,
         };
 
         let s_fragment = TextFragment {
             text: "S".to_string(),
             color: Some(Color::from_rgb(0, 229, 37)),
-            font: Some(shared_state.assets.font),
+            font: Some(shared_state.assets.skin.font),
             scale,
         };
 
-        Text::default()
+        let mut title = Text::default();
+        title
             .add(t_fragment)
             .add(e_fragment)
             .add(tt_fragment)
             .add(r_fragment)
             .add(i_fragment)
-            .add(s_fragment)
-            .draw(ctx, DrawParam::from((ScreenPoint2::new(100.0, 100.0), )))?;
+            .add(s_fragment);
+        let width = title.width(ctx) as f32;
+        title.draw(ctx, DrawParam::from((layout::centered(width, 30.0), )))?;
 
-        Text::new("PRESS SPACE TO START")
-            .set_font(shared_state.assets.font, Scale::uniform(20.0))
-            .draw(ctx, DrawParam::from((ScreenPoint2::new(200.0, 450.0), )))?;
+        // The entries line up on the left, with the widest of them centered.
+        let names: Vec<Text> = self.entries.iter().map(|(entry, _)| {
+            let mut name = match entry {
+                MenuEntry::VersusCpu => Text::new(format!("{} < {} >", entry.name(), Difficulty::ALL[self.difficulty].name())),
+                MenuEntry::Skin => Text::new(format!("{} < {} >", entry.name(), shared_state.assets.skin.name.to_uppercase())),
+                _ => Text::new(entry.name()),
+            };
+            name.set_font(shared_state.assets.skin.font, Scale::uniform(15.0));
+            name
+        }).collect();
+        let widest = names.iter().map(|name| name.width(ctx)).max().unwrap_or(0) as f32;
+        let left = layout::centered(widest, 0.0).x;
 
-        Text::new("CONTROLS: ARROW KEYS + W/Q FOR ROTATING CLOCKWISE/COUNTER CLOCKWISE")
-            .set_font(shared_state.assets.font, Scale::uniform(10.0))
-            .draw(ctx, DrawParam::from((ScreenPoint2::new(50.0, 500.0), )))?;
+        for (index, name) in names.iter().enumerate() {
+            let color = if index == self.selected {
+                shared_state.assets.skin.colors.highlight
+            } else {
+                graphics::WHITE
+            };
+            graphics::draw(ctx, name, (ScreenPoint2::new(left, layout::menu_entry(index)), color))?;
+        }
+
+        if let Some((_, description)) = self.entries.get(self.selected) {
+            let mut description = Text::new(*description);
+            description.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+            let width = description.width(ctx) as f32;
+            graphics::draw(ctx, &description, (layout::centered(width, layout::footer(3.0)), graphics::WHITE))?;
+        }
+
+        let mut help = Text::new("UP/DOWN TO CHOOSE, ENTER TO SELECT, ESCAPE TO QUIT");
+        help.set_font(shared_state.assets.skin.font, Scale::uniform(10.0));
+        let width = help.width(ctx) as f32;
+        help.draw(ctx, DrawParam::from((layout::centered(width, layout::footer(1.0)), )))?;
 
         graphics::present(ctx)
     }
 
     fn input(&mut self, shared_state: &mut SharedState, event: KeyCode, _started: bool) {
+        self.idle_time = 0.0;
         match event {
-            KeyCode::Space => {
-                shared_state.game_started = true;
-                shared_state.assets.theme.stop();
+            KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            KeyCode::Down if self.selected + 1 < self.entries.len() => {
+                self.selected += 1;
+            }
+            KeyCode::Left | KeyCode::Right if self.entries[self.selected].0 == MenuEntry::VersusCpu => {
+                let count = Difficulty::ALL.len();
+                let step = if event == KeyCode::Left { count - 1 } else { 1 };
+                self.difficulty = (self.difficulty + step) % count;
+            }
+            KeyCode::Left | KeyCode::Right if self.entries[self.selected].0 == MenuEntry::Skin => {
+                let count = shared_state.assets.skins.len();
+                let step = if event == KeyCode::Left { count - 1 } else { 1 };
+                self.skin = Some((shared_state.assets.skin_index + step) % count);
+            }
+            KeyCode::Return | KeyCode::Space => {
+                self.chosen = self.entries.get(self.selected).map(|(entry, _)| *entry);
+            }
+            KeyCode::Escape => {
+                self.quit = true;
             }
             _ => ()
         }
@@ -0,0 +1,259 @@
+use ggez::{GameResult, Context, graphics, timer};
+use ggez_goodies::scene::{Scene, SceneSwitch};
+use ggez::event::KeyCode;
+use ggez::graphics::BLACK;
+use ggez::audio::SoundSource;
+use crate::SharedState;
+use crate::world::Tetrimino;
+use crate::drawing::{draw_tetrimino, draw_ghost, draw_background, draw_board, draw_line_clear, draw_centered, draw_garbage_meter};
+use crate::game::{GamePlayState, GameEvent, GameConfig};
+use crate::controls::{KeyMap, Controller};
+use crate::ai::{AiController, Difficulty};
+use crate::modes::GameMode;
+use crate::modes::versus::{AttackTable, VersusPlayer};
+use crate::resources::SoundEffects;
+use crate::layout::{self, SPLIT_BOARDS, PREVIEW_X_OFFSET, PREVIEW_Y_OFFSET};
+
+/// The computer opponent's board is drawn at this scale, where the second player's would be.
+const CPU_SCALE: f32 = 0.5;
+
+pub struct VersusScene {
+    players: Vec<GamePlayState>,
+    key_maps: [KeyMap; 2],
+    wins: [u8; 2],
+    /// The match goes to whoever wins more than half of this many rounds.
+    best_of: u8,
+    round_winner: Option<usize>,
+    attack_table: AttackTable,
+    /// The second player is the computer when set, with a fresh controller every round.
+    difficulty: Option<Difficulty>,
+    opponent: Option<AiController>,
+    sound_effects: SoundEffects,
+    quit: bool,
+}
+
+impl VersusScene {
+    pub fn new(ctx: &mut Context, best_of: u8) -> GameResult<Box<VersusScene>> {
+        VersusScene::with_opponent(ctx, None, best_of)
+    }
+
+    /// A single player against the AI, playing with the single player keys.
+    pub fn against_cpu(ctx: &mut Context, difficulty: Difficulty, best_of: u8) -> GameResult<Box<VersusScene>> {
+        VersusScene::with_opponent(ctx, Some(difficulty), best_of)
+    }
+
+    fn with_opponent(ctx: &mut Context, difficulty: Option<Difficulty>, best_of: u8) -> GameResult<Box<VersusScene>> {
+        let attack_table = AttackTable::load(ctx);
+        let key_maps = match difficulty {
+            Some(_) => [KeyMap::single_player(), KeyMap::right_player()],
+            None => [KeyMap::left_player(), KeyMap::right_player()],
+        };
+        let versus_scene = VersusScene {
+            players: VersusScene::new_round(&attack_table)?,
+            key_maps,
+            wins: [0, 0],
+            best_of,
+            round_winner: None,
+            attack_table,
+            difficulty,
+            opponent: difficulty.map(AiController::with_difficulty),
+            sound_effects: SoundEffects::new(ctx)?,
+            quit: false,
+        };
+        Ok(Box::new(versus_scene))
+    }
+
+    fn start_round(&mut self) -> GameResult {
+        self.players = VersusScene::new_round(&self.attack_table)?;
+        self.opponent = self.difficulty.map(AiController::with_difficulty);
+        Ok(())
+    }
+
+    fn new_round(attack_table: &AttackTable) -> GameResult<Vec<GamePlayState>> {
+        let mut players = Vec::new();
+        for _ in 0..2 {
+            let mut player = GamePlayState::new(GameConfig::new(GameMode::Versus), None)?;
+            player.hold_enabled = true;
+            player.versus = Some(VersusPlayer::new(attack_table.clone()));
+            players.push(player);
+        }
+        Ok(players)
+    }
+
+    fn rounds_to_win(&self) -> u8 {
+        self.best_of / 2 + 1
+    }
+
+    fn match_over(&self) -> bool {
+        self.wins.iter().any(|wins| *wins >= self.rounds_to_win())
+    }
+
+    fn player_name(&self, index: usize) -> String {
+        match self.difficulty {
+            Some(_) if index == 1 => String::from("CPU"),
+            _ => format!("P{}", index + 1),
+        }
+    }
+
+    /// The scale a player's board is drawn at.
+    fn scale(&self, index: usize) -> f32 {
+        match self.difficulty {
+            Some(_) if index == 1 => CPU_SCALE,
+            _ => 1.0,
+        }
+    }
+}
+
+impl Scene<SharedState, KeyCode> for VersusScene {
+    fn update(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> SceneSwitch<SharedState, KeyCode> {
+        if self.quit {
+            return SceneSwitch::Pop;
+        }
+
+        const DESIRED_FPS: u32 = 60;
+        while timer::check_update_time(ctx, DESIRED_FPS) {
+            if self.round_winner.is_some() {
+                continue;
+            }
+
+            if let Some(opponent) = self.opponent.as_mut() {
+                if let Some(action) = opponent.next_action(&self.players[1]) {
+                    self.players[1].apply(action);
+                }
+            }
+
+            for player in self.players.iter_mut() {
+                match player.step() {
+                    Some(GameEvent::ToppedOut) => {
+                        self.sound_effects.game_over.play().unwrap();
+                    }
+                    Some(GameEvent::Locked(cleaned_lines)) => {
+                        self.sound_effects.fall.play().unwrap();
+                        if cleaned_lines > 0 {
+                            self.sound_effects.line.play().unwrap();
+                        }
+                    }
+                    None => (),
+                }
+            }
+
+            let sent: Vec<u8> = self.players.iter_mut()
+                .map(|player| player.versus.as_mut().map_or(0, VersusPlayer::take_outgoing))
+                .collect();
+            for (index, lines) in sent.iter().enumerate() {
+                let opponent = &mut self.players[1 - index];
+                let columns = opponent.board.columns();
+                if let Some(versus) = opponent.versus.as_mut() {
+                    versus.receive(*lines, columns);
+                }
+            }
+
+            let topped_out: Vec<bool> = self.players.iter().map(|player| player.topped_out).collect();
+            if topped_out.iter().any(|topped_out| *topped_out) {
+                let winner = match (topped_out[0], topped_out[1]) {
+                    (true, false) => Some(1),
+                    (false, true) => Some(0),
+                    _ => None,
+                };
+                for player in self.players.iter_mut() {
+                    player.game_over = true;
+                }
+                match winner {
+                    Some(winner) => {
+                        self.wins[winner] += 1;
+                        self.round_winner = Some(winner);
+                    }
+                    None => {
+                        self.start_round().unwrap();
+                    }
+                }
+            }
+        }
+        if let Some(broadcaster) = shared_state.broadcaster.as_mut() {
+            let names: Vec<String> = (0..self.players.len()).map(|index| self.player_name(index)).collect();
+            let players: Vec<(&str, &GamePlayState)> = names.iter().map(String::as_str).zip(self.players.iter()).collect();
+            broadcaster.publish(&players);
+        }
+        SceneSwitch::None
+    }
+
+    fn draw(&mut self, shared_state: &mut SharedState, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx, BLACK);
+        draw_background(ctx, &shared_state.assets)?;
+
+        for (index, player) in self.players.iter().enumerate() {
+            let assets = &mut shared_state.assets;
+            let (scale, x_offset, y_offset) = (self.scale(index), SPLIT_BOARDS[index], 0.0);
+            let board_dimensions = (player.board.width * scale, player.board.height * scale);
+            let preview_y_offset = y_offset + PREVIEW_Y_OFFSET * scale;
+
+            if player.piece_active() {
+                draw_ghost(assets, ctx, &player.tetrimino, &player.board, board_dimensions, Option::from((x_offset, y_offset)))?;
+                draw_tetrimino(assets, ctx, &player.tetrimino, board_dimensions, Option::from((x_offset, y_offset)))?;
+            }
+            if let Some(next_tetrimino) = player.queue.front() {
+                draw_tetrimino(assets, ctx, &Tetrimino::from(next_tetrimino), board_dimensions, Option::from((x_offset + PREVIEW_X_OFFSET * scale, preview_y_offset)))?;
+            }
+            if let Some(hold) = &player.hold {
+                draw_tetrimino(assets, ctx, &Tetrimino::from(hold), board_dimensions, Option::from((x_offset - PREVIEW_X_OFFSET * scale, preview_y_offset)))?;
+            }
+            draw_board(assets, ctx, &player.board, board_dimensions, Option::from((x_offset, y_offset)), player.stack_visibility(), player.frames)?;
+            if let (Some(progress), Some(lock)) = (player.clear_progress(), &player.last_lock) {
+                draw_line_clear(assets, ctx, &player.board, lock, progress, board_dimensions, Option::from((x_offset, y_offset)))?;
+            }
+            if let Some(versus) = &player.versus {
+                draw_garbage_meter(ctx, versus.pending(), board_dimensions, layout::garbage_meter(x_offset, scale), y_offset)?;
+
+                let sent = format!("{} SENT: {}", self.player_name(index), versus.lines_sent);
+                draw_centered(ctx, shared_state.assets.skin.font, &sent, 8.0, layout::centered_line(4.0 + index as f32), graphics::WHITE)?;
+            }
+        }
+
+        let font = shared_state.assets.skin.font;
+        draw_centered(ctx, font, &format!("{} - {}", self.wins[0], self.wins[1]), 20.0, layout::centered_line(1.0), graphics::WHITE)?;
+        draw_centered(ctx, font, &format!("FIRST TO {}", self.rounds_to_win()), 8.0, layout::centered_line(2.5), graphics::WHITE)?;
+
+        if let Some(winner) = self.round_winner {
+            let message = if self.match_over() {
+                format!("{} WINS THE MATCH", self.player_name(winner))
+            } else {
+                format!("{} WINS THE ROUND", self.player_name(winner))
+            };
+            draw_centered(ctx, font, &message, 8.0, layout::centered_line(9.0), graphics::WHITE)?;
+            draw_centered(ctx, font, "SPACE TO CONTINUE", 8.0, layout::centered_line(10.0), graphics::WHITE)?;
+        }
+
+        graphics::present(ctx)
+    }
+
+    fn input(&mut self, _shared_state: &mut SharedState, event: KeyCode, _started: bool) {
+        match event {
+            KeyCode::Escape => {
+                self.quit = true;
+            }
+            KeyCode::Space if self.round_winner.is_some() => {
+                if self.match_over() {
+                    self.wins = [0, 0];
+                }
+                self.start_round().ok();
+                self.round_winner = None;
+            }
+            _ => {
+                let humans = if self.opponent.is_some() { 1 } else { 2 };
+                for (player, key_map) in self.players.iter_mut().zip(self.key_maps.iter()).take(humans) {
+                    if let Some(action) = key_map.action(event) {
+                        player.apply(action);
+                    }
+                }
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "VersusScene"
+    }
+
+    fn draw_previous(&self) -> bool {
+        false
+    }
+}
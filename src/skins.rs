@@ -0,0 +1,332 @@
+//! Skin packs, which change how the game looks and sounds. A pack is a directory under `/skins`
+//! with a `skin.manifest` naming its files. Anything the manifest leaves out, or that fails to
+//! load, comes from the built-in skin. The format is documented in `docs/skins.md`.
+
+use std::io::Read;
+use ggez::{Context, GameResult, GameError, filesystem, graphics};
+use ggez::graphics::{Color, DrawParam, Font, Image, Rect};
+use crate::types::ScreenPoint2;
+use crate::world::{BoardType, TetriminoType};
+
+pub const SKINS_DIR: &str = "/skins";
+const MANIFEST_FILE: &str = "skin.manifest";
+pub const BUILT_IN_MUSIC: &str = "/Tetris_theme.ogg";
+/// The order blocks and sprite sheets list the pieces in.
+const PIECES: [TetriminoType; 7] = [
+    TetriminoType::I, TetriminoType::J, TetriminoType::L, TetriminoType::O,
+    TetriminoType::S, TetriminoType::T, TetriminoType::Z,
+];
+const BUILT_IN_BLOCKS: [&str; 7] = [
+    "/i_block.png", "/j_block.png", "/l_block.png", "/o_block.png", "/s_block.png", "/t_block.png", "/z_block.png",
+];
+/// How opaque a faded ghost is.
+pub const GHOST_ALPHA: f32 = 0.3;
+
+/// Where a pack's block textures come from.
+#[derive(Debug, Clone, PartialEq)]
+enum Blocks {
+    BuiltIn,
+    /// One file per piece, in the order of `PIECES`.
+    Files(Vec<String>),
+    /// A single image of square cells side by side: the pieces in the order of `PIECES`, then
+    /// optionally garbage and the ghost.
+    Sheet(String),
+}
+
+/// How the ghost, the outline of where the falling piece would land, is drawn.
+#[derive(Debug, Clone, PartialEq)]
+enum GhostStyle {
+    Off,
+    /// The piece's own blocks, faded.
+    Faded,
+    File(String),
+}
+
+/// The colours of HUD text, of the selected menu entry and of warnings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HudColors {
+    pub text: Color,
+    pub highlight: Color,
+    pub warning: Color,
+}
+
+impl HudColors {
+    fn built_in() -> HudColors {
+        HudColors {
+            text: graphics::WHITE,
+            highlight: Color::from_rgb(229, 223, 36),
+            warning: Color::from_rgb(230, 0, 36),
+        }
+    }
+}
+
+/// A skin as its manifest describes it, before any of its files are loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub name: String,
+    /// The pack's directory, which its files are relative to. The built-in skin has none.
+    dir: Option<String>,
+    blocks: Blocks,
+    garbage: Option<String>,
+    /// The walls and floor of the playfield, drawn like garbage unless given.
+    wall: Option<String>,
+    /// Unless given, a sheet's ninth cell or no ghost at all.
+    ghost: Option<GhostStyle>,
+    background: Option<String>,
+    font: Option<String>,
+    music: Option<String>,
+    colors: HudColors,
+}
+
+impl Manifest {
+    pub fn built_in() -> Manifest {
+        Manifest {
+            name: String::from("Classic"),
+            dir: None,
+            blocks: Blocks::BuiltIn,
+            garbage: None,
+            wall: None,
+            ghost: None,
+            background: None,
+            font: None,
+            music: None,
+            colors: HudColors::built_in(),
+        }
+    }
+
+    /// The built-in skin followed by every pack in the skins directory, skipping the ones whose
+    /// manifest fails to parse.
+    pub fn load_all(ctx: &mut Context) -> Vec<Manifest> {
+        let mut dirs: Vec<_> = match filesystem::read_dir(ctx, SKINS_DIR) {
+            Ok(paths) => paths.filter(|path| filesystem::is_dir(ctx, path)).collect(),
+            Err(_) => Vec::new(),
+        };
+        dirs.sort();
+        let mut manifests = vec![Manifest::built_in()];
+        for dir in dirs {
+            let dir = dir.to_string_lossy().replace('\\', "/");
+            let mut source = String::new();
+            let read = filesystem::open(ctx, format!("{}/{}", dir, MANIFEST_FILE))
+                .and_then(|mut file| Ok(file.read_to_string(&mut source)?));
+            match read.and_then(|_| Manifest::parse(&dir, &source)) {
+                Ok(manifest) => manifests.push(manifest),
+                Err(GameError::ResourceLoadError(message)) => eprintln!("Skipping the skin in {}: {}", dir, message),
+                Err(_) => (),
+            }
+        }
+        manifests
+    }
+
+    /// Reads the manifest of the pack in `dir`. The pack is named after its directory unless the
+    /// manifest names it.
+    pub fn parse(dir: &str, source: &str) -> GameResult<Manifest> {
+        let mut manifest = Manifest::built_in();
+        manifest.name = dir.rsplit('/').next().unwrap_or(dir).to_string();
+        manifest.dir = Some(dir.to_string());
+        for line in source.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with("//")) {
+            let (key, value) = match line.find(':') {
+                Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+                None => return Err(parse_error(format!("Expected 'key: value', got '{}'", line))),
+            };
+            let words: Vec<&str> = value.split_whitespace().collect();
+            let file = Some(String::from(value));
+            match (key, words.as_slice()) {
+                ("name", _) => manifest.name = String::from(value),
+                ("blocks", files) if files.len() == PIECES.len() => {
+                    manifest.blocks = Blocks::Files(files.iter().map(|file| file.to_string()).collect());
+                }
+                ("blocks", _) => return Err(parse_error(format!("Expected {} block files, got '{}'", PIECES.len(), value))),
+                ("sheet", _) => manifest.blocks = Blocks::Sheet(String::from(value)),
+                ("garbage", _) => manifest.garbage = file,
+                ("wall", _) => manifest.wall = file,
+                ("ghost", ["off"]) => manifest.ghost = Some(GhostStyle::Off),
+                ("ghost", ["faded"]) => manifest.ghost = Some(GhostStyle::Faded),
+                ("ghost", _) => manifest.ghost = Some(GhostStyle::File(String::from(value))),
+                ("background", _) => manifest.background = file,
+                ("font", _) => manifest.font = file,
+                ("music", _) => manifest.music = file,
+                ("text", [r, g, b]) => manifest.colors.text = parse_color(r, g, b)?,
+                ("highlight", [r, g, b]) => manifest.colors.highlight = parse_color(r, g, b)?,
+                ("warning", [r, g, b]) => manifest.colors.warning = parse_color(r, g, b)?,
+                _ => return Err(parse_error(format!("Unknown setting '{}'", line))),
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Whether `name` picks this skin, by its name or its directory, ignoring case.
+    pub fn is_called(&self, name: &str) -> bool {
+        let dir_name = self.dir.as_ref().and_then(|dir| dir.rsplit('/').next());
+        self.name.eq_ignore_ascii_case(name) || dir_name.is_some_and(|dir_name| dir_name.eq_ignore_ascii_case(name))
+    }
+
+    /// The path of the pack's music, or of the built-in theme.
+    pub fn music_path(&self) -> String {
+        self.music.as_ref().map_or_else(|| String::from(BUILT_IN_MUSIC), |music| self.path(music))
+    }
+
+    fn path(&self, file: &str) -> String {
+        match &self.dir {
+            Some(dir) => format!("{}/{}", dir, file),
+            None => format!("/{}", file),
+        }
+    }
+}
+
+/// A block texture: a whole image, or one cell of a sprite sheet.
+#[derive(Clone)]
+pub struct Sprite {
+    image: Image,
+    /// The part of the image used, as fractions of its size.
+    src: Rect,
+}
+
+impl Sprite {
+    fn whole(image: Image) -> Sprite {
+        Sprite { image, src: Rect::new(0.0, 0.0, 1.0, 1.0) }
+    }
+
+    /// Draws the sprite stretched over a cell of `size` at `dest`, tinted by `color`.
+    pub fn draw(&self, ctx: &mut Context, dest: ScreenPoint2, size: (f32, f32), color: Color) -> GameResult {
+        let (width, height) = size;
+        let scale = [
+            width / (self.src.w * f32::from(self.image.width())),
+            height / (self.src.h * f32::from(self.image.height())),
+        ];
+        let draw_params = DrawParam::new()
+            .src(self.src)
+            .scale(scale)
+            .color(color)
+            .dest(dest);
+        graphics::draw(ctx, &self.image, draw_params)
+    }
+}
+
+#[derive(Clone)]
+pub enum Ghost {
+    Off,
+    Faded,
+    Sprite(Sprite),
+}
+
+/// A skin with its files loaded.
+#[derive(Clone)]
+pub struct Skin {
+    pub name: String,
+    blocks: Vec<Sprite>,
+    garbage: Sprite,
+    wall: Sprite,
+    pub ghost: Ghost,
+    /// Stretched over the virtual screen behind the playfield.
+    pub background: Option<Image>,
+    pub font: Font,
+    pub colors: HudColors,
+}
+
+impl Skin {
+    pub fn built_in(ctx: &mut Context) -> GameResult<Skin> {
+        let mut blocks = Vec::new();
+        for file in BUILT_IN_BLOCKS.iter() {
+            blocks.push(Sprite::whole(Image::new(ctx, file)?));
+        }
+        let garbage = Sprite::whole(Image::new(ctx, "/b_block.png")?);
+        Ok(Skin {
+            name: Manifest::built_in().name,
+            blocks,
+            wall: garbage.clone(),
+            garbage,
+            ghost: Ghost::Off,
+            background: None,
+            font: Font::new(ctx, "/PressStart2P-Regular.ttf")?,
+            colors: HudColors::built_in(),
+        })
+    }
+
+    /// Loads the skin `manifest` describes, taking whatever it lacks or fails to load from
+    /// `built_in`. Every file that fails is reported.
+    pub fn load(ctx: &mut Context, manifest: &Manifest, built_in: &Skin) -> Skin {
+        let mut skin = built_in.clone();
+        skin.name = manifest.name.clone();
+        skin.colors = manifest.colors;
+        let image = |ctx: &mut Context, file: &str| match Image::new(ctx, manifest.path(file)) {
+            Ok(image) => Some(image),
+            Err(error) => {
+                eprintln!("Skin '{}' could not load {}, using the built-in one: {}", manifest.name, file, error);
+                None
+            }
+        };
+
+        let mut sheet_extras = Vec::new();
+        match &manifest.blocks {
+            Blocks::BuiltIn => (),
+            Blocks::Files(files) => {
+                for (block, file) in skin.blocks.iter_mut().zip(files) {
+                    if let Some(image) = image(ctx, file) {
+                        *block = Sprite::whole(image);
+                    }
+                }
+            }
+            Blocks::Sheet(file) => {
+                if let Some(sheet) = image(ctx, file) {
+                    let cells = (sheet.width() / sheet.height().max(1)) as usize;
+                    if cells < PIECES.len() {
+                        eprintln!("Skin '{}' has a sheet of {} cells, not {}, using the built-in blocks", manifest.name, cells, PIECES.len());
+                    } else {
+                        let cell_width = 1.0 / cells as f32;
+                        let mut sprites = (0..cells)
+                            .map(|cell| Sprite { image: sheet.clone(), src: Rect::new(cell as f32 * cell_width, 0.0, cell_width, 1.0) });
+                        skin.blocks = sprites.by_ref().take(PIECES.len()).collect();
+                        sheet_extras = sprites.collect();
+                    }
+                }
+            }
+        }
+
+        let mut sheet_extras = sheet_extras.into_iter();
+        if let Some(garbage) = sheet_extras.next() {
+            skin.garbage = garbage;
+        }
+        if let Some(garbage) = manifest.garbage.as_ref().and_then(|file| image(ctx, file)) {
+            skin.garbage = Sprite::whole(garbage);
+        }
+        skin.wall = match manifest.wall.as_ref().and_then(|file| image(ctx, file)) {
+            Some(wall) => Sprite::whole(wall),
+            None => skin.garbage.clone(),
+        };
+        skin.ghost = match &manifest.ghost {
+            Some(GhostStyle::Off) => Ghost::Off,
+            Some(GhostStyle::Faded) => Ghost::Faded,
+            Some(GhostStyle::File(file)) => image(ctx, file).map_or(Ghost::Faded, |ghost| Ghost::Sprite(Sprite::whole(ghost))),
+            None => sheet_extras.next().map_or(Ghost::Off, Ghost::Sprite),
+        };
+        skin.background = manifest.background.as_ref().and_then(|file| image(ctx, file));
+        if let Some(file) = &manifest.font {
+            match Font::new(ctx, manifest.path(file)) {
+                Ok(font) => skin.font = font,
+                Err(error) => eprintln!("Skin '{}' could not load {}, using the built-in font: {}", manifest.name, file, error),
+            }
+        }
+        skin
+    }
+
+    /// The texture for a board or piece code, or `None` for empty cells.
+    pub fn block(&self, code: u8) -> Option<&Sprite> {
+        match TetriminoType::from_code(code) {
+            Some(kind) => PIECES.iter().position(|piece| *piece == kind).map(|index| &self.blocks[index]),
+            None => match BoardType::from_code(code) {
+                Some(BoardType::GARBAGE) => Some(&self.garbage),
+                Some(BoardType::LIMIT) => Some(&self.wall),
+                _ => None,
+            },
+        }
+    }
+}
+
+fn parse_color(r: &str, g: &str, b: &str) -> GameResult<Color> {
+    let channel = |value: &str| value.parse::<u8>().map_err(|_| parse_error(format!("Expected a colour channel from 0 to 255, got '{}'", value)));
+    Ok(Color::from_rgb(channel(r)?, channel(g)?, channel(b)?))
+}
+
+fn parse_error(message: String) -> GameError {
+    GameError::ResourceLoadError(message)
+}
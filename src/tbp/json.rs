@@ -0,0 +1,256 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// How deeply arrays and objects may nest. Anything deeper is refused rather than parsed, so
+/// hostile input cannot overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+/// Just enough JSON for the bot protocol: one value per line, objects keep their key order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(key, value)| (String::from(key), value)).collect())
+    }
+
+    pub fn string(value: &str) -> Json {
+        Json::String(String::from(value))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(value) if value.fract() == 0.0 => Some(*value as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<Json, String> {
+        let mut chars = source.chars().peekable();
+        let value = parse_value(&mut chars, 0)?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(value),
+            Some(letter) => Err(format!("Unexpected '{}' after the value", letter)),
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for letter in value.chars() {
+        match letter {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            letter if (letter as u32) < 0x20 => write!(f, "\\u{:04x}", letter as u32)?,
+            letter => write!(f, "{}", letter)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|letter| letter.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn expect_word(chars: &mut Peekable<Chars>, word: &str, value: Json) -> Result<Json, String> {
+    for expected in word.chars() {
+        if chars.next() != Some(expected) {
+            return Err(format!("Expected '{}'", word));
+        }
+    }
+    Ok(value)
+}
+
+fn parse_value(chars: &mut Peekable<Chars>, depth: usize) -> Result<Json, String> {
+    skip_whitespace(chars);
+    if depth >= MAX_DEPTH && matches!(chars.peek(), Some('[') | Some('{')) {
+        return Err(format!("Values nest more than {} deep", MAX_DEPTH));
+    }
+    match chars.peek() {
+        Some('n') => expect_word(chars, "null", Json::Null),
+        Some('t') => expect_word(chars, "true", Json::Bool(true)),
+        Some('f') => expect_word(chars, "false", Json::Bool(false)),
+        Some('"') => parse_string(chars).map(Json::String),
+        Some('[') => {
+            chars.next();
+            let mut values = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Json::Array(values));
+            }
+            loop {
+                values.push(parse_value(chars, depth + 1)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => (),
+                    Some(']') => return Ok(Json::Array(values)),
+                    _ => return Err(String::from("Expected ',' or ']' in an array")),
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            let mut fields = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Ok(Json::Object(fields));
+            }
+            loop {
+                skip_whitespace(chars);
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                if chars.next() != Some(':') {
+                    return Err(String::from("Expected ':' after a key"));
+                }
+                fields.push((key, parse_value(chars, depth + 1)?));
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => (),
+                    Some('}') => return Ok(Json::Object(fields)),
+                    _ => return Err(String::from("Expected ',' or '}' in an object")),
+                }
+            }
+        }
+        Some(letter) if *letter == '-' || letter.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(letter) = chars.peek() {
+                if letter.is_ascii_digit() || "+-.eE".contains(*letter) {
+                    number.push(*letter);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            number.parse().map(Json::Number).map_err(|_| format!("Bad number '{}'", number))
+        }
+        Some(letter) => Err(format!("Unexpected '{}'", letter)),
+        None => Err(String::from("Unexpected end of input")),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    if chars.next() != Some('"') {
+        return Err(String::from("Expected a string"));
+    }
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(value),
+            Some('\\') => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some('b') => value.push('\u{8}'),
+                Some('f') => value.push('\u{c}'),
+                Some('u') => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    let letter = u32::from_str_radix(&code, 16).ok().and_then(std::char::from_u32);
+                    value.push(letter.unwrap_or('\u{fffd}'));
+                }
+                Some(letter) => value.push(letter),
+                None => return Err(String::from("Unterminated string")),
+            },
+            Some(letter) => value.push(letter),
+            None => return Err(String::from("Unterminated string")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_values() {
+        let json = Json::parse(r#"{"a":[1,{"b":"c"}],"d":null}"#).unwrap();
+        assert_eq!(json.to_string(), r#"{"a":[1,{"b":"c"}],"d":null}"#);
+    }
+
+    #[test]
+    fn accepts_nesting_up_to_the_limit() {
+        let source = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert!(Json::parse(&source).is_ok());
+    }
+
+    #[test]
+    fn rejects_deep_nesting_without_overflowing() {
+        assert!(Json::parse(&"[".repeat(100_000)).is_err());
+        assert!(Json::parse(&"{\"a\":".repeat(100_000)).is_err());
+    }
+}
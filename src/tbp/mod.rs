@@ -0,0 +1,370 @@
+//! The Tetris Bot Protocol: external bots run as child processes and talk JSON, one message per
+//! line, over their standard input and output. The game starts the bot with the board and queue,
+//! asks it for suggestions, and tells it which move was played and which pieces were revealed.
+
+pub mod json;
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use ggez::{GameResult, GameError};
+use crate::pathfinding::{placements, footprint, Placement};
+use crate::controls::{Action, Controller};
+use crate::game::GamePlayState;
+use crate::modes::rules::Randomizer;
+use crate::world::{Board, BoardType, TetriminoType};
+use self::json::Json;
+
+/// Frames between presses while playing a bot's move.
+const ACTION_DELAY: u32 = 2;
+/// Rows in a protocol board; the rows above the playfield are always empty here. Protocol boards
+/// are always `Board::COLUMNS` wide.
+const BOARD_ROWS: usize = 40;
+const ORIENTATIONS: [&str; 4] = ["north", "east", "south", "west"];
+
+/// A running bot process. Its output is read on a separate thread so the game never waits on it.
+pub struct BotProcess {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Json>,
+}
+
+impl BotProcess {
+    /// Runs `command`, split on whitespace into the program and its arguments.
+    pub fn spawn(command: &str) -> GameResult<BotProcess> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or_else(|| protocol_error("The bot command is empty"))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or_else(|| protocol_error("No input pipe to the bot"))?;
+        let stdout = child.stdout.take().ok_or_else(|| protocol_error("No output pipe from the bot"))?;
+
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                match Json::parse(&line) {
+                    Ok(message) => {
+                        if sender.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    Err(error) => eprintln!("Ignoring a bad message from the bot: {}", error),
+                }
+            }
+        });
+
+        Ok(BotProcess { child, stdin, messages })
+    }
+
+    pub fn send(&mut self, message: &Json) -> GameResult {
+        writeln!(self.stdin, "{}", message)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    pub fn poll(&self) -> Option<Json> {
+        self.messages.try_recv().ok()
+    }
+}
+
+impl Drop for BotProcess {
+    fn drop(&mut self) {
+        let _ = self.send(&message("quit", Vec::new()));
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BotPhase {
+    /// Waiting for the bot's `info`, then for `ready` once the rules are sent.
+    Starting,
+    /// Ready to be sent the game with `start`.
+    Ready,
+    /// Asked for a suggestion for the current piece.
+    Thinking,
+    /// Playing the chosen move.
+    Playing,
+    /// The bot reported an error or quit; the piece is left to fall.
+    Failed,
+}
+
+/// Plays the game with the moves an external bot suggests, turning each one into the presses that
+/// get the active piece there.
+pub struct TbpController {
+    bot: BotProcess,
+    phase: BotPhase,
+    plan: VecDeque<Action>,
+    planned_piece: Option<u32>,
+    holding: bool,
+    /// The board the bot expects once its move locks. Anything else, such as garbage, restarts it.
+    expected: Option<Board>,
+    /// Pieces the bot knows about that have not been played or held yet, the active one included.
+    known_pieces: usize,
+    wait: u32,
+}
+
+impl TbpController {
+    pub fn new(command: &str) -> GameResult<TbpController> {
+        Ok(TbpController {
+            bot: BotProcess::spawn(command)?,
+            phase: BotPhase::Starting,
+            plan: VecDeque::new(),
+            planned_piece: None,
+            holding: false,
+            expected: None,
+            known_pieces: 0,
+            wait: 0,
+        })
+    }
+
+    fn read_messages(&mut self, state: &GamePlayState) -> GameResult {
+        while let Some(message) = self.bot.poll() {
+            match message.get("type").and_then(Json::as_str) {
+                Some("info") => {
+                    let randomizer = match state.config.rules.randomizer {
+                        Randomizer::Bag => "seven_bag",
+                        _ => "unknown",
+                    };
+                    self.bot.send(&self::message("rules", vec![("randomizer", Json::string(randomizer))]))?;
+                }
+                Some("ready") => self.phase = BotPhase::Ready,
+                Some("error") => {
+                    let reason = message.get("reason").and_then(Json::as_str).unwrap_or("unknown");
+                    eprintln!("The bot failed: {}", reason);
+                    self.phase = BotPhase::Failed;
+                }
+                Some("suggestion") if self.phase == BotPhase::Thinking => self.choose(state, &message)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the first suggested move the active piece can reach, telling the bot it was played.
+    /// When none can be reached the bot is stopped and started over on the next piece.
+    fn choose(&mut self, state: &GamePlayState, suggestion: &Json) -> GameResult {
+        let moves = suggestion.get("moves").and_then(Json::as_array).unwrap_or(&[]);
+        let mut candidates = candidates(state);
+        for suggested in moves {
+            let cells = suggested.get("location").and_then(|location| location_cells(location, state.board.rows()));
+            let found = cells.and_then(|(kind, cells)| candidates.iter()
+                .position(|(candidate, placement, _)| *candidate == kind && footprint(&placement.tetrimino) == cells));
+            if let Some(index) = found {
+                let (_, placement, held) = candidates.swap_remove(index);
+                self.bot.send(&message("play", vec![("move", suggested.clone())]))?;
+                self.known_pieces = self.known_pieces.saturating_sub(if held && state.hold.is_none() { 2 } else { 1 });
+                self.expected = Some(placement.board);
+                self.plan = placement.actions.into_iter().collect();
+                if held {
+                    self.plan.push_front(Action::Hold);
+                }
+                self.phase = BotPhase::Playing;
+                return Ok(());
+            }
+        }
+        self.bot.send(&message("stop", Vec::new()))?;
+        self.phase = BotPhase::Ready;
+        Ok(())
+    }
+
+    fn start(&mut self, state: &GamePlayState) -> GameResult {
+        if state.board.columns() != Board::COLUMNS {
+            return Err(protocol_error("The bot protocol only plays boards ten columns wide"));
+        }
+        let mut queue = vec![state.tetrimino.kind];
+        queue.extend(state.queue.iter());
+        self.known_pieces = queue.len();
+        self.bot.send(&message("start", vec![
+            ("hold", state.hold.map_or(Json::Null, |kind| Json::string(piece_name(kind)))),
+            ("queue", Json::Array(queue.iter().map(|kind| Json::string(piece_name(*kind))).collect())),
+            ("combo", Json::Number(0.0)),
+            ("back_to_back", Json::Bool(false)),
+            ("board", board_json(&state.board)),
+        ]))?;
+        self.bot.send(&message("suggest", Vec::new()))?;
+        self.phase = BotPhase::Thinking;
+        Ok(())
+    }
+
+    /// Tells the bot about the pieces revealed since its last move, then asks about the new piece.
+    fn continue_game(&mut self, state: &GamePlayState) -> GameResult {
+        let in_sync = self.expected.as_ref().is_some_and(|expected| same_stack(expected, &state.board));
+        if !in_sync {
+            self.bot.send(&message("stop", Vec::new()))?;
+            return self.start(state);
+        }
+        let revealed = (state.queue.len() + 1).saturating_sub(self.known_pieces);
+        for kind in state.queue.iter().skip(state.queue.len().saturating_sub(revealed)) {
+            self.bot.send(&message("new_piece", vec![("piece", Json::string(piece_name(*kind)))]))?;
+            self.known_pieces += 1;
+        }
+        self.bot.send(&message("suggest", Vec::new()))?;
+        self.phase = BotPhase::Thinking;
+        Ok(())
+    }
+
+    fn update(&mut self, state: &GamePlayState) -> GameResult {
+        self.read_messages(state)?;
+        if !state.piece_active() {
+            return Ok(());
+        }
+        if self.holding {
+            self.holding = false;
+            self.planned_piece = Some(state.pieces);
+        }
+        if self.planned_piece == Some(state.pieces) {
+            return Ok(());
+        }
+        match self.phase {
+            BotPhase::Ready => self.start(state)?,
+            BotPhase::Playing => self.continue_game(state)?,
+            _ => return Ok(()),
+        }
+        self.planned_piece = Some(state.pieces);
+        self.plan.clear();
+        Ok(())
+    }
+}
+
+impl Controller for TbpController {
+    fn next_action(&mut self, state: &GamePlayState) -> Option<Action> {
+        if let Err(error) = self.update(state) {
+            eprintln!("Lost the bot: {}", error);
+            self.phase = BotPhase::Failed;
+        }
+        if self.phase != BotPhase::Playing || !state.piece_active() {
+            return None;
+        }
+        if self.wait > 0 {
+            self.wait -= 1;
+            return None;
+        }
+        let action = self.plan.pop_front();
+        match action {
+            Some(Action::Hold) => self.holding = true,
+            Some(Action::SoftDrop) => (),
+            _ => self.wait = ACTION_DELAY,
+        }
+        action
+    }
+
+    fn thinking(&self) -> bool {
+        self.phase == BotPhase::Starting || self.phase == BotPhase::Thinking
+    }
+
+    fn failed(&self) -> bool {
+        self.phase == BotPhase::Failed
+    }
+}
+
+/// Every placement of the active piece, and of the piece hold would bring in, with a flag saying
+/// whether it needs a hold first.
+fn candidates(state: &GamePlayState) -> Vec<(TetriminoType, Placement, bool)> {
+    let rotation = state.config.rules.rotation;
+    let mut candidates: Vec<_> = placements(&state.tetrimino, &state.board, rotation).into_iter()
+        .map(|placement| (state.tetrimino.kind, placement, false))
+        .collect();
+    if state.hold_enabled && !state.hold_used {
+        if let Some(held) = state.hold.or_else(|| state.queue.front().copied()) {
+            candidates.extend(placements(&state.new_piece(&held), &state.board, rotation).into_iter()
+                .map(|placement| (held, placement, true)));
+        }
+    }
+    candidates
+}
+
+/// The board cells, sorted, that a protocol location covers. Protocol rows count up from the
+/// bottom of the playfield; board rows count down from the top.
+pub fn location_cells(location: &Json, rows: usize) -> Option<(TetriminoType, Vec<(i8, i8)>)> {
+    let kind = location.get("type").and_then(Json::as_str).and_then(piece_from_name)?;
+    let orientation = location.get("orientation").and_then(Json::as_str)?;
+    let turns = ORIENTATIONS.iter().position(|name| *name == orientation)?;
+    let x = location.get("x").and_then(Json::as_i64)?;
+    let y = location.get("y").and_then(Json::as_i64)?;
+
+    let mut cells: Vec<(i8, i8)> = north_cells(kind).iter()
+        .map(|(dx, dy)| (0..turns).fold((*dx, *dy), |(dx, dy), _| (dy, -dx)))
+        .map(|(dx, dy)| ((x + i64::from(dx) + 1) as i8, (rows as i64 - 1 - y - i64::from(dy)) as i8))
+        .collect();
+    cells.sort();
+    Some((kind, cells))
+}
+
+/// Cells of each piece facing north around its centre, with y pointing up.
+fn north_cells(kind: TetriminoType) -> [(i8, i8); 4] {
+    match kind {
+        TetriminoType::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+        TetriminoType::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        TetriminoType::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+        TetriminoType::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+        TetriminoType::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+        TetriminoType::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+        TetriminoType::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+    }
+}
+
+pub fn piece_name(kind: TetriminoType) -> &'static str {
+    match kind {
+        TetriminoType::I => "I",
+        TetriminoType::J => "J",
+        TetriminoType::L => "L",
+        TetriminoType::O => "O",
+        TetriminoType::S => "S",
+        TetriminoType::T => "T",
+        TetriminoType::Z => "Z",
+    }
+}
+
+pub fn piece_from_name(name: &str) -> Option<TetriminoType> {
+    let mut letters = name.chars();
+    match (letters.next(), letters.next()) {
+        (Some(letter), None) => TetriminoType::from_char(letter),
+        _ => None,
+    }
+}
+
+/// Forty rows of ten cells from the bottom up, each a piece letter, "G" for garbage or null.
+fn board_json(board: &Board) -> Json {
+    let rows = board.rows();
+    let board_rows = (0..BOARD_ROWS).map(|y| {
+        let cells = (1..=board.columns()).map(|column| {
+            let code = if y < rows {
+                board.data.get((rows - 1 - y, column)).map_or(0, |cell| cell.code)
+            } else {
+                0
+            };
+            match (BoardType::from_code(code), TetriminoType::from_code(code)) {
+                (Some(BoardType::GARBAGE), _) => Json::string("G"),
+                (_, Some(kind)) => Json::string(piece_name(kind)),
+                _ => Json::Null,
+            }
+        });
+        Json::Array(cells.collect())
+    });
+    Json::Array(board_rows.collect())
+}
+
+fn same_stack(expected: &Board, board: &Board) -> bool {
+    expected.data.shape() == board.data.shape()
+        && expected.data.iter().zip(board.data.iter()).all(|(a, b)| a.is_empty() == b.is_empty())
+}
+
+fn message(kind: &str, mut fields: Vec<(&str, Json)>) -> Json {
+    fields.insert(0, ("type", Json::string(kind)));
+    Json::object(fields)
+}
+
+fn protocol_error(message: &str) -> GameError {
+    GameError::ResourceLoadError(String::from(message))
+}
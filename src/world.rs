@@ -1,10 +1,11 @@
-use ggez::nalgebra::{MatrixMN, Matrix2, U21, U12, U20, U10};
+use ggez::nalgebra::{DMatrix, Matrix2};
 use arrayvec::ArrayVec;
-use crate::constants::{BOARD_HEIGHT, BOARD_WIDTH, POINTS_FOR_ONE_LINE, POINTS_FOR_TWO_LINES, POINTS_FOR_THREE_LINES, POINTS_FOR_MORE_THAN_THREE_LINES, TOP_BOUNDARY};
+use crate::constants::{BOARD_HEIGHT, BOARD_WIDTH, TOP_BOUNDARY};
 use std::convert::TryFrom;
 use crate::types::{WorldPoint2, WorldVector2};
+use crate::modes::rules::RuleSet;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TetriminoType {
     I,
     J,
@@ -40,19 +41,36 @@ impl TetriminoType {
             _ => Option::None,
         }
     }
+
+    pub fn from_char(letter: char) -> Option<TetriminoType> {
+        match letter.to_ascii_uppercase() {
+            'I' => Option::from(TetriminoType::I),
+            'J' => Option::from(TetriminoType::J),
+            'L' => Option::from(TetriminoType::L),
+            'O' => Option::from(TetriminoType::O),
+            'S' => Option::from(TetriminoType::S),
+            'T' => Option::from(TetriminoType::T),
+            'Z' => Option::from(TetriminoType::Z),
+            _ => Option::None,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum BoardType {
     EMPTY,
     BLOCK,
+    GARBAGE,
     LIMIT,
 }
 
 impl BoardType {
+    pub const GARBAGE_CODE: u8 = 8;
+
     pub fn from_code(code: u8) -> Option<BoardType> {
         match code {
             0 => Option::from(BoardType::EMPTY),
+            BoardType::GARBAGE_CODE => Option::from(BoardType::GARBAGE),
             99 => Option::from(BoardType::LIMIT),
             _ => Option::from(BoardType::BLOCK),
         }
@@ -66,46 +84,115 @@ enum Direction {
     RIGHT,
 }
 
-#[derive(Debug)]
+/// A single board position: the block code plus the frame at which it was locked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub code: u8,
+    pub locked_at: u32,
+}
+
+impl Cell {
+    pub const EMPTY: Cell = Cell { code: 0, locked_at: 0 };
+    pub const LIMIT: Cell = Cell { code: 99, locked_at: 0 };
+
+    pub fn is_empty(&self) -> bool {
+        self.code == 0
+    }
+}
+
+/// The playfield surrounded by LIMIT cells on both sides and along the bottom. `width` and
+/// `height` are the size of a standard ten column board on screen, so cells keep the same size
+/// on wider boards.
+#[derive(Debug, Clone)]
 pub struct Board {
-    pub data: MatrixMN<u8, U21, U12>,
+    pub data: DMatrix<Cell>,
     pub height: f32,
     pub width: f32,
 }
 
 impl Board {
+    pub const ROWS: usize = 20;
+    pub const COLUMNS: usize = 10;
+
     pub fn new() -> Board {
-        let data: MatrixMN<u8, U21, U12> = MatrixMN::<u8, U20, U10>::zeros()
-            .insert_row(20, 99)
-            .insert_column(0, 99)
-            .insert_column(11, 99);
+        Board::with_size(Board::COLUMNS, Board::ROWS)
+    }
+
+    pub fn with_columns(columns: usize) -> Board {
+        Board::with_size(columns, Board::ROWS)
+    }
+
+    /// Big mode: a half-width, half-height board drawn with cells twice the usual size.
+    pub fn big() -> Board {
+        let mut board = Board::with_size(Board::COLUMNS / 2, Board::ROWS / 2);
+        board.width *= 2.0;
+        board.height *= 2.0;
+        board
+    }
+
+    fn with_size(columns: usize, rows: usize) -> Board {
         Board {
-            data,
+            data: Board::empty_data(columns, rows),
             height: BOARD_HEIGHT,
             width: BOARD_WIDTH,
         }
     }
 
-    pub fn update(&mut self, tetrimino: &Tetrimino, score: &mut ScoreBoard) -> u8 {
-        if let Some(matrix_index) = to_matrix_index(tetrimino.pos.x, tetrimino.pos.y) {
-            *self.data.index_mut(matrix_index) = tetrimino.kind.to_code();
-        }
-        for vector in tetrimino.vectors.iter() {
-            if let Some(matrix_index) = to_matrix_index(tetrimino.pos.x + vector.x, tetrimino.pos.y + vector.y) {
-                *self.data.index_mut(matrix_index) = tetrimino.kind.to_code();
+    pub fn columns(&self) -> usize {
+        self.data.ncols() - 2
+    }
+
+    pub fn rows(&self) -> usize {
+        self.data.nrows() - 1
+    }
+
+    /// The column new pieces enter at, the middle of the playfield.
+    pub fn spawn_column(&self) -> i8 {
+        self.columns().div_ceil(2) as i8
+    }
+
+    fn stamp(&mut self, tetrimino: &Tetrimino, locked_at: u32) {
+        let cell = Cell {
+            code: tetrimino.kind.to_code(),
+            locked_at,
+        };
+        for point in tetrimino.cells().iter() {
+            if let Some(matrix_index) = to_matrix_index(point.x, point.y) {
+                if let Some(element) = self.data.get_mut(matrix_index) {
+                    *element = cell;
+                }
             }
         }
+    }
+
+    pub fn update(&mut self, tetrimino: &Tetrimino, locked_at: u32) -> u8 {
+        self.stamp(tetrimino, locked_at);
+        self.clear_full_rows()
+    }
+
+    /// Locks a piece into the board without clearing the rows it completes, so they can stay
+    /// through a line clear delay until `clear_full_rows`.
+    pub fn place(&mut self, tetrimino: &Tetrimino, locked_at: u32) {
+        self.stamp(tetrimino, locked_at);
+    }
+
+    /// The complete rows of the playfield, from the top.
+    pub fn full_rows(&self) -> Vec<usize> {
+        (0..self.rows())
+            .filter(|row| (1..=self.columns()).all(|column| !self.data.index((*row, column)).is_empty()))
+            .collect()
+    }
 
-        let mut updated_data: MatrixMN<u8, U21, U12> = MatrixMN::<u8, U20, U10>::zeros()
-            .insert_row(20, 99)
-            .insert_column(0, 99)
-            .insert_column(11, 99);
-        let mut updated_data_row_index = 19;
+    /// Takes away the complete rows and drops the stack above them, returning how many there were.
+    pub fn clear_full_rows(&mut self) -> u8 {
+        let rows = self.rows();
+        let mut updated_data = Board::empty_data(self.columns(), rows);
+        let mut updated_data_row_index = rows - 1;
         let mut cleaned_lines: u8 = 0;
 
-        for data_row_index in (0..=19).rev() {
+        for data_row_index in (0..rows).rev() {
             let mut row = self.data.row_mut(data_row_index);
-            let row_complete = row.column_iter().all(|element| *element.get((0, 0)).unwrap() > 0);
+            let row_complete = row.column_iter().all(|element| !element.get((0, 0)).unwrap().is_empty());
             if !row_complete {
                 for (c, element) in row.column_iter_mut().enumerate() {
                     *updated_data.index_mut((updated_data_row_index, c)) = *element.get((0, 0)).unwrap();
@@ -114,43 +201,109 @@ impl Board {
                     updated_data_row_index -= 1;
                 }
             } else {
-                score.lines += 1;
                 cleaned_lines += 1;
             }
         }
 
-        score.score = match cleaned_lines {
-            0 => score.score,
-            1 => score.score + POINTS_FOR_ONE_LINE,
-            2 => score.score + POINTS_FOR_TWO_LINES,
-            3 => score.score + POINTS_FOR_THREE_LINES,
-            _ => score.score + POINTS_FOR_MORE_THAN_THREE_LINES,
-        };
+        self.data = updated_data;
+        cleaned_lines
+    }
+
+    /// Builds a board from rows of block codes, aligned to the bottom of the playfield.
+    pub fn from_rows(rows: &[[u8; 10]]) -> Board {
+        let mut board = Board::new();
+        let first_row = Board::ROWS - rows.len().min(Board::ROWS);
+        for (r, row) in rows.iter().rev().take(Board::ROWS).rev().enumerate() {
+            for (c, code) in row.iter().enumerate() {
+                *board.data.index_mut((first_row + r, c + 1)) = Cell {
+                    code: *code,
+                    locked_at: 0,
+                };
+            }
+        }
+        board
+    }
 
-        score.level = match score.lines {
-            0..=5 => 0,
-            6..=10 => 1,
-            11..=15 => 2,
-            16..=20 => 3,
-            21..=25 => 4,
-            26..=30 => 5,
-            31..=35 => 6,
-            36..=40 => 7,
-            41..=45 => 8,
-            46..=50 => 9,
-            _ => 9,
+    /// The block codes of the playfield without the walls and floor, row by row from the top.
+    pub fn codes(&self) -> Vec<u8> {
+        let (rows, columns) = (self.rows(), self.columns());
+        let mut codes = Vec::with_capacity(rows * columns);
+        for row in 0..rows {
+            for column in 1..=columns {
+                codes.push(self.data.get((row, column)).map_or(0, |cell| cell.code));
+            }
+        }
+        codes
+    }
+
+    /// Builds a board `columns` wide and `rows` high from block codes laid out as `codes` returns
+    /// them. Cells grow on boards with fewer rows than usual, as in big mode.
+    pub fn from_codes(columns: usize, rows: usize, codes: &[u8]) -> Board {
+        let mut board = Board::with_size(columns, rows);
+        let scale = Board::ROWS as f32 / rows.max(1) as f32;
+        board.width *= scale;
+        board.height *= scale;
+        for (index, code) in codes.iter().enumerate().take(rows * columns) {
+            *board.data.index_mut((index / columns, index % columns + 1)) = Cell {
+                code: *code,
+                locked_at: 0,
+            };
+        }
+        board
+    }
+
+    /// Pushes the stack up and fills the bottom `lines` rows with garbage, leaving `hole` open.
+    /// Returns false when blocks were pushed off the top of the playfield.
+    pub fn push_garbage(&mut self, lines: u8, hole: usize, locked_at: u32) -> bool {
+        let columns = self.columns();
+        let rows = self.rows();
+        let lines = usize::from(lines).min(rows);
+        let overflow = (0..lines).any(|r| (1..=columns).any(|c| !self.data.index((r, c)).is_empty()));
+        let garbage = Cell {
+            code: BoardType::GARBAGE_CODE,
+            locked_at,
         };
 
+        let mut updated_data = Board::empty_data(columns, rows);
+        for r in 0..rows {
+            for c in 1..=columns {
+                *updated_data.index_mut((r, c)) = if r + lines < rows {
+                    *self.data.index((r + lines, c))
+                } else if c == hole {
+                    Cell::EMPTY
+                } else {
+                    garbage
+                };
+            }
+        }
         self.data = updated_data;
-        cleaned_lines
+        !overflow
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.rows(0, self.rows()).iter().all(|element| element.is_empty() || *element == Cell::LIMIT)
+    }
+
+    fn empty_data(columns: usize, rows: usize) -> DMatrix<Cell> {
+        DMatrix::from_element(rows, columns, Cell::EMPTY)
+            .insert_row(rows, Cell::LIMIT)
+            .insert_column(0, Cell::LIMIT)
+            .insert_column(columns + 1, Cell::LIMIT)
     }
 }
 
-#[derive(Debug)]
+impl Default for Board {
+    fn default() -> Board {
+        Board::new()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Tetrimino {
     pub kind: TetriminoType,
     pub pos: WorldPoint2,
     pub vectors: [WorldVector2; 3],
+    pub last_move_rotation: bool,
 }
 
 impl Tetrimino {
@@ -159,144 +312,179 @@ impl Tetrimino {
 
     pub fn from(kind: &TetriminoType) -> Tetrimino {
         let pos = WorldPoint2::new(5, TOP_BOUNDARY);
-        match kind {
-            TetriminoType::I => Tetrimino {
-                kind: TetriminoType::I,
-                pos,
-                vectors: [WorldVector2::new(-1, 0), WorldVector2::new(1, 0), WorldVector2::new(2, 0)],
-            },
-            TetriminoType::J => Tetrimino {
-                kind: TetriminoType::J,
-                pos,
-                vectors: [WorldVector2::new(-2, 0), WorldVector2::new(-1, 0), WorldVector2::new(0, 1)],
-            },
-            TetriminoType::L => Tetrimino {
-                kind: TetriminoType::L,
-                pos,
-                vectors: [WorldVector2::new(0, 1), WorldVector2::new(1, 0), WorldVector2::new(2, 0)],
-            },
-            TetriminoType::O => Tetrimino {
-                kind: TetriminoType::O,
-                pos,
-                vectors: [WorldVector2::new(1, 0), WorldVector2::new(0, 1), WorldVector2::new(1, 1)],
-            },
-            TetriminoType::S => Tetrimino {
-                kind: TetriminoType::S,
-                pos,
-                vectors: [WorldVector2::new(-1, 1), WorldVector2::new(0, 1), WorldVector2::new(1, 0)],
-            },
-            TetriminoType::T => Tetrimino {
-                kind: TetriminoType::T,
-                pos,
-                vectors: [WorldVector2::new(-1, 0), WorldVector2::new(0, 1), WorldVector2::new(1, 0)],
-            },
-            TetriminoType::Z => Tetrimino {
-                kind: TetriminoType::Z,
-                pos,
-                vectors: [WorldVector2::new(-1, -1), WorldVector2::new(0, -1), WorldVector2::new(1, 0)],
-            },
+        let vectors = match kind {
+            TetriminoType::I => [WorldVector2::new(-1, 0), WorldVector2::new(1, 0), WorldVector2::new(2, 0)],
+            TetriminoType::J => [WorldVector2::new(-2, 0), WorldVector2::new(-1, 0), WorldVector2::new(0, 1)],
+            TetriminoType::L => [WorldVector2::new(0, 1), WorldVector2::new(1, 0), WorldVector2::new(2, 0)],
+            TetriminoType::O => [WorldVector2::new(1, 0), WorldVector2::new(0, 1), WorldVector2::new(1, 1)],
+            TetriminoType::S => [WorldVector2::new(-1, 1), WorldVector2::new(0, 1), WorldVector2::new(1, 0)],
+            TetriminoType::T => [WorldVector2::new(-1, 0), WorldVector2::new(0, 1), WorldVector2::new(1, 0)],
+            TetriminoType::Z => [WorldVector2::new(-1, -1), WorldVector2::new(0, -1), WorldVector2::new(1, 0)],
+        };
+        Tetrimino {
+            kind: *kind,
+            pos,
+            vectors,
+            last_move_rotation: false,
         }
     }
 
-    pub fn move_left(&mut self, board: &Board) {
-        if self.can_move(Direction::LEFT, board) {
+    /// The centre block followed by the three blocks around it.
+    pub fn cells(&self) -> [WorldPoint2; 4] {
+        [
+            self.pos,
+            self.pos + self.vectors[0],
+            self.pos + self.vectors[1],
+            self.pos + self.vectors[2],
+        ]
+    }
+
+    /// Whether any block of the piece overlaps the stack or the board limits.
+    pub fn collides(&self, board: &Board) -> bool {
+        self.cells().iter().any(|point| !board_position_empty(point.x, point.y, board))
+    }
+
+    /// Moves the piece up until it no longer overlaps the stack, which can happen when the stack
+    /// moves under it. Returns false when it still overlaps at the top of the playfield.
+    pub fn lift_clear_of(&mut self, board: &Board) -> bool {
+        while self.collides(board) && self.pos.y > TOP_BOUNDARY {
+            self.pos.y -= 1;
+        }
+        !self.collides(board)
+    }
+
+    pub fn move_left(&mut self, board: &Board) -> bool {
+        let can_move = self.can_move(Direction::LEFT, board);
+        if can_move {
             self.pos.x += -1;
+            self.last_move_rotation = false;
         }
+        can_move
     }
 
-    pub fn move_right(&mut self, board: &Board) {
-        if self.can_move(Direction::RIGHT, board) {
+    pub fn move_right(&mut self, board: &Board) -> bool {
+        let can_move = self.can_move(Direction::RIGHT, board);
+        if can_move {
             self.pos.x += 1;
+            self.last_move_rotation = false;
         }
+        can_move
     }
 
     pub fn move_down(&mut self, board: &Board) -> bool {
         let can_move = self.can_move(Direction::DOWN, board);
         if can_move {
             self.pos.y += 1;
+            self.last_move_rotation = false;
         }
         can_move
     }
 
-    fn can_move(&mut self, direction: Direction, board: &Board) -> bool {
-        let mut can_move = true;
-        for vector in self.vectors.iter() {
-            match direction {
-                Direction::DOWN => {
-                    if !board_position_empty(self.pos.x + vector.x, self.pos.y + vector.y + 1, board) {
-                        can_move = false;
-                    }
-                }
-                Direction::LEFT => {
-                    if !board_position_empty(self.pos.x + vector.x - 1, self.pos.y + vector.y, board) {
-                        can_move = false;
-                    }
-                }
-                Direction::RIGHT => {
-                    if !board_position_empty(self.pos.x + vector.x + 1, self.pos.y + vector.y, board) {
-                        can_move = false;
-                    }
-                }
-            }
-            if !can_move {
-                break;
-            }
-        }
-        can_move
+    pub fn is_grounded(&self, board: &Board) -> bool {
+        !self.can_move(Direction::DOWN, board)
+    }
+
+    fn can_move(&self, direction: Direction, board: &Board) -> bool {
+        let (x, y) = match direction {
+            Direction::DOWN => (0, 1),
+            Direction::LEFT => (-1, 0),
+            Direction::RIGHT => (1, 0),
+        };
+        self.cells().iter().all(|point| board_position_empty(point.x + x, point.y + y, board))
     }
 
     pub fn rotate_counter_clockwise(&mut self, board: &Board) {
-        let rotation_matrix = Matrix2::from_row_slice(&Tetrimino::COUNTER_CLOCKWISE_MATRIX);
-        let mut new_vectors = ArrayVec::<[WorldVector2; 3]>::new();
-        let mut can_rotate = true;
-
-        for vector in self.vectors.iter() {
-            let result: WorldVector2 = rotation_matrix * vector;
-            if !board_position_empty(self.pos.x + result.x, self.pos.y + result.y, board) {
-                can_rotate = false;
-                break;
+        self.rotate(false, board, &[(0, 0)]);
+    }
+
+    pub fn rotate_clockwise(&mut self, board: &Board) {
+        self.rotate(true, board, &[(0, 0)]);
+    }
+
+    /// Rotates the piece, trying each kick offset in turn until the rotated piece fits.
+    /// Returns false when none of them do.
+    pub fn rotate(&mut self, clockwise: bool, board: &Board, kicks: &[(i8, i8)]) -> bool {
+        let rotation_matrix = if clockwise {
+            Matrix2::from_row_slice(&Tetrimino::CLOCKWISE_MATRIX)
+        } else {
+            Matrix2::from_row_slice(&Tetrimino::COUNTER_CLOCKWISE_MATRIX)
+        };
+        let new_vectors: ArrayVec<[WorldVector2; 3]> = self.vectors.iter()
+            .map(|vector| rotation_matrix * vector)
+            .collect();
+        let rotated = Tetrimino {
+            vectors: new_vectors.into_inner().unwrap(),
+            ..self.clone()
+        };
+
+        for (x, y) in kicks {
+            let mut kicked = rotated.clone();
+            kicked.pos.x += x;
+            kicked.pos.y += y;
+            if !kicked.collides(board) {
+                *self = kicked;
+                self.last_move_rotation = true;
+                return true;
             }
-            new_vectors.push(result);
         }
+        false
+    }
 
-        if can_rotate {
-            self.vectors = new_vectors.into_inner().unwrap();
+    /// Turns a freshly spawned piece upside down, for rules that spawn pieces flat side down.
+    pub fn point_up(&mut self) {
+        for vector in self.vectors.iter_mut() {
+            *vector = -*vector;
         }
     }
 
-    pub fn rotate_clockwise(&mut self, board: &Board) {
-        let rotation_matrix = Matrix2::from_row_slice(&Tetrimino::CLOCKWISE_MATRIX);
-        let mut new_vectors = ArrayVec::<[WorldVector2; 3]>::new();
-        let mut can_rotate = true;
-
-        for vector in self.vectors.iter() {
-            let result = rotation_matrix * vector;
-            if !board_position_empty(self.pos.x + result.x, self.pos.y + result.y, board) {
-                can_rotate = false;
-                break;
-            }
-            new_vectors.push(result);
+    /// Three-corner rule: a T that got into place by rotating, with at least three of the four
+    /// cells diagonal to its centre occupied.
+    pub fn is_t_spin(&self, board: &Board) -> bool {
+        if self.kind != TetriminoType::T || !self.last_move_rotation {
+            return false;
         }
-
-        if can_rotate {
-            self.vectors = new_vectors.into_inner().unwrap();
-        }    }
+        let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+        corners.iter()
+            .filter(|(x, y)| !board_position_empty(self.pos.x + x, self.pos.y + y, board))
+            .count() >= 3
+    }
 }
 
+#[derive(Clone)]
 pub struct ScoreBoard {
-    pub level: u8,
-    pub lines: u8,
+    pub start_level: u16,
+    pub level: u16,
+    pub lines: u16,
     pub score: u32,
 }
 
 impl ScoreBoard {
     pub fn new() -> ScoreBoard {
+        ScoreBoard::with_level(0)
+    }
+
+    pub fn with_level(start_level: u16) -> ScoreBoard {
         ScoreBoard {
-            level: 0,
+            start_level,
+            level: start_level,
             lines: 0,
             score: 0,
         }
     }
+
+    /// Scores a lock at the level the piece was played on, then counts the lines towards the
+    /// next level.
+    pub fn add_lines(&mut self, cleaned_lines: u8, t_spin: bool, rules: &RuleSet) {
+        self.score += rules.points(cleaned_lines, t_spin, self.level);
+        self.lines += u16::from(cleaned_lines);
+        self.level = rules.level(self.start_level, self.lines);
+    }
+}
+
+impl Default for ScoreBoard {
+    fn default() -> ScoreBoard {
+        ScoreBoard::new()
+    }
 }
 
 fn to_matrix_index(x_coordinate: i8, y_coordinate: i8) -> Option<(usize, usize)> {
@@ -320,11 +508,54 @@ fn to_matrix_index(x_coordinate: i8, y_coordinate: i8) -> Option<(usize, usize)>
 fn board_position_empty(x: i8, y: i8, board: &Board) -> bool {
     let mut position_empty = true;
     if let Some(matrix_index) = to_matrix_index(x, y) {
-        if let Some(value) = board.data.get(matrix_index) {
-            if value > &0 {
+        if let Some(cell) = board.data.get(matrix_index) {
+            if !cell.is_empty() {
                 position_empty = false;
             }
         }
     }
     position_empty
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: Cell = Cell { code: BoardType::GARBAGE_CODE, locked_at: 0 };
+
+    #[test]
+    fn placed_pieces_fill_their_cells() {
+        let mut board = Board::new();
+        let mut tetrimino = Tetrimino::from(&TetriminoType::O);
+        while tetrimino.move_down(&board) {}
+        board.place(&tetrimino, 7);
+        for point in tetrimino.cells().iter() {
+            let cell = board.data[(point.y as usize, point.x as usize)];
+            assert_eq!(cell, Cell { code: TetriminoType::O.to_code(), locked_at: 7 });
+        }
+        assert!(board.full_rows().is_empty());
+    }
+
+    #[test]
+    fn full_rows_are_cleared_and_the_stack_drops() {
+        let mut board = Board::new();
+        let rows = board.rows();
+        for column in 1..=board.columns() {
+            board.data[(rows - 1, column)] = BLOCK;
+            board.data[(rows - 3, column)] = BLOCK;
+            if column > 1 {
+                board.data[(rows - 2, column)] = BLOCK;
+            }
+        }
+        board.data[(rows - 4, 3)] = BLOCK;
+        assert_eq!(board.full_rows(), vec![rows - 3, rows - 1]);
+
+        assert_eq!(board.clear_full_rows(), 2);
+        assert!(board.full_rows().is_empty());
+        assert!(board.data[(rows - 1, 1)].is_empty());
+        assert!((2..=board.columns()).all(|column| board.data[(rows - 1, column)] == BLOCK));
+        assert_eq!(board.data[(rows - 2, 3)], BLOCK);
+        assert!((0..rows - 2).all(|row| (1..=board.columns()).all(|column| board.data[(row, column)].is_empty())));
+        assert_eq!(board.clear_full_rows(), 0);
+    }
+}
@@ -0,0 +1,44 @@
+//! Plays a few pieces with the mock bot over the Tetris Bot Protocol, the bot running as its own
+//! process just like a real engine would.
+
+use std::thread;
+use std::time::{Duration, Instant};
+use tetris::controls::Controller;
+use tetris::game::{GameConfig, GamePlayState};
+use tetris::modes::GameMode;
+use tetris::tbp::TbpController;
+
+const PIECES: usize = 5;
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+#[test]
+fn the_mock_bot_places_pieces() {
+    let mut config = GameConfig::new(GameMode::Marathon);
+    config.seed = Some(2024);
+    let mut state = GamePlayState::new(config, None).unwrap();
+    let mut bot = TbpController::new(env!("CARGO_BIN_EXE_tbp_mock_bot")).unwrap();
+
+    let started = Instant::now();
+    let mut locks = Vec::new();
+    while locks.len() < PIECES && !state.game_over {
+        assert!(started.elapsed() < TIMEOUT, "the bot placed {} pieces in time", locks.len());
+        assert!(!bot.failed(), "the bot failed");
+        if let Some(action) = bot.next_action(&state) {
+            state.apply(action);
+        }
+        if bot.thinking() {
+            thread::sleep(Duration::from_millis(1));
+            continue;
+        }
+        state.step();
+        if let Some(lock) = &state.last_lock {
+            if locks.last() != Some(&lock.frame) {
+                locks.push(lock.frame);
+            }
+        }
+    }
+
+    assert!(!state.game_over);
+    assert_eq!(locks.len(), PIECES);
+    assert_eq!(state.board.codes().iter().filter(|code| **code != 0).count(), 4 * PIECES);
+}